@@ -0,0 +1,731 @@
+use anyhow::{Result, anyhow};
+   use chrono::{Duration, Utc};
+   use clap::{Args, Parser, Subcommand, ValueEnum};
+   use serde::Serialize;
+   use std::io::Write;
+   use std::path::PathBuf;
+   use std::time::Duration as StdDuration;
+   use tokio::sync::watch;
+   use ethers::prelude::{BlockNumber, Middleware};
+   use crate::config::Config;
+   use crate::daemon::{self, CycleTrigger, DaemonSummary, ScanTrigger};
+   use crate::database::{Database, DatabaseStats, OpportunityFilter, PairStats, StoredEndpointHealth, StoredOpportunity};
+   use crate::modules::amounts::{self, Decimal};
+   use crate::modules::mempool::{MempoolWatcher, PendingTransactions};
+   use crate::modules::new_heads::NewHeads;
+   use crate::modules::pool_discovery::{DiscoveredPool, PoolDiscovery};
+   use crate::modules::price_fetcher::{self, BlockRef};
+   use crate::modules::price_source::PriceSourceRegistry;
+   use crate::scanner::{ScanReport, Scanner};
+
+   #[derive(Debug, Parser)]
+   #[command(name = "polygon_arbitrage_bot", version, about = "Polygon arbitrage opportunity detector")]
+   pub struct Cli {
+       /// Config file (default: CONFIG_PATH, then ./config.toml)
+       #[arg(long, short, global = true)]
+       pub config: Option<PathBuf>,
+
+       /// Config profile, e.g. mainnet or local (default: CONFIG_PROFILE, then the file's)
+       #[arg(long, short, global = true)]
+       pub profile: Option<String>,
+
+       /// Output format; json and csv keep stdout machine-readable
+       #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Text)]
+       pub format: OutputFormat,
+
+       /// MySQL URL, overriding the config and DATABASE_URL
+       #[arg(long, global = true)]
+       pub database_url: Option<String>,
+
+       #[command(subcommand)]
+       pub command: Command,
+   }
+
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+   pub enum OutputFormat {
+       Text,
+       Json,
+       Csv,
+   }
+
+   #[derive(Debug, Subcommand)]
+   pub enum Command {
+       /// Run one detection cycle
+       Scan,
+       /// Run detection cycles until SIGINT/SIGTERM, then print a summary
+       #[command(visible_alias = "daemon")]
+       Watch {
+           /// Seconds between cycles (default: the config's scan_interval)
+           #[arg(long, short, conflicts_with = "on_block", value_parser = clap::value_parser!(u64).range(1..))]
+           interval: Option<u64>,
+           /// Scan on each new block instead of a timer
+           #[arg(long)]
+           on_block: bool,
+           /// Stop after this many cycles
+           #[arg(long)]
+           cycles: Option<u64>,
+       },
+       /// Project pending router swaps onto current prices and report the arbitrage they open
+       Mempool {
+           /// Stop after this many pending swaps
+           #[arg(long)]
+           limit: Option<u64>,
+       },
+       /// List stored opportunities, newest first
+       History {
+           #[command(flatten)]
+           filter: FilterArgs,
+       },
+       /// Summarise stored opportunities
+       Stats {
+           /// Window for the per-pair breakdown, in hours
+           #[arg(long, default_value_t = 24)]
+           hours: u32,
+       },
+       /// Write stored opportunities as CSV (the default) or JSON
+       Export {
+           #[command(flatten)]
+           filter: FilterArgs,
+           /// File to write instead of stdout
+           #[arg(long, short)]
+           output: Option<PathBuf>,
+       },
+       /// Inspect and toggle pools found by discovery
+       Pools {
+           #[command(subcommand)]
+           action: PoolsCommand,
+       },
+   }
+
+   #[derive(Debug, Subcommand)]
+   pub enum PoolsCommand {
+       /// List discovered pools, oldest first
+       List {
+           /// Only this pair, e.g. WETH/USDC
+           #[arg(long)]
+           pair: Option<String>,
+           /// Only pools on this DEX
+           #[arg(long)]
+           dex: Option<String>,
+       },
+       /// Search the factories' creation logs once and store what qualifies
+       Discover {
+           /// Blocks behind the head to search (default: the config's discovery_backfill_blocks)
+           #[arg(long)]
+           blocks: Option<u64>,
+       },
+       /// Monitor a discovered pool again
+       Enable {
+           pool: String,
+       },
+       /// Stop monitoring a discovered pool
+       Disable {
+           pool: String,
+       },
+   }
+
+   #[derive(Debug, Clone, Default, Args)]
+   pub struct FilterArgs {
+       /// Only this pair, e.g. WETH/USDC
+       #[arg(long)]
+       pub pair: Option<String>,
+       /// Only trades buying or selling on this DEX
+       #[arg(long)]
+       pub dex: Option<String>,
+       /// Minimum profit after fees, in percent
+       #[arg(long)]
+       pub min_profit: Option<f64>,
+       /// Only opportunities from the last N hours
+       #[arg(long)]
+       pub hours: Option<u32>,
+       /// Maximum rows (history defaults to 20, export to all)
+       #[arg(long)]
+       pub limit: Option<i32>,
+       /// Also list opportunities from blocks that were reorged out
+       #[arg(long)]
+       pub include_reorged: bool,
+   }
+
+   impl FilterArgs {
+       pub fn to_filter(&self, default_limit: Option<i32>) -> OpportunityFilter {
+           OpportunityFilter {
+               token_pair: self.pair.clone(),
+               dex: self.dex.clone(),
+               min_profit: self.min_profit.map(|percent| amounts::decimal(percent) / Decimal::ONE_HUNDRED),
+               since: self.hours.map(|hours| Utc::now() - Duration::hours(hours.into())),
+               limit: self.limit.or(default_limit),
+               include_reorged: self.include_reorged,
+           }
+       }
+   }
+
+   /// `stats` output: the overall summary plus the per-pair breakdown.
+   #[derive(Debug, Serialize)]
+   pub struct StatsReport {
+       pub hours: u32,
+       #[serde(flatten)]
+       pub summary: DatabaseStats,
+       pub pairs: Vec<PairStats>,
+       pub endpoints: Vec<StoredEndpointHealth>, // As last recorded by a running bot
+   }
+
+   pub async fn run(cli: Cli) -> Result<()> {
+       let mut config = Config::load(cli.config.as_deref(), cli.profile.as_deref())?;
+       if cli.database_url.is_some() {
+           config.database_url = cli.database_url.clone();
+       }
+
+       match &cli.command {
+           Command::Scan => scan(&config, cli.format, Some(1)).await,
+           Command::Watch { interval, on_block, cycles } => {
+               if let Some(interval) = interval {
+                   config.scan_trigger = ScanTrigger::Interval;
+                   config.scan_interval = *interval;
+               }
+               if *on_block {
+                   config.scan_trigger = ScanTrigger::Block;
+               }
+               scan(&config, cli.format, *cycles).await
+           }
+           Command::Mempool { limit } => mempool(&config, cli.format, *limit).await,
+           Command::History { filter } => {
+               let database = connect(&config).await?;
+               let opportunities = database.get_opportunities(&filter.to_filter(Some(20))).await?;
+               write_opportunities(&mut std::io::stdout().lock(), cli.format, &opportunities)
+           }
+           Command::Stats { hours } => {
+               let database = connect(&config).await?;
+               let report = StatsReport {
+                   hours: *hours,
+                   summary: database.get_stats().await?,
+                   pairs: database.get_pair_stats(Utc::now() - Duration::hours((*hours).into())).await?,
+                   endpoints: database.get_endpoint_health().await?,
+               };
+               write_stats(&mut std::io::stdout().lock(), cli.format, &report)
+           }
+           Command::Export { filter, output } => {
+               let database = connect(&config).await?;
+               let opportunities = database.get_opportunities(&filter.to_filter(None)).await?;
+               let format = match cli.format {
+                   OutputFormat::Text => OutputFormat::Csv,
+                   format => format,
+               };
+               match output {
+                   Some(path) => {
+                       let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                       write_opportunities(&mut file, format, &opportunities)?;
+                       file.flush()?;
+                       eprintln!("📝 Exported {} opportunities to {}", opportunities.len(), path.display());
+                       Ok(())
+                   }
+                   None => write_opportunities(&mut std::io::stdout().lock(), format, &opportunities),
+               }
+           }
+           Command::Pools { action } => pools(&config, cli.format, action).await,
+       }
+   }
+
+   async fn connect(config: &Config) -> Result<Database> {
+       let url = config
+           .database_url
+           .as_deref()
+           .ok_or_else(|| anyhow!("No database configured: set database_url, DATABASE_URL or --database-url"))?;
+       Database::new(url).await
+   }
+
+   /// Runs `cycles` detection cycles (until SIGINT/SIGTERM when None) on the
+   /// config's trigger. A failed cycle is reported and the next one runs.
+   async fn scan(config: &Config, format: OutputFormat, cycles: Option<u64>) -> Result<()> {
+       let text = format == OutputFormat::Text;
+       if text {
+           println!("🚀 Polygon Arbitrage Opportunity Detector Bot");
+           println!("============================================");
+           print_config(config);
+       }
+
+       let database = match &config.database_url {
+           Some(_) => Some(connect(config).await?),
+           None => {
+               eprintln!("⚠️  No database configured; opportunities won't be stored");
+               None
+           }
+       };
+       if text && database.is_some() {
+           println!("✅ Database connected");
+       }
+
+       let scanner = Scanner::from_config(config, text).await?;
+       let mut trigger = match config.scan_trigger {
+           ScanTrigger::Interval => CycleTrigger::interval(StdDuration::from_secs(config.scan_interval)),
+           ScanTrigger::Block if config.ws_url.is_some() => {
+               CycleTrigger::new_heads(NewHeads::subscribe(config.ws_url.as_deref().unwrap_or_default()))
+           }
+           ScanTrigger::Block => CycleTrigger::per_block(
+               scanner
+                   .price_fetcher()
+                   .registry()
+                   .client()
+                   .ok_or_else(|| anyhow!("Scanning per block needs an RPC client"))?,
+           ),
+       };
+       if text {
+           if let Some(tokens) = scanner.price_fetcher().registry().tokens() {
+               println!("✅ Token metadata loaded for {} tokens", tokens.len());
+           }
+           println!("✅ All components initialized");
+           println!("\n📈 Starting price monitoring...");
+       }
+
+       let mut shutdown = if cycles == Some(1) { watch::channel(false).1 } else { daemon::shutdown_signal() };
+       let mut summary = DaemonSummary::new();
+       let mut cycle = 0;
+       // Per-block scans wait for the first block; timed ones start right away
+       let mut due = config.scan_trigger == ScanTrigger::Interval;
+       while cycles.is_none_or(|cycles| cycle < cycles) {
+           let mut block = None;
+           if !due {
+               match trigger.wait(&mut shutdown).await {
+                   Ok(Some(tick)) => {
+                       summary.record_skipped_blocks(tick.skipped);
+                       block = tick.block;
+                   }
+                   Ok(None) => break,
+                   Err(e) => {
+                       eprintln!("⚠️  Could not wait for the next cycle: {}", e);
+                       tokio::time::sleep(StdDuration::from_secs(1)).await;
+                       continue;
+                   }
+               }
+           }
+           due = false;
+
+           cycle += 1;
+           if text {
+               match block {
+                   Some(block) => println!("\n🔄 Monitoring Cycle #{} at block #{}", cycle, block.number),
+                   None => println!("\n🔄 Monitoring Cycle #{}", cycle),
+               }
+               println!("{}", "-".repeat(50));
+           }
+
+           match scanner.scan_at(database.as_ref(), block).await {
+               Ok(report) => {
+                   summary.record(&report);
+                   match format {
+                       OutputFormat::Text => scanner.print_report(&report),
+                       OutputFormat::Json => println!("{}", serde_json::to_string(&report)?),
+                       OutputFormat::Csv => write_report_csv(&mut std::io::stdout().lock(), &report, cycle == 1)?,
+                   }
+                   // CSV rows can't carry it, so the event goes to stderr
+                   if let (OutputFormat::Csv, Some(reorg)) = (format, &report.reorg) {
+                       eprintln!("{}", serde_json::to_string(reorg)?);
+                   }
+               }
+               // A one-off scan has nothing to retry, so its failure is the command's
+               Err(e) if cycles == Some(1) => return Err(e),
+               Err(e) => {
+                   summary.record_failure();
+                   eprintln!("❌ Cycle #{} failed: {}", cycle, e);
+               }
+           }
+
+           // Recorded after failed cycles too: those are when endpoints struggle
+           if let (Some(database), Some(pool)) = (&database, scanner.price_fetcher().registry().rpc_pool()) {
+               if let Err(e) = database.store_endpoint_health(&pool.health()).await {
+                   eprintln!("⚠️  Could not record RPC endpoint health: {}", e);
+               }
+           }
+
+           if *shutdown.borrow() {
+               break;
+           }
+           if text && cycles.is_none_or(|cycles| cycle < cycles) {
+               match config.scan_trigger {
+                   ScanTrigger::Interval => println!("\n⏳ Waiting {} seconds before next check...", config.scan_interval),
+                   ScanTrigger::Block => println!("\n⏳ Waiting for the next block..."),
+               }
+           }
+       }
+
+       summary.reconnects = trigger.reconnects();
+       if let Some(pool) = scanner.price_fetcher().registry().rpc_pool() {
+           summary.endpoints = pool.health();
+       }
+       if let Some(database) = &database {
+           if text {
+               println!("\n📊 Database Statistics:");
+               print_summary(&database.get_stats().await?);
+           }
+           // Let in-flight writes land before exiting
+           database.close().await;
+       }
+       if text {
+           if cycles != Some(1) {
+               summary.print();
+           }
+           println!("\n🏁 Monitoring complete!");
+           if database.is_some() {
+               println!("📝 All opportunities stored in database!");
+           }
+       } else if cycles != Some(1) {
+           eprintln!("{}", serde_json::to_string(&summary)?);
+       }
+
+       Ok(())
+   }
+
+   /// Follows pending transactions and new heads over the config's ws_url,
+   /// evaluating each pending swap against prices kept at the latest head.
+   async fn mempool(config: &Config, format: OutputFormat, limit: Option<u64>) -> Result<()> {
+       if format == OutputFormat::Csv {
+           return Err(anyhow!("Mempool reports have no CSV form; use --format json"));
+       }
+       let ws_url = config
+           .ws_url
+           .as_deref()
+           .ok_or_else(|| anyhow!("Watching the mempool needs a ws_url"))?;
+       // Prices move with every head, so keep them current from logs
+       let config = Config {
+           pool_cache: true,
+           ..config.clone()
+       };
+       let text = format == OutputFormat::Text;
+
+       let scanner = Scanner::from_config(&config, false).await?;
+       let watcher = MempoolWatcher::from_config(&config)?;
+       let heads = NewHeads::subscribe(ws_url);
+       let mut new_heads = heads.receiver();
+       let mut pending = PendingTransactions::subscribe(ws_url);
+       let mut prices = scanner.current_prices(None).await?;
+       if text {
+           println!("👀 Watching pending transactions to {} routers against {} quotes", config.dexes.len(), prices.len());
+       }
+
+       let mut shutdown = daemon::shutdown_signal();
+       let mut reports = 0;
+       while limit.is_none_or(|limit| reports < limit) {
+           tokio::select! {
+               _ = shutdown.changed() => break,
+               Ok(()) = new_heads.changed() => {
+                   let head = *new_heads.borrow_and_update();
+                   match scanner.current_prices(head).await {
+                       Ok(current) => prices = current,
+                       Err(e) => eprintln!("⚠️  Could not refresh prices: {}", e),
+                   }
+               }
+               tx = pending.next() => {
+                   let Some(tx) = tx else {
+                       break;
+                   };
+                   match watcher.evaluate(&prices, &tx) {
+                       Ok(Some(report)) => {
+                           reports += 1;
+                           match format {
+                               OutputFormat::Json => println!("{}", serde_json::to_string(&report)?),
+                               _ => watcher.print_report(&report),
+                           }
+                       }
+                       Ok(None) => {}
+                       Err(e) => eprintln!("⚠️  Could not evaluate pending tx {:?}: {}", tx.hash, e),
+                   }
+               }
+           }
+       }
+
+       if text {
+           println!(
+               "\n🏁 Evaluated {} pending swaps ({} dropped, {} reconnects)",
+               reports,
+               pending.dropped(),
+               pending.reconnects() + heads.reconnects()
+           );
+       }
+       Ok(())
+   }
+
+   async fn pools(config: &Config, format: OutputFormat, action: &PoolsCommand) -> Result<()> {
+       match action {
+           PoolsCommand::List { pair, dex } => {
+               let database = connect(config).await?;
+               let mut pools = database.get_discovered_pools().await?;
+               pools.retain(|pool| {
+                   pair.as_ref().is_none_or(|pair| pool.token_pair() == *pair) && dex.as_ref().is_none_or(|dex| pool.dex == *dex)
+               });
+               write_pools(&mut std::io::stdout().lock(), format, &pools)
+           }
+           PoolsCommand::Discover { blocks } => {
+               let registry = PriceSourceRegistry::from_config(config)?;
+               let (Some(client), Some(tokens)) = (registry.client(), registry.tokens()) else {
+                   return Err(anyhow!("Pool discovery needs an RPC-backed DEX"));
+               };
+               let head = client
+                   .get_block(BlockNumber::Latest)
+                   .await?
+                   .ok_or_else(|| anyhow!("RPC returned no head block"))?;
+               let mut discovery = PoolDiscovery::from_config(client, tokens, config)?
+                   .with_backfill(blocks.unwrap_or(config.discovery_backfill_blocks));
+               let report = discovery.scan_to(BlockRef::from_block(&head)?).await?;
+
+               if config.database_url.is_some() {
+                   let database = connect(config).await?;
+                   let stored = database.store_discovered_pools(&report.pools).await?;
+                   database.close().await;
+                   eprintln!("💾 Stored {} discovered pools", stored);
+               }
+               if format == OutputFormat::Text {
+                   println!(
+                       "🔍 Blocks #{}-#{}: {} pools created, {} between whitelisted tokens too shallow",
+                       report.from_block, report.to_block, report.created, report.too_shallow
+                   );
+               }
+               write_pools(&mut std::io::stdout().lock(), format, &report.pools)
+           }
+           PoolsCommand::Enable { pool } | PoolsCommand::Disable { pool } => {
+               let enabled = matches!(action, PoolsCommand::Enable { .. });
+               let address = price_fetcher::parse_address(pool)?;
+               let database = connect(config).await?;
+               if !database.set_pool_enabled(address, enabled).await? {
+                   return Err(anyhow!("{} is not a discovered pool", pool));
+               }
+               println!("{} {:?}", if enabled { "✅ Enabled" } else { "⏸️  Disabled" }, address);
+               Ok(())
+           }
+       }
+   }
+
+   fn print_config(config: &Config) {
+       println!("✅ Configuration loaded:");
+       println!("  - Profile: {}", config.profile);
+       match config.endpoints().as_slice() {
+           [endpoint] => println!("  - RPC URL: {}", endpoint.url),
+           endpoints => println!("  - RPC Endpoints: {} (first {})", endpoints.len(), config.rpc_url()),
+       }
+       if let Some(ws_url) = &config.ws_url {
+           println!("  - WebSocket URL: {}", ws_url);
+       }
+       println!("  - Min Profit Threshold: {:.2}%", config.min_profit_threshold * 100.0);
+       println!("  - Max Block Lag: {} blocks", config.max_block_lag);
+       println!("  - Max Cycle Hops: {}", config.max_cycle_hops);
+       println!("  - Priority Fee Percentile: {}", config.priority_fee_percentile);
+       println!("  - Trade Size: ${:.2}", config.trade_size);
+       if let Some(max_trade_size) = config.max_trade_size {
+           println!("  - Max Trade Size: ${:.2}", max_trade_size);
+       }
+       println!("  - Reserve Tolerance: {:.2}%", config.reserve_tolerance * 100.0);
+       println!("  - Gas Estimate Sender: {}", config.gas_estimate_sender.as_deref().unwrap_or("none (gas model only)"));
+       println!("  - MATIC Price Feed: {}", config.native_price_feed.as_deref().unwrap_or("none (pool only)"));
+       println!("  - Token Pairs: {}", config.token_pairs.len());
+       println!("  - DEXes: {}", config.dexes.len());
+       if config.discovery {
+           println!("  - Pool Discovery: {} whitelisted tokens", config.discovery_tokens.len());
+       }
+   }
+
+   fn print_summary(stats: &DatabaseStats) {
+       println!("  - Total opportunities stored: {}", stats.total_opportunities);
+       if stats.reorged_opportunities > 0 {
+           println!("  - Reorged out: {}", stats.reorged_opportunities);
+       }
+       println!("  - Average daily profit: {:.3}%", stats.avg_daily_profit * Decimal::ONE_HUNDRED);
+       if let Some((pair, profit)) = &stats.best_daily_pair {
+           println!("  - Best daily pair: {} ({:.3}%)", pair, *profit * Decimal::ONE_HUNDRED);
+       }
+   }
+
+   const OPPORTUNITY_COLUMNS: [&str; 18] = [
+       "id", "created_at", "token_pair", "buy_dex", "sell_dex", "buy_price", "sell_price",
+       "raw_spread", "profit_percentage", "profit_usd", "trade_size", "amount_in", "net_profit",
+       "gas_costs", "block_number", "block_hash", "native_price_usd", "reorged",
+   ];
+
+   pub fn write_opportunities(out: &mut dyn Write, format: OutputFormat, opportunities: &[StoredOpportunity]) -> Result<()> {
+       match format {
+           OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(opportunities)?)?,
+           OutputFormat::Csv => {
+               write_csv_row(out, OPPORTUNITY_COLUMNS)?;
+               for opp in opportunities {
+                   write_csv_row(out, [
+                       opp.id.to_string(),
+                       opp.created_at.to_rfc3339(),
+                       opp.token_pair.clone(),
+                       opp.buy_dex.clone(),
+                       opp.sell_dex.clone(),
+                       opp.buy_price.to_string(),
+                       opp.sell_price.to_string(),
+                       optional(opp.raw_spread),
+                       opp.profit_percentage.to_string(),
+                       opp.profit_usd.to_string(),
+                       opp.trade_size.to_string(),
+                       optional(opp.amount_in),
+                       optional(opp.net_profit),
+                       optional(opp.gas_costs),
+                       optional(opp.block_number),
+                       opp.block_hash.clone().unwrap_or_default(),
+                       optional(opp.native_price_usd),
+                       opp.reorged.to_string(),
+                   ])?;
+               }
+           }
+           OutputFormat::Text => {
+               if opportunities.is_empty() {
+                   writeln!(out, "❌ No stored opportunities match")?;
+               }
+               for opp in opportunities {
+                   writeln!(
+                       out,
+                       "#{:<6} {}  {:<12} {:>10} -> {:<10} {:>7.3}%  ${:>9.2}  net {}  block {}{}",
+                       opp.id,
+                       opp.created_at.format("%Y-%m-%d %H:%M:%S"),
+                       opp.token_pair,
+                       opp.buy_dex,
+                       opp.sell_dex,
+                       opp.profit_percentage * Decimal::ONE_HUNDRED,
+                       opp.profit_usd,
+                       opp.net_profit.map_or("-".to_string(), |net| format!("${:.2}", net)),
+                       optional(opp.block_number),
+                       if opp.reorged { " (reorged)" } else { "" },
+                   )?;
+               }
+           }
+       }
+       Ok(())
+   }
+
+   pub fn write_stats(out: &mut dyn Write, format: OutputFormat, report: &StatsReport) -> Result<()> {
+       match format {
+           OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(report)?)?,
+           OutputFormat::Csv => {
+               write_csv_row(out, ["token_pair", "opportunities", "avg_profit", "max_profit", "total_profit_usd", "total_net_profit"])?;
+               for pair in &report.pairs {
+                   write_csv_row(out, [
+                       pair.token_pair.clone(),
+                       pair.opportunities.to_string(),
+                       pair.avg_profit.to_string(),
+                       pair.max_profit.to_string(),
+                       pair.total_profit_usd.to_string(),
+                       optional(pair.total_net_profit),
+                   ])?;
+               }
+           }
+           OutputFormat::Text => {
+               let stats = &report.summary;
+               writeln!(out, "📊 Database Statistics:")?;
+               writeln!(out, "  - Total opportunities stored: {}", stats.total_opportunities)?;
+               if stats.reorged_opportunities > 0 {
+                   writeln!(out, "  - Reorged out: {}", stats.reorged_opportunities)?;
+               }
+               writeln!(out, "  - Average daily profit: {:.3}%", stats.avg_daily_profit * Decimal::ONE_HUNDRED)?;
+               if let Some((pair, profit)) = &stats.best_daily_pair {
+                   writeln!(out, "  - Best daily pair: {} ({:.3}%)", pair, *profit * Decimal::ONE_HUNDRED)?;
+               }
+               writeln!(out, "\n📈 Last {} hours by pair:", report.hours)?;
+               for pair in &report.pairs {
+                   writeln!(
+                       out,
+                       "  {:<12} {:>5} found  avg {:.3}%  max {:.3}%  quoted ${:.2}  net {}",
+                       pair.token_pair,
+                       pair.opportunities,
+                       pair.avg_profit * Decimal::ONE_HUNDRED,
+                       pair.max_profit * Decimal::ONE_HUNDRED,
+                       pair.total_profit_usd,
+                       pair.total_net_profit.map_or("-".to_string(), |net| format!("${:.2}", net)),
+                   )?;
+               }
+               if !report.endpoints.is_empty() {
+                   writeln!(out, "\n🌐 RPC endpoints:")?;
+                   for endpoint in &report.endpoints {
+                       writeln!(out, "  {}  (as of {})", endpoint.health.summary(), endpoint.updated_at.format("%Y-%m-%d %H:%M:%S"))?;
+                   }
+               }
+           }
+       }
+       Ok(())
+   }
+
+   pub fn write_pools(out: &mut dyn Write, format: OutputFormat, pools: &[DiscoveredPool]) -> Result<()> {
+       match format {
+           OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(pools)?)?,
+           OutputFormat::Csv => {
+               write_csv_row(out, ["pool", "dex", "fee", "token_pair", "created_block", "base_balance", "quote_balance", "enabled"])?;
+               for pool in pools {
+                   write_csv_row(out, [
+                       format!("{:?}", pool.pool),
+                       pool.dex.clone(),
+                       optional(pool.fee),
+                       pool.token_pair(),
+                       pool.created_block.to_string(),
+                       pool.base_balance.to_string(),
+                       pool.quote_balance.to_string(),
+                       pool.enabled.to_string(),
+                   ])?;
+               }
+           }
+           OutputFormat::Text => {
+               if pools.is_empty() {
+                   writeln!(out, "❌ No discovered pools match")?;
+               }
+               for pool in pools {
+                   writeln!(
+                       out,
+                       "{:?}  {:<12} {:<18} block {:<10} {} {} / {} {}{}",
+                       pool.pool,
+                       pool.token_pair(),
+                       pool.market(),
+                       pool.created_block,
+                       pool.base_balance.round_dp(4),
+                       pool.pair.base,
+                       pool.quote_balance.round_dp(4),
+                       pool.pair.quote,
+                       if pool.enabled { "" } else { " (disabled)" },
+                   )?;
+               }
+           }
+       }
+       Ok(())
+   }
+
+   /// One CSV row per pair-wise opportunity found in the cycle.
+   fn write_report_csv(out: &mut dyn Write, report: &ScanReport, header: bool) -> Result<()> {
+       if header {
+           write_csv_row(out, [
+               "block_number", "token_pair", "buy_dex", "sell_dex", "buy_price", "sell_price",
+               "profit_percentage", "profit_usd", "trade_size", "net_profit", "stored_id",
+           ])?;
+       }
+       for analyzed in &report.opportunities {
+           let opp = &analyzed.opportunity;
+           write_csv_row(out, [
+               optional(opp.block.map(|block| block.number)),
+               opp.token_pair.clone(),
+               opp.buy_dex.clone(),
+               opp.sell_dex.clone(),
+               opp.buy_price.to_string(),
+               opp.sell_price.to_string(),
+               opp.profit_percentage.to_string(),
+               opp.profit_usd.to_string(),
+               opp.trade_size.to_string(),
+               analyzed.analysis.net_profit.to_string(),
+               optional(analyzed.stored_id),
+           ])?;
+       }
+       Ok(())
+   }
+
+   fn optional<T: ToString>(value: Option<T>) -> String {
+       value.map(|value| value.to_string()).unwrap_or_default()
+   }
+
+   fn write_csv_row<S: AsRef<str>>(out: &mut dyn Write, fields: impl IntoIterator<Item = S>) -> Result<()> {
+       let fields: Vec<String> = fields.into_iter().map(|field| csv_field(field.as_ref())).collect();
+       writeln!(out, "{}", fields.join(","))?;
+       Ok(())
+   }
+
+   // RFC 4180: quote fields holding separators, quotes or line breaks
+   fn csv_field(field: &str) -> String {
+       if field.contains([',', '"', '\n', '\r']) {
+           format!("\"{}\"", field.replace('"', "\"\""))
+       } else {
+           field.to_string()
+       }
+   }
\ No newline at end of file
@@ -1,13 +1,43 @@
 use serde::{Deserialize, Serialize};
-   use std::collections::HashMap;
-   use anyhow::Result;
+   use anyhow::{Result, anyhow};
+   use std::collections::HashSet;
+   use std::fmt::Display;
+   use std::path::Path;
+   use std::str::FromStr;
+   use crate::daemon::ScanTrigger;
+   use crate::modules::native_price::MATIC_USD_FEED;
+   use crate::modules::price_source::DexKind;
+   use crate::modules::token_registry;
+
+   pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+   pub const DEFAULT_PROFILE: &str = "mainnet";
 
    #[derive(Debug, Clone, Serialize, Deserialize)]
    pub struct Config {
-       pub polygon_rpc_url: String,
+       pub profile: String,
+       pub rpc_urls: Vec<String>, // Endpoints sharing reads equally, unless rpc_endpoints is set
+       pub rpc_endpoints: Vec<RpcEndpoint>, // Weighted, rate-limited endpoints replacing rpc_urls
+       pub ws_url: Option<String>, // newHeads subscription for per-block scans
+       pub database_url: Option<String>,
        pub min_profit_threshold: f64,
+       pub max_block_lag: u64,
+       pub max_cycle_hops: usize,
+       pub priority_fee_percentile: f64,
+       pub native_price_feed: Option<String>, // Chainlink MATIC/USD aggregator
+       pub native_price_max_age: u64, // seconds
+       pub gas_estimate_sender: Option<String>, // Account swaps are simulated from
+       pub reserve_tolerance: f64, // Reserve movement allowed between detection and inclusion
+       pub trade_size: f64, // USD, for trades that can't be sized against pool depth
+       pub max_trade_size: Option<f64>, // USD cap on sized trades
+       pub scan_trigger: ScanTrigger,
+       pub scan_interval: u64, // seconds between cycles on the interval trigger
+       pub pool_cache: bool, // Serve prices from pool state kept current by logs
+       pub reorg_depth: u64, // Recent blocks tracked for detecting and rolling back reorgs
+       pub discovery: bool, // Monitor pools found in factory creation logs too
+       pub discovery_backfill_blocks: u64, // History scanned for created pools on the first cycle
+       pub discovery_tokens: Vec<DiscoveryToken>, // Whitelist; earlier tokens are preferred as quote
        pub token_pairs: Vec<TokenPair>,
-       pub dex_contracts: HashMap<String, String>,
+       pub dexes: Vec<DexConfig>,
    }
 
    #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,20 +48,445 @@ use serde::{Deserialize, Serialize};
        pub quote_address: String,
    }
 
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   pub struct DexConfig {
+       pub name: String,
+       pub kind: DexKind,
+       pub router: String,
+       pub factory: Option<String>,
+       // Swap fee for constant-product forks that don't charge 0.3%;
+       // V3 pools take theirs from the fee tier
+       pub fee: Option<f64>,
+   }
+
+   /// An RPC endpoint of the pool reads are spread over.
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   #[serde(deny_unknown_fields)]
+   pub struct RpcEndpoint {
+       pub url: String,
+       #[serde(default = "default_weight")]
+       pub weight: f64, // Share of the reads relative to the other endpoints
+       #[serde(default)]
+       pub max_rps: Option<f64>, // Requests per second the endpoint allows us
+   }
+
+   fn default_weight() -> f64 {
+       1.0
+   }
+
+   /// A token discovered pools may trade, and the balance of it a pool must
+   /// hold to be monitored.
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   #[serde(deny_unknown_fields)]
+   pub struct DiscoveryToken {
+       pub symbol: String,
+       pub address: String,
+       #[serde(default)]
+       pub min_liquidity: f64, // Whole tokens
+   }
+
+   /// Keys a config file may set, at the top level or inside a profile.
+   /// Anything left out keeps its default.
+   #[derive(Debug, Default, Deserialize)]
+   #[serde(deny_unknown_fields)]
+   struct FileConfig {
+       rpc_urls: Option<Vec<String>>,
+       rpc_endpoints: Option<Vec<RpcEndpoint>>,
+       ws_url: Option<String>,
+       database_url: Option<String>,
+       min_profit_threshold: Option<f64>,
+       max_block_lag: Option<u64>,
+       max_cycle_hops: Option<usize>,
+       priority_fee_percentile: Option<f64>,
+       native_price_feed: Option<String>,
+       native_price_max_age: Option<u64>,
+       gas_estimate_sender: Option<String>,
+       reserve_tolerance: Option<f64>,
+       trade_size: Option<f64>,
+       max_trade_size: Option<f64>,
+       scan_trigger: Option<ScanTrigger>,
+       scan_interval: Option<u64>,
+       pool_cache: Option<bool>,
+       reorg_depth: Option<u64>,
+       discovery: Option<bool>,
+       discovery_backfill_blocks: Option<u64>,
+       discovery_tokens: Option<Vec<DiscoveryToken>>,
+       token_pairs: Option<Vec<TokenPair>>,
+       dexes: Option<Vec<DexConfig>>,
+   }
+
+   impl Default for Config {
+       /// Polygon mainnet with the built-in pairs and DEXes.
+       fn default() -> Self {
+           Self {
+               profile: DEFAULT_PROFILE.to_string(),
+               rpc_urls: vec!["https://polygon-rpc.com".to_string()],
+               rpc_endpoints: Vec::new(),
+               ws_url: None,
+               database_url: None,
+               min_profit_threshold: 0.01,
+               max_block_lag: 0,
+               max_cycle_hops: 4,
+               priority_fee_percentile: 50.0,
+               native_price_feed: Some(MATIC_USD_FEED.to_string()),
+               native_price_max_age: 3600,
+               gas_estimate_sender: None,
+               reserve_tolerance: 0.001,
+               trade_size: 1000.0,
+               max_trade_size: None,
+               scan_trigger: ScanTrigger::Interval,
+               scan_interval: 5,
+               pool_cache: false,
+               reorg_depth: 64,
+               discovery: false,
+               discovery_backfill_blocks: 100_000,
+               discovery_tokens: Self::default_discovery_tokens(),
+               token_pairs: Self::default_token_pairs(),
+               dexes: Self::default_dexes(),
+           }
+       }
+   }
+
    impl Config {
+       /// Built-in defaults with environment overrides, for running without a file.
        pub fn new() -> Result<Self> {
-           let config = Config {
-               polygon_rpc_url: std::env::var("POLYGON_RPC_URL")
-                   .unwrap_or_else(|_| "https://polygon-rpc.com".to_string()),
-               min_profit_threshold: std::env::var("MIN_PROFIT_THRESHOLD")
-                   .unwrap_or_else(|_| "0.01".to_string())
-                   .parse()
-                   .unwrap_or(0.01),
-               token_pairs: Self::default_token_pairs(),
-               dex_contracts: Self::default_dex_contracts(),
+           let mut errors = Vec::new();
+           let mut config = Self::default();
+           config.apply_env(&env_var, &mut errors);
+           config.validate(&mut errors);
+           into_result(config, errors)
+       }
+
+       /// Reads `path` (CONFIG_PATH, then ./config.toml, when None) with the
+       /// given profile (CONFIG_PROFILE, then the file's `profile` key, when
+       /// None). Environment variables override the file. Without a file at the
+       /// default path the built-in defaults are used.
+       pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+           let explicit = path.map(Path::to_path_buf).or_else(|| env_var("CONFIG_PATH").map(Into::into));
+           let path = explicit.clone().unwrap_or_else(|| DEFAULT_CONFIG_PATH.into());
+
+           let text = match std::fs::read_to_string(&path) {
+               Ok(text) => text,
+               Err(e) if explicit.is_none() && e.kind() == std::io::ErrorKind::NotFound => {
+                   if let Some(profile) = profile.map(str::to_string).or_else(|| env_var("CONFIG_PROFILE")) {
+                       if profile != DEFAULT_PROFILE {
+                           return Err(anyhow!("Profile {} needs a config file; {} not found", profile, path.display()));
+                       }
+                   }
+                   return Self::new();
+               }
+               Err(e) => return Err(anyhow!("Failed to read config {}: {}", path.display(), e)),
            };
-           
-           Ok(config)
+
+           Self::from_toml(&text, profile, &env_var)
+               .map_err(|e| anyhow!("{}: {}", path.display(), e))
+       }
+
+       /// Parses a config file, applies the selected profile, then the
+       /// variables `env` returns, and validates the result.
+       pub fn from_toml(text: &str, profile: Option<&str>, env: &dyn Fn(&str) -> Option<String>) -> Result<Self> {
+           let mut table: toml::Table = text.parse().map_err(|e| anyhow!("Invalid TOML: {}", e))?;
+
+           let default_profile = match table.remove("profile") {
+               Some(toml::Value::String(profile)) => Some(profile),
+               Some(other) => return Err(anyhow!("profile: expected a string, found {}", other.type_str())),
+               None => None,
+           };
+           let profiles = match table.remove("profiles") {
+               Some(toml::Value::Table(profiles)) => profiles,
+               Some(other) => return Err(anyhow!("profiles: expected a table, found {}", other.type_str())),
+               None => toml::Table::new(),
+           };
+
+           let name = profile
+               .map(str::to_string)
+               .or_else(|| env("CONFIG_PROFILE"))
+               .or(default_profile)
+               .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+           match profiles.get(&name) {
+               Some(toml::Value::Table(overrides)) => merge(&mut table, overrides.clone()),
+               Some(_) => return Err(anyhow!("profiles.{}: expected a table", name)),
+               // A file without profiles describes a single setup
+               None if profiles.is_empty() => {}
+               None => {
+                   let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                   return Err(anyhow!("Unknown profile {}; the file defines {}", name, known.join(", ")));
+               }
+           }
+
+           let file: FileConfig = toml::Value::Table(table)
+               .try_into()
+               .map_err(|e| anyhow!("Invalid config: {}", e))?;
+
+           let mut errors = Vec::new();
+           let mut config = Self {
+               profile: name,
+               ..Self::default()
+           };
+           config.apply_file(file);
+           config.apply_env(env, &mut errors);
+           config.validate(&mut errors);
+           into_result(config, errors)
+       }
+
+       /// First RPC endpoint.
+       pub fn rpc_url(&self) -> &str {
+           match self.rpc_endpoints.first() {
+               Some(endpoint) => &endpoint.url,
+               None => self.rpc_urls.first().map(String::as_str).unwrap_or_default(),
+           }
+       }
+
+       /// Endpoints the RPC pool spreads reads over: `rpc_endpoints` when set,
+       /// else `rpc_urls` with equal weights and no rate limit.
+       pub fn endpoints(&self) -> Vec<RpcEndpoint> {
+           if !self.rpc_endpoints.is_empty() {
+               return self.rpc_endpoints.clone();
+           }
+           self.rpc_urls
+               .iter()
+               .map(|url| RpcEndpoint {
+                   url: url.clone(),
+                   weight: default_weight(),
+                   max_rps: None,
+               })
+               .collect()
+       }
+
+       fn apply_file(&mut self, file: FileConfig) {
+           let FileConfig {
+               rpc_urls,
+               rpc_endpoints,
+               ws_url,
+               database_url,
+               min_profit_threshold,
+               max_block_lag,
+               max_cycle_hops,
+               priority_fee_percentile,
+               native_price_feed,
+               native_price_max_age,
+               gas_estimate_sender,
+               reserve_tolerance,
+               trade_size,
+               max_trade_size,
+               scan_trigger,
+               scan_interval,
+               pool_cache,
+               reorg_depth,
+               discovery,
+               discovery_backfill_blocks,
+               discovery_tokens,
+               token_pairs,
+               dexes,
+           } = file;
+
+           set(&mut self.rpc_urls, rpc_urls);
+           set(&mut self.rpc_endpoints, rpc_endpoints);
+           set(&mut self.min_profit_threshold, min_profit_threshold);
+           set(&mut self.max_block_lag, max_block_lag);
+           set(&mut self.max_cycle_hops, max_cycle_hops);
+           set(&mut self.priority_fee_percentile, priority_fee_percentile);
+           set(&mut self.native_price_max_age, native_price_max_age);
+           set(&mut self.reserve_tolerance, reserve_tolerance);
+           set(&mut self.trade_size, trade_size);
+           set(&mut self.scan_trigger, scan_trigger);
+           set(&mut self.scan_interval, scan_interval);
+           set(&mut self.pool_cache, pool_cache);
+           set(&mut self.reorg_depth, reorg_depth);
+           set(&mut self.discovery, discovery);
+           set(&mut self.discovery_backfill_blocks, discovery_backfill_blocks);
+           set(&mut self.discovery_tokens, discovery_tokens);
+           set(&mut self.token_pairs, token_pairs);
+           set(&mut self.dexes, dexes);
+           if database_url.is_some() {
+               self.database_url = database_url;
+           }
+           if let Some(url) = ws_url {
+               self.ws_url = Some(url).filter(|url| !url.is_empty());
+           }
+           // An empty string switches an optional setting off
+           if let Some(feed) = native_price_feed {
+               self.native_price_feed = Some(feed).filter(|feed| !feed.is_empty());
+           }
+           if let Some(sender) = gas_estimate_sender {
+               self.gas_estimate_sender = Some(sender).filter(|sender| !sender.is_empty());
+           }
+           if max_trade_size.is_some() {
+               self.max_trade_size = max_trade_size;
+           }
+       }
+
+       fn apply_env(&mut self, env: &dyn Fn(&str) -> Option<String>, errors: &mut Vec<String>) {
+           if let Some(urls) = env("POLYGON_RPC_URL") {
+               self.rpc_urls = urls.split(',').map(|url| url.trim().to_string()).collect();
+               self.rpc_endpoints.clear();
+           }
+           if let Some(url) = env("POLYGON_WS_URL") {
+               self.ws_url = Some(url).filter(|url| !url.is_empty());
+           }
+           if let Some(url) = env("DATABASE_URL") {
+               self.database_url = Some(url).filter(|url| !url.is_empty());
+           }
+           env_override(env, "MIN_PROFIT_THRESHOLD", &mut self.min_profit_threshold, errors);
+           env_override(env, "MAX_BLOCK_LAG", &mut self.max_block_lag, errors);
+           env_override(env, "MAX_CYCLE_HOPS", &mut self.max_cycle_hops, errors);
+           env_override(env, "PRIORITY_FEE_PERCENTILE", &mut self.priority_fee_percentile, errors);
+           env_override(env, "NATIVE_PRICE_MAX_AGE", &mut self.native_price_max_age, errors);
+           env_override(env, "RESERVE_TOLERANCE", &mut self.reserve_tolerance, errors);
+           env_override(env, "TRADE_SIZE", &mut self.trade_size, errors);
+           env_override(env, "SCAN_TRIGGER", &mut self.scan_trigger, errors);
+           env_override(env, "SCAN_INTERVAL", &mut self.scan_interval, errors);
+           env_override(env, "POOL_CACHE", &mut self.pool_cache, errors);
+           env_override(env, "REORG_DEPTH", &mut self.reorg_depth, errors);
+           env_override(env, "DISCOVERY", &mut self.discovery, errors);
+           env_override(env, "DISCOVERY_BACKFILL_BLOCKS", &mut self.discovery_backfill_blocks, errors);
+           if let Some(feed) = env("NATIVE_PRICE_FEED") {
+               self.native_price_feed = Some(feed).filter(|feed| !feed.is_empty());
+           }
+           if let Some(sender) = env("GAS_ESTIMATE_SENDER") {
+               self.gas_estimate_sender = Some(sender).filter(|sender| !sender.is_empty());
+           }
+           match env("MAX_TRADE_SIZE").filter(|size| !size.is_empty()) {
+               Some(size) => match size.parse() {
+                   Ok(size) => self.max_trade_size = Some(size),
+                   Err(e) => errors.push(format!("MAX_TRADE_SIZE={}: {}", size, e)),
+               },
+               None if env("MAX_TRADE_SIZE").is_some() => self.max_trade_size = None,
+               None => {}
+           }
+       }
+
+       /// Appends every problem found to `errors`.
+       fn validate(&self, errors: &mut Vec<String>) {
+           if self.rpc_urls.is_empty() {
+               errors.push("rpc_urls: at least one endpoint is required".to_string());
+           }
+           for (i, url) in self.rpc_urls.iter().enumerate() {
+               match reqwest::Url::parse(url) {
+                   Ok(parsed) if ["http", "https"].contains(&parsed.scheme()) => {}
+                   // WebSocket endpoints belong in ws_url
+                   Ok(parsed) => errors.push(format!("rpc_urls[{}]: expected http:// or https://, found {}://", i, parsed.scheme())),
+                   Err(e) => errors.push(format!("rpc_urls[{}]: {} ({})", i, e, url)),
+               }
+           }
+           for (i, endpoint) in self.rpc_endpoints.iter().enumerate() {
+               let field = format!("rpc_endpoints[{}]", i);
+               match reqwest::Url::parse(&endpoint.url) {
+                   Ok(parsed) if ["http", "https"].contains(&parsed.scheme()) => {}
+                   Ok(parsed) => errors.push(format!("{}.url: expected http:// or https://, found {}://", field, parsed.scheme())),
+                   Err(e) => errors.push(format!("{}.url: {} ({})", field, e, endpoint.url)),
+               }
+               if !(endpoint.weight > 0.0 && endpoint.weight.is_finite()) {
+                   errors.push(format!("{}.weight: {} is not a positive number", field, endpoint.weight));
+               }
+               if let Some(max_rps) = endpoint.max_rps {
+                   if !(max_rps > 0.0 && max_rps.is_finite()) {
+                       errors.push(format!("{}.max_rps: {} is not a positive rate", field, max_rps));
+                   }
+               }
+           }
+           if let Some(url) = &self.ws_url {
+               match reqwest::Url::parse(url) {
+                   Ok(parsed) if ["ws", "wss"].contains(&parsed.scheme()) => {}
+                   Ok(parsed) => errors.push(format!("ws_url: expected ws:// or wss://, found {}://", parsed.scheme())),
+                   Err(e) => errors.push(format!("ws_url: {} ({})", e, url)),
+               }
+           }
+           if let Some(url) = &self.database_url {
+               if !url.starts_with("mysql://") {
+                   errors.push("database_url: expected a mysql:// URL".to_string());
+               }
+           }
+
+           check_fraction(errors, "min_profit_threshold", self.min_profit_threshold);
+           check_fraction(errors, "reserve_tolerance", self.reserve_tolerance);
+           if !(0.0..=100.0).contains(&self.priority_fee_percentile) {
+               errors.push(format!("priority_fee_percentile: {} is not in [0, 100]", self.priority_fee_percentile));
+           }
+           if self.max_cycle_hops < 2 {
+               errors.push(format!("max_cycle_hops: {} is below 2, the shortest cycle", self.max_cycle_hops));
+           }
+           if self.native_price_max_age == 0 {
+               errors.push("native_price_max_age: must be positive".to_string());
+           }
+           if !(self.trade_size > 0.0 && self.trade_size.is_finite()) {
+               errors.push(format!("trade_size: {} is not a positive amount", self.trade_size));
+           }
+           if let Some(max) = self.max_trade_size {
+               if !(max > 0.0 && max.is_finite()) {
+                   errors.push(format!("max_trade_size: {} is not a positive amount", max));
+               }
+           }
+           if self.scan_trigger == ScanTrigger::Interval && self.scan_interval == 0 {
+               errors.push("scan_interval: must be at least 1 second".to_string());
+           }
+           if self.reorg_depth == 0 {
+               errors.push("reorg_depth: must be at least 1 block".to_string());
+           }
+           check_address(errors, "native_price_feed".to_string(), self.native_price_feed.as_deref());
+           check_address(errors, "gas_estimate_sender".to_string(), self.gas_estimate_sender.as_deref());
+
+           if self.token_pairs.is_empty() {
+               errors.push("token_pairs: at least one pair is required".to_string());
+           }
+           let mut pairs = HashSet::new();
+           for (i, pair) in self.token_pairs.iter().enumerate() {
+               let field = format!("token_pairs[{}]", i);
+               if pair.base.is_empty() || pair.quote.is_empty() {
+                   errors.push(format!("{}: base and quote symbols are required", field));
+               } else if pair.base == pair.quote {
+                   errors.push(format!("{}: base and quote are both {}", field, pair.base));
+               }
+               if !pairs.insert((&pair.base, &pair.quote)) {
+                   errors.push(format!("{}: {}/{} is listed twice", field, pair.base, pair.quote));
+               }
+               check_address(errors, format!("{}.base_address", field), Some(&pair.base_address));
+               check_address(errors, format!("{}.quote_address", field), Some(&pair.quote_address));
+           }
+
+           let mut symbols = HashSet::new();
+           for (i, token) in self.discovery_tokens.iter().enumerate() {
+               let field = format!("discovery_tokens[{}]", i);
+               if token.symbol.is_empty() {
+                   errors.push(format!("{}: symbol is required", field));
+               } else if !symbols.insert(&token.symbol) {
+                   errors.push(format!("{}: {} is listed twice", field, token.symbol));
+               }
+               if !(token.min_liquidity >= 0.0 && token.min_liquidity.is_finite()) {
+                   errors.push(format!("{}.min_liquidity: {} is not a non-negative amount", field, token.min_liquidity));
+               }
+               check_address(errors, format!("{}.address", field), Some(&token.address));
+           }
+           if self.discovery && self.discovery_tokens.len() < 2 {
+               errors.push("discovery_tokens: discovery needs at least two whitelisted tokens".to_string());
+           }
+
+           if self.dexes.is_empty() {
+               errors.push("dexes: at least one DEX is required".to_string());
+           }
+           let mut names = HashSet::new();
+           for (i, dex) in self.dexes.iter().enumerate() {
+               let field = format!("dexes[{}]", i);
+               if dex.name.is_empty() {
+                   errors.push(format!("{}: name is required", field));
+               } else if !names.insert(&dex.name) {
+                   errors.push(format!("{}: name {} is used twice", field, dex.name));
+               }
+               match (dex.kind, dex.fee) {
+                   (DexKind::UniswapV3, Some(_)) => {
+                       errors.push(format!("{}.fee: Uniswap V3 fees come from the pool fee tiers", field));
+                   }
+                   (_, Some(fee)) => check_fraction(errors, &format!("{}.fee", field), fee),
+                   _ => {}
+               }
+               if dex.kind != DexKind::Simulated {
+                   check_address(errors, format!("{}.router", field), Some(&dex.router));
+                   match &dex.factory {
+                       Some(factory) => check_address(errors, format!("{}.factory", field), Some(factory)),
+                       None => errors.push(format!("{}.factory: required for {:?}", field, dex.kind)),
+                   }
+               }
+           }
        }
 
        fn default_token_pairs() -> Vec<TokenPair> {
@@ -48,24 +503,126 @@ use serde::{Deserialize, Serialize};
                    base_address: "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6".to_string(),
                    quote_address: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_string(),
                },
+               TokenPair {
+                   base: "WMATIC".to_string(),
+                   quote: "USDC".to_string(),
+                   base_address: "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270".to_string(),
+                   quote_address: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_string(),
+               },
+               // Cross pairs close USDC -> X -> Y -> USDC triangles
+               TokenPair {
+                   base: "WMATIC".to_string(),
+                   quote: "WETH".to_string(),
+                   base_address: "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270".to_string(),
+                   quote_address: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".to_string(),
+               },
+               TokenPair {
+                   base: "WBTC".to_string(),
+                   quote: "WETH".to_string(),
+                   base_address: "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6".to_string(),
+                   quote_address: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".to_string(),
+               },
+           ]
+       }
+
+       fn default_discovery_tokens() -> Vec<DiscoveryToken> {
+           [
+               ("USDC", "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174", 10_000.0),
+               ("WETH", "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", 4.0),
+               ("WMATIC", "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270", 20_000.0),
+               ("WBTC", "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6", 0.2),
+           ]
+           .into_iter()
+           .map(|(symbol, address, min_liquidity)| DiscoveryToken {
+               symbol: symbol.to_string(),
+               address: address.to_string(),
+               min_liquidity,
+           })
+           .collect()
+       }
+
+       fn default_dexes() -> Vec<DexConfig> {
+           vec![
+               // Uniswap V3 Router/Factory on Polygon
+               DexConfig {
+                   name: "uniswap_v3".to_string(),
+                   kind: DexKind::UniswapV3,
+                   router: "0xE592427A0AEce92De3Edee1F18E0157C05861564".to_string(),
+                   factory: Some("0x1F98431c8aD98523631AE4a59f267346ea31F984".to_string()),
+                   fee: None,
+               },
+               // SushiSwap Router/Factory on Polygon
+               DexConfig {
+                   name: "sushiswap".to_string(),
+                   kind: DexKind::UniswapV2,
+                   router: "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506".to_string(),
+                   factory: Some("0xc35DADB65012eC5796536bD9864eD8773aBc74C4".to_string()),
+                   fee: None,
+               },
+               // QuickSwap Router/Factory on Polygon
+               DexConfig {
+                   name: "quickswap".to_string(),
+                   kind: DexKind::UniswapV2,
+                   router: "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff".to_string(),
+                   factory: Some("0x5757371414417b8C6CAad45bAeF941aBc7d3Ab32".to_string()),
+                   fee: None,
+               },
            ]
        }
+   }
+
+   fn env_var(name: &str) -> Option<String> {
+       std::env::var(name).ok()
+   }
+
+   fn set<T>(target: &mut T, value: Option<T>) {
+       if let Some(value) = value {
+           *target = value;
+       }
+   }
+
+   fn env_override<T: FromStr>(env: &dyn Fn(&str) -> Option<String>, name: &str, target: &mut T, errors: &mut Vec<String>)
+   where
+       T::Err: Display,
+   {
+       if let Some(value) = env(name) {
+           match value.parse() {
+               Ok(parsed) => *target = parsed,
+               Err(e) => errors.push(format!("{}={}: {}", name, value, e)),
+           }
+       }
+   }
+
+   fn check_fraction(errors: &mut Vec<String>, field: &str, value: f64) {
+       if !(0.0..1.0).contains(&value) {
+           errors.push(format!("{}: {} is not a fraction in [0, 1)", field, value));
+       }
+   }
 
-       fn default_dex_contracts() -> HashMap<String, String> {
-           let mut dex_map = HashMap::new();
-           
-           // Uniswap V3 Router on Polygon
-           dex_map.insert(
-               "uniswap_v3".to_string(),
-               "0xE592427A0AEce92De3Edee1F18E0157C05861564".to_string()
-           );
-           
-           // SushiSwap Router on Polygon  
-           dex_map.insert(
-               "sushiswap".to_string(),
-               "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506".to_string()
-           );
-           
-           dex_map
+   fn check_address(errors: &mut Vec<String>, field: String, address: Option<&str>) {
+       if let Some(address) = address {
+           if let Err(e) = token_registry::parse_checksummed(address) {
+               errors.push(format!("{}: {}", field, e));
+           }
+       }
+   }
+
+   /// Profile tables override the base key by key; nested tables merge, anything else is replaced.
+   fn merge(base: &mut toml::Table, overrides: toml::Table) {
+       for (key, value) in overrides {
+           match (base.get_mut(&key), value) {
+               (Some(toml::Value::Table(base)), toml::Value::Table(value)) => merge(base, value),
+               (_, value) => {
+                   base.insert(key, value);
+               }
+           }
+       }
+   }
+
+   fn into_result(config: Config, errors: Vec<String>) -> Result<Config> {
+       if errors.is_empty() {
+           Ok(config)
+       } else {
+           Err(anyhow!("Invalid configuration ({} errors):\n  - {}", errors.len(), errors.join("\n  - ")))
        }
    }
\ No newline at end of file
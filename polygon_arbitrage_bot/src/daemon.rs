@@ -0,0 +1,296 @@
+use anyhow::{Result, anyhow};
+   use chrono::{DateTime, Utc};
+   use ethers::prelude::*;
+   use serde::{Deserialize, Serialize};
+   use std::str::FromStr;
+   use std::sync::Arc;
+   use std::time::Duration;
+   use tokio::sync::watch;
+   use crate::modules::amounts::Decimal;
+   use crate::modules::new_heads::NewHeads;
+   use crate::modules::price_fetcher::BlockRef;
+   use crate::modules::rpc_pool::{EndpointHealth, RpcClient};
+   use crate::scanner::ScanReport;
+
+   // How often the node is asked for a new block when scanning per block
+   const BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+   /// What starts the next detection cycle.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+   #[serde(rename_all = "snake_case")]
+   pub enum ScanTrigger {
+       /// A fixed delay after each cycle
+       #[default]
+       Interval,
+       /// Each new block
+       Block,
+   }
+
+   impl FromStr for ScanTrigger {
+       type Err = anyhow::Error;
+
+       fn from_str(s: &str) -> Result<Self> {
+           match s {
+               "interval" => Ok(Self::Interval),
+               "block" => Ok(Self::Block),
+               other => Err(anyhow!("unknown scan trigger {}; expected interval or block", other)),
+           }
+       }
+   }
+
+   /// Flips to true on the first SIGINT or SIGTERM. In-flight work is left to
+   /// finish; a second signal exits immediately.
+   pub fn shutdown_signal() -> watch::Receiver<bool> {
+       let (sender, receiver) = watch::channel(false);
+       tokio::spawn(async move {
+           if wait_for_signal().await.is_err() {
+               return;
+           }
+           eprintln!("\n🛑 Shutdown requested; finishing the current cycle (signal again to force)");
+           let _ = sender.send(true);
+
+           if wait_for_signal().await.is_ok() {
+               eprintln!("🛑 Forced shutdown");
+               std::process::exit(130);
+           }
+       });
+       receiver
+   }
+
+   #[cfg(unix)]
+   async fn wait_for_signal() -> Result<()> {
+       use tokio::signal::unix::{signal, SignalKind};
+
+       let mut terminate = signal(SignalKind::terminate())?;
+       tokio::select! {
+           result = tokio::signal::ctrl_c() => result?,
+           _ = terminate.recv() => {}
+       }
+       Ok(())
+   }
+
+   #[cfg(not(unix))]
+   async fn wait_for_signal() -> Result<()> {
+       Ok(tokio::signal::ctrl_c().await?)
+   }
+
+   /// Why a cycle starts.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+   pub struct Tick {
+       /// Blocks mined since the last cycle's that get no cycle of their own
+       pub skipped: u64,
+       /// The head to pin reads to, when the trigger knows it
+       pub block: Option<BlockRef>,
+   }
+
+   /// Waits for the moment to start the next cycle.
+   pub enum CycleTrigger {
+       Interval(Duration),
+       /// Polls the HTTP node for a higher block number
+       Block {
+           client: Arc<RpcClient>,
+           poll: Duration,
+           last_block: Option<u64>,
+       },
+       /// Follows a `newHeads` WebSocket subscription
+       Heads {
+           heads: NewHeads,
+           receiver: watch::Receiver<Option<BlockRef>>,
+           last_block: Option<BlockRef>,
+       },
+   }
+
+   impl CycleTrigger {
+       pub fn interval(interval: Duration) -> Self {
+           CycleTrigger::Interval(interval)
+       }
+
+       pub fn per_block(client: Arc<RpcClient>) -> Self {
+           CycleTrigger::Block {
+               client,
+               poll: BLOCK_POLL_INTERVAL,
+               last_block: None,
+           }
+       }
+
+       pub fn new_heads(heads: NewHeads) -> Self {
+           CycleTrigger::Heads {
+               receiver: heads.receiver(),
+               heads,
+               last_block: None,
+           }
+       }
+
+       /// WebSocket reconnects so far; zero for the other triggers.
+       pub fn reconnects(&self) -> u64 {
+           match self {
+               CycleTrigger::Heads { heads, .. } => heads.reconnects(),
+               _ => 0,
+           }
+       }
+
+       /// Overrides how often a per-block trigger polls the node.
+       pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+           if let CycleTrigger::Block { poll, .. } = &mut self {
+               *poll = interval;
+           }
+           self
+       }
+
+       /// Returns once the next cycle is due: after the interval, or when a
+       /// block newer than the last one seen arrives. Blocks mined while a
+       /// cycle ran are coalesced into one tick for the newest of them. None
+       /// when shutdown was requested first.
+       pub async fn wait(&mut self, shutdown: &mut watch::Receiver<bool>) -> Result<Option<Tick>> {
+           if *shutdown.borrow() {
+               return Ok(None);
+           }
+
+           match self {
+               CycleTrigger::Interval(interval) => {
+                   tokio::select! {
+                       _ = tokio::time::sleep(*interval) => Ok(Some(Tick { skipped: 0, block: None })),
+                       _ = shutdown.changed() => Ok(None),
+                   }
+               }
+               CycleTrigger::Block { client, poll, last_block } => loop {
+                   let block = client.get_block_number().await?.as_u64();
+                   match *last_block {
+                       Some(last) if block <= last => {}
+                       last => {
+                           *last_block = Some(block);
+                           return Ok(Some(Tick {
+                               skipped: last.map_or(0, |last| block - last - 1),
+                               block: None,
+                           }));
+                       }
+                   }
+
+                   tokio::select! {
+                       _ = tokio::time::sleep(*poll) => {}
+                       _ = shutdown.changed() => return Ok(None),
+                   }
+               },
+               CycleTrigger::Heads { receiver, last_block, .. } => loop {
+                   let head = *receiver.borrow_and_update();
+                   if let Some(head) = head.filter(|head| last_block.is_none_or(|last| last.hash != head.hash)) {
+                       let skipped = last_block.map_or(0, |last| head.number.saturating_sub(last.number + 1));
+                       *last_block = Some(head);
+                       return Ok(Some(Tick { skipped, block: Some(head) }));
+                   }
+
+                   tokio::select! {
+                       changed = receiver.changed() => changed.map_err(|_| anyhow!("newHeads subscription stopped"))?,
+                       _ = shutdown.changed() => return Ok(None),
+                   }
+               },
+           }
+       }
+   }
+
+   /// Totals over a daemon run, printed on shutdown.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct DaemonSummary {
+       pub started_at: DateTime<Utc>,
+       pub cycles: u64,
+       pub failed_cycles: u64,
+       pub skipped_blocks: u64,
+       pub reconnects: u64,
+       pub reorgs: u64,
+       pub orphaned_blocks: u64,
+       pub opportunities: u64,
+       pub stored: u64,
+       pub routes: u64, // Triangular and multi-hop
+       pub best_profit_percentage: Option<Decimal>,
+       pub last_block: Option<u64>,
+       pub endpoints: Vec<EndpointHealth>, // RPC endpoint health at shutdown
+   }
+
+   impl DaemonSummary {
+       pub fn new() -> Self {
+           Self {
+               started_at: Utc::now(),
+               cycles: 0,
+               failed_cycles: 0,
+               skipped_blocks: 0,
+               reconnects: 0,
+               reorgs: 0,
+               orphaned_blocks: 0,
+               opportunities: 0,
+               stored: 0,
+               routes: 0,
+               best_profit_percentage: None,
+               last_block: None,
+               endpoints: Vec::new(),
+           }
+       }
+
+       pub fn record(&mut self, report: &ScanReport) {
+           self.cycles += 1;
+           self.opportunities += report.opportunities.len() as u64;
+           self.stored += report.opportunities.iter().filter(|analyzed| analyzed.stored_id.is_some()).count() as u64;
+           self.routes += (report.triangular.len() + report.multi_hop.len()) as u64;
+           for analyzed in &report.opportunities {
+               let profit = analyzed.opportunity.profit_percentage;
+               if self.best_profit_percentage.is_none_or(|best| profit > best) {
+                   self.best_profit_percentage = Some(profit);
+               }
+           }
+           if let Some(block) = report.block {
+               self.last_block = Some(block.number);
+           }
+           if let Some(reorg) = &report.reorg {
+               self.reorgs += 1;
+               self.orphaned_blocks += reorg.orphaned.len() as u64;
+           }
+       }
+
+       pub fn record_failure(&mut self) {
+           self.cycles += 1;
+           self.failed_cycles += 1;
+       }
+
+       pub fn record_skipped_blocks(&mut self, blocks: u64) {
+           self.skipped_blocks += blocks;
+       }
+
+       pub fn uptime(&self) -> chrono::Duration {
+           Utc::now() - self.started_at
+       }
+
+       pub fn print(&self) {
+           let uptime = self.uptime();
+           println!("\n📋 Run Summary:");
+           println!("  - Uptime: {}h {:02}m {:02}s", uptime.num_hours(), uptime.num_minutes() % 60, uptime.num_seconds() % 60);
+           println!("  - Cycles: {} ({} failed)", self.cycles, self.failed_cycles);
+           if self.skipped_blocks > 0 {
+               println!("  - Blocks skipped while scanning: {}", self.skipped_blocks);
+           }
+           if self.reconnects > 0 {
+               println!("  - WebSocket reconnects: {}", self.reconnects);
+           }
+           if self.reorgs > 0 {
+               println!("  - Reorgs: {} ({} blocks orphaned)", self.reorgs, self.orphaned_blocks);
+           }
+           println!("  - Opportunities: {} found, {} stored", self.opportunities, self.stored);
+           println!("  - Cycle routes: {}", self.routes);
+           if let Some(best) = self.best_profit_percentage {
+               println!("  - Best spread: {:.3}%", best * Decimal::ONE_HUNDRED);
+           }
+           if let Some(block) = self.last_block {
+               println!("  - Last block: #{}", block);
+           }
+           if self.endpoints.len() > 1 || self.endpoints.iter().any(|endpoint| endpoint.failures > 0) {
+               println!("  - RPC endpoints:");
+               for endpoint in &self.endpoints {
+                   println!("      {}", endpoint.summary());
+               }
+           }
+       }
+   }
+
+   impl Default for DaemonSummary {
+       fn default() -> Self {
+           Self::new()
+       }
+   }
\ No newline at end of file
@@ -1,29 +1,53 @@
 use anyhow::Result;
-   use sqlx::{MySqlPool, Row};
+   use serde::Serialize;
+   use sqlx::mysql::MySqlRow;
+   use sqlx::{MySql, MySqlPool, QueryBuilder, Row};
    use chrono::{DateTime, Utc};
+   use ethers::types::Address;
+   use crate::config::TokenPair;
+   use crate::modules::amounts::{Decimal, TokenAmount};
    use crate::modules::arbitrage_detector::ArbitrageOpportunity;
+   use crate::modules::pool_discovery::DiscoveredPool;
+   use crate::modules::price_fetcher::{self, PriceData};
    use crate::modules::profit_calculator::ProfitAnalysis;
+   use crate::modules::reorg::ChainLink;
+   use crate::modules::rpc_pool::EndpointHealth;
 
    pub struct Database {
        pool: MySqlPool,
    }
 
-   #[derive(Debug)]
+   #[derive(Debug, Clone, Serialize)]
    pub struct StoredOpportunity {
        pub id: i32,
        pub token_pair: String,
        pub buy_dex: String,
        pub sell_dex: String,
-       pub buy_price: f64,
-       pub sell_price: f64,
-       pub profit_percentage: f64,
-       pub profit_usd: f64,
-       pub trade_size: f64,
-       pub net_profit: Option<f64>,
-       pub gas_costs: Option<f64>,
+       pub buy_price: Decimal,
+       pub sell_price: Decimal,
+       pub profit_percentage: Decimal,
+       pub raw_spread: Option<Decimal>,
+       pub profit_usd: Decimal,
+       pub trade_size: Decimal,
+       pub amount_in: Option<TokenAmount>,
+       pub net_profit: Option<Decimal>,
+       pub gas_costs: Option<Decimal>,
+       pub block_number: Option<u64>,
+       pub block_hash: Option<String>,
+       pub block_timestamp: Option<u64>,
+       pub native_price_usd: Option<Decimal>,
+       pub native_price_source: Option<String>,
+       pub reorged: bool, // Its block was orphaned; the opportunity never existed on chain
        pub created_at: DateTime<Utc>,
    }
 
+   /// Rows flagged by `mark_reorged`.
+   #[derive(Debug, Clone, Copy, Default, Serialize)]
+   pub struct ReorgedRows {
+       pub opportunities: u64,
+       pub price_snapshots: u64,
+   }
+
    impl Database {
        pub async fn new(database_url: &str) -> Result<Self> {
            let pool = MySqlPool::connect(database_url).await?;
@@ -42,27 +66,168 @@ use anyhow::Result;
                    token_pair VARCHAR(50) NOT NULL,
                    buy_dex VARCHAR(50) NOT NULL,
                    sell_dex VARCHAR(50) NOT NULL,
-                   buy_price DECIMAL(20, 8) NOT NULL,
-                   sell_price DECIMAL(20, 8) NOT NULL,
-                   profit_percentage DECIMAL(10, 6) NOT NULL,
-                   profit_usd DECIMAL(20, 2) NOT NULL,
-                   trade_size DECIMAL(20, 2) NOT NULL,
-                   net_profit DECIMAL(20, 2) NULL,
-                   gas_costs DECIMAL(20, 8) NULL,
+                   buy_price DECIMAL(65, 28) NOT NULL,
+                   sell_price DECIMAL(65, 28) NOT NULL,
+                   profit_percentage DECIMAL(65, 28) NOT NULL,
+                   raw_spread DECIMAL(65, 28) NULL,
+                   profit_usd DECIMAL(65, 28) NOT NULL,
+                   trade_size DECIMAL(65, 28) NOT NULL,
+                   amount_in_raw VARCHAR(78) NULL,
+                   amount_in_decimals TINYINT UNSIGNED NULL,
+                   net_profit DECIMAL(65, 28) NULL,
+                   gas_costs DECIMAL(65, 28) NULL,
+                   block_number BIGINT UNSIGNED NULL,
+                   block_hash CHAR(66) NULL,
+                   block_timestamp BIGINT UNSIGNED NULL,
+                   native_price_usd DECIMAL(65, 28) NULL,
+                   native_price_source VARCHAR(20) NULL,
+                   reorged BOOLEAN NOT NULL DEFAULT FALSE,
                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
                    INDEX idx_token_pair (token_pair),
                    INDEX idx_created_at (created_at),
-                   INDEX idx_profit_percentage (profit_percentage)
+                   INDEX idx_profit_percentage (profit_percentage),
+                   INDEX idx_block_number (block_number)
                )
                "#
            )
            .execute(&self.pool)
            .await?;
 
-           println!("✅ Database tables created/verified");
+           sqlx::query(
+               r#"
+               CREATE TABLE IF NOT EXISTS price_snapshots (
+                   id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                   dex_name VARCHAR(50) NOT NULL,
+                   token_pair VARCHAR(50) NOT NULL,
+                   price DECIMAL(65, 28) NOT NULL,
+                   liquidity DECIMAL(65, 28) NOT NULL,
+                   fee DOUBLE NOT NULL,
+                   block_number BIGINT UNSIGNED NOT NULL,
+                   block_hash CHAR(66) NOT NULL,
+                   block_timestamp BIGINT UNSIGNED NOT NULL,
+                   reorged BOOLEAN NOT NULL DEFAULT FALSE,
+                   created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                   INDEX idx_pair_dex (token_pair, dex_name),
+                   INDEX idx_block_number (block_number)
+               )
+               "#
+           )
+           .execute(&self.pool)
+           .await?;
+
+           sqlx::query(
+               r#"
+               CREATE TABLE IF NOT EXISTS discovered_pools (
+                   pool_address CHAR(42) PRIMARY KEY,
+                   dex_name VARCHAR(50) NOT NULL,
+                   fee INT UNSIGNED NULL,
+                   token_pair VARCHAR(50) NOT NULL,
+                   base_symbol VARCHAR(20) NOT NULL,
+                   quote_symbol VARCHAR(20) NOT NULL,
+                   base_address CHAR(42) NOT NULL,
+                   quote_address CHAR(42) NOT NULL,
+                   created_block BIGINT UNSIGNED NOT NULL,
+                   base_balance DECIMAL(65, 28) NOT NULL,
+                   quote_balance DECIMAL(65, 28) NOT NULL,
+                   enabled BOOLEAN NOT NULL DEFAULT TRUE,
+                   discovered_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                   INDEX idx_token_pair (token_pair)
+               )
+               "#
+           )
+           .execute(&self.pool)
+           .await?;
+
+           sqlx::query(
+               r#"
+               CREATE TABLE IF NOT EXISTS rpc_endpoint_health (
+                   endpoint VARCHAR(255) PRIMARY KEY,
+                   weight DOUBLE NOT NULL,
+                   max_rps DOUBLE NULL,
+                   requests BIGINT UNSIGNED NOT NULL,
+                   failures BIGINT UNSIGNED NOT NULL,
+                   error_rate DOUBLE NOT NULL,
+                   latency_ms DOUBLE NULL,
+                   healthy BOOLEAN NOT NULL,
+                   updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+               )
+               "#
+           )
+           .execute(&self.pool)
+           .await?;
+
+           // Tables created before block tracking existed need the new columns
+           self.ensure_column("arbitrage_opportunities", "block_number", "BIGINT UNSIGNED NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "block_hash", "CHAR(66) NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "block_timestamp", "BIGINT UNSIGNED NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "raw_spread", "DECIMAL(65, 28) NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "native_price_usd", "DECIMAL(65, 28) NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "native_price_source", "VARCHAR(20) NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "amount_in_raw", "VARCHAR(78) NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "amount_in_decimals", "TINYINT UNSIGNED NULL").await?;
+           self.ensure_column("arbitrage_opportunities", "reorged", "BOOLEAN NOT NULL DEFAULT FALSE").await?;
+
+           // Older tables rounded prices and USD values; widen them so values round-trip exactly
+           for (column, nullable) in [
+               ("buy_price", false),
+               ("sell_price", false),
+               ("profit_percentage", false),
+               ("raw_spread", true),
+               ("profit_usd", false),
+               ("trade_size", false),
+               ("net_profit", true),
+               ("gas_costs", true),
+               ("native_price_usd", true),
+           ] {
+               self.ensure_exact_decimal("arbitrage_opportunities", column, nullable).await?;
+           }
+
+           Ok(())
+       }
+
+       async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+           let exists: (i64,) = sqlx::query_as(
+               "SELECT COUNT(*) FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ?"
+           )
+           .bind(table)
+           .bind(column)
+           .fetch_one(&self.pool)
+           .await?;
+
+           if exists.0 == 0 {
+               sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                   .execute(&self.pool)
+                   .await?;
+           }
+
+           Ok(())
+       }
+
+       /// Widens a DECIMAL column to DECIMAL(65, 28), which holds any `Decimal`.
+       async fn ensure_exact_decimal(&self, table: &str, column: &str, nullable: bool) -> Result<()> {
+           let (precision, scale): (Option<u64>, Option<u64>) = sqlx::query_as(
+               "SELECT NUMERIC_PRECISION, NUMERIC_SCALE FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ?"
+           )
+           .bind(table)
+           .bind(column)
+           .fetch_one(&self.pool)
+           .await?;
+
+           if precision != Some(65) || scale != Some(28) {
+               let null = if nullable { "NULL" } else { "NOT NULL" };
+               sqlx::query(&format!("ALTER TABLE {} MODIFY COLUMN {} DECIMAL(65, 28) {}", table, column, null))
+                   .execute(&self.pool)
+                   .await?;
+           }
+
            Ok(())
        }
 
+       /// Waits for in-flight queries and closes every connection.
+       pub async fn close(&self) {
+           self.pool.close().await;
+       }
+
        pub async fn store_opportunity(
            &self,
            opportunity: &ArbitrageOpportunity,
@@ -71,8 +236,10 @@ use anyhow::Result;
            let result = sqlx::query(
                r#"
                INSERT INTO arbitrage_opportunities 
-               (token_pair, buy_dex, sell_dex, buy_price, sell_price, profit_percentage, profit_usd, trade_size, net_profit, gas_costs)
-               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+               (token_pair, buy_dex, sell_dex, buy_price, sell_price, profit_percentage, raw_spread, profit_usd, trade_size,
+                amount_in_raw, amount_in_decimals, net_profit, gas_costs,
+                block_number, block_hash, block_timestamp, native_price_usd, native_price_source)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                "#
            )
            .bind(&opportunity.token_pair)
@@ -81,81 +248,391 @@ use anyhow::Result;
            .bind(opportunity.buy_price)
            .bind(opportunity.sell_price)
            .bind(opportunity.profit_percentage)
+           .bind(opportunity.raw_spread)
            .bind(opportunity.profit_usd)
            .bind(opportunity.trade_size)
+           .bind(opportunity.amount_in.map(|amount| amount.raw.to_string()))
+           .bind(opportunity.amount_in.map(|amount| amount.decimals))
            .bind(analysis.map(|a| a.net_profit))
            .bind(analysis.map(|a| a.gas_costs))
+           .bind(opportunity.block.map(|b| b.number))
+           .bind(opportunity.block.map(|b| format!("{:?}", b.hash)))
+           .bind(opportunity.block.map(|b| b.timestamp))
+           .bind(analysis.map(|a| a.native_price.usd))
+           .bind(analysis.map(|a| a.native_price.label()))
            .execute(&self.pool)
            .await?;
 
            Ok(result.last_insert_id() as i32)
        }
 
-       pub async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<StoredOpportunity>> {
+       /// Stores every quote read on chain in a cycle; quotes without a block
+       /// (simulated ones) are skipped. Returns the rows written.
+       pub async fn store_price_snapshots(&self, prices: &[PriceData]) -> Result<u64> {
+           let snapshots: Vec<_> = prices.iter().filter_map(|price| Some((price, price.block?))).collect();
+           if snapshots.is_empty() {
+               return Ok(0);
+           }
+
+           let mut query = QueryBuilder::<MySql>::new(
+               "INSERT INTO price_snapshots (dex_name, token_pair, price, liquidity, fee, block_number, block_hash, block_timestamp) "
+           );
+           query.push_values(snapshots, |mut row, (price, block)| {
+               row.push_bind(&price.dex_name)
+                   .push_bind(&price.token_pair)
+                   .push_bind(price.price)
+                   .push_bind(price.liquidity)
+                   .push_bind(price.fee)
+                   .push_bind(block.number)
+                   .push_bind(format!("{:?}", block.hash))
+                   .push_bind(block.timestamp);
+           });
+           let result = query.build().execute(&self.pool).await?;
+
+           Ok(result.rows_affected())
+       }
+
+       /// Flags opportunities and price snapshots read at any of the
+       /// `orphaned` blocks.
+       pub async fn mark_reorged(&self, orphaned: &[ChainLink]) -> Result<ReorgedRows> {
+           if orphaned.is_empty() {
+               return Ok(ReorgedRows::default());
+           }
+
+           Ok(ReorgedRows {
+               opportunities: self.mark_table_reorged("arbitrage_opportunities", orphaned).await?,
+               price_snapshots: self.mark_table_reorged("price_snapshots", orphaned).await?,
+           })
+       }
+
+       async fn mark_table_reorged(&self, table: &str, orphaned: &[ChainLink]) -> Result<u64> {
+           // The block number bound lets the index narrow the hash match
+           let oldest = orphaned.iter().map(|block| block.number).min().unwrap_or_default();
+           let mut query = QueryBuilder::<MySql>::new(format!("UPDATE {} SET reorged = TRUE WHERE block_number >= ", table));
+           query.push_bind(oldest).push(" AND block_hash IN (");
+           let mut hashes = query.separated(", ");
+           for block in orphaned {
+               hashes.push_bind(format!("{:?}", block.hash));
+           }
+           query.push(")");
+
+           Ok(query.build().execute(&self.pool).await?.rows_affected())
+       }
+
+       /// Upserts pools found by discovery. Known pools get their balances
+       /// refreshed but keep their enabled flag. Returns the rows written.
+       pub async fn store_discovered_pools(&self, pools: &[DiscoveredPool]) -> Result<u64> {
+           if pools.is_empty() {
+               return Ok(0);
+           }
+
+           let mut query = QueryBuilder::<MySql>::new(
+               "INSERT INTO discovered_pools (pool_address, dex_name, fee, token_pair, base_symbol, quote_symbol, base_address, quote_address, created_block, base_balance, quote_balance, enabled) "
+           );
+           query.push_values(pools, |mut row, pool| {
+               row.push_bind(ethers::utils::to_checksum(&pool.pool, None))
+                   .push_bind(&pool.dex)
+                   .push_bind(pool.fee)
+                   .push_bind(pool.token_pair())
+                   .push_bind(&pool.pair.base)
+                   .push_bind(&pool.pair.quote)
+                   .push_bind(&pool.pair.base_address)
+                   .push_bind(&pool.pair.quote_address)
+                   .push_bind(pool.created_block)
+                   .push_bind(pool.base_balance)
+                   .push_bind(pool.quote_balance)
+                   .push_bind(pool.enabled);
+           });
+           query.push(" ON DUPLICATE KEY UPDATE base_balance = VALUES(base_balance), quote_balance = VALUES(quote_balance)");
+           let result = query.build().execute(&self.pool).await?;
+
+           Ok(result.rows_affected())
+       }
+
+       /// Every discovered pool, oldest first.
+       pub async fn get_discovered_pools(&self) -> Result<Vec<DiscoveredPool>> {
            let rows = sqlx::query(
                r#"
-               SELECT id, token_pair, buy_dex, sell_dex, buy_price, sell_price, 
-                      profit_percentage, profit_usd, trade_size, net_profit, gas_costs, created_at
-               FROM arbitrage_opportunities 
-               ORDER BY created_at DESC 
-               LIMIT ?
+               SELECT pool_address, dex_name, fee, base_symbol, quote_symbol, base_address, quote_address,
+                      created_block, base_balance, quote_balance, enabled
+               FROM discovered_pools
+               ORDER BY created_block, pool_address
                "#
            )
-           .bind(limit)
            .fetch_all(&self.pool)
            .await?;
 
-           let mut opportunities = Vec::new();
-           for row in rows {
-               opportunities.push(StoredOpportunity {
-                   id: row.get("id"),
-                   token_pair: row.get("token_pair"),
-                   buy_dex: row.get("buy_dex"),
-                   sell_dex: row.get("sell_dex"),
-                   buy_price: row.get("buy_price"),
-                   sell_price: row.get("sell_price"),
-                   profit_percentage: row.get("profit_percentage"),
-                   profit_usd: row.get("profit_usd"),
-                   trade_size: row.get("trade_size"),
-                   net_profit: row.get("net_profit"),
-                   gas_costs: row.get("gas_costs"),
-                   created_at: row.get("created_at"),
-               });
+           rows.iter()
+               .map(|row| {
+                   Ok(DiscoveredPool {
+                       pool: price_fetcher::parse_address(row.get("pool_address"))?,
+                       dex: row.get("dex_name"),
+                       pair: TokenPair {
+                           base: row.get("base_symbol"),
+                           quote: row.get("quote_symbol"),
+                           base_address: row.get("base_address"),
+                           quote_address: row.get("quote_address"),
+                       },
+                       fee: row.get("fee"),
+                       created_block: row.get("created_block"),
+                       base_balance: row.get("base_balance"),
+                       quote_balance: row.get("quote_balance"),
+                       enabled: row.get("enabled"),
+                   })
+               })
+               .collect()
+       }
+
+       /// Turns monitoring of a discovered pool on or off. Returns false when
+       /// the pool is unknown.
+       pub async fn set_pool_enabled(&self, pool: Address, enabled: bool) -> Result<bool> {
+           let known: Option<(bool,)> = sqlx::query_as("SELECT enabled FROM discovered_pools WHERE pool_address = ?")
+               .bind(ethers::utils::to_checksum(&pool, None))
+               .fetch_optional(&self.pool)
+               .await?;
+           if known.is_none() {
+               return Ok(false);
+           }
+
+           sqlx::query("UPDATE discovered_pools SET enabled = ? WHERE pool_address = ?")
+               .bind(enabled)
+               .bind(ethers::utils::to_checksum(&pool, None))
+               .execute(&self.pool)
+               .await?;
+
+           Ok(true)
+       }
+
+       /// Records the RPC endpoints' latest health, for `stats`.
+       pub async fn store_endpoint_health(&self, endpoints: &[EndpointHealth]) -> Result<()> {
+           if endpoints.is_empty() {
+               return Ok(());
+           }
+
+           let mut query = QueryBuilder::<MySql>::new(
+               "INSERT INTO rpc_endpoint_health (endpoint, weight, max_rps, requests, failures, error_rate, latency_ms, healthy) "
+           );
+           query.push_values(endpoints, |mut row, endpoint| {
+               row.push_bind(&endpoint.endpoint)
+                   .push_bind(endpoint.weight)
+                   .push_bind(endpoint.max_rps)
+                   .push_bind(endpoint.requests)
+                   .push_bind(endpoint.failures)
+                   .push_bind(endpoint.error_rate)
+                   .push_bind(endpoint.latency_ms)
+                   .push_bind(endpoint.healthy);
+           });
+           query.push(
+               " ON DUPLICATE KEY UPDATE weight = VALUES(weight), max_rps = VALUES(max_rps), requests = VALUES(requests), \
+                failures = VALUES(failures), error_rate = VALUES(error_rate), latency_ms = VALUES(latency_ms), healthy = VALUES(healthy), \
+                updated_at = CURRENT_TIMESTAMP"
+           );
+           query.build().execute(&self.pool).await?;
+
+           Ok(())
+       }
+
+       /// Endpoint health as last recorded by a running bot, most recent first.
+       pub async fn get_endpoint_health(&self) -> Result<Vec<StoredEndpointHealth>> {
+           let rows = sqlx::query(
+               r#"
+               SELECT endpoint, weight, max_rps, requests, failures, error_rate, latency_ms, healthy, updated_at
+               FROM rpc_endpoint_health
+               ORDER BY updated_at DESC, endpoint
+               "#
+           )
+           .fetch_all(&self.pool)
+           .await?;
+
+           Ok(rows
+               .iter()
+               .map(|row| StoredEndpointHealth {
+                   health: EndpointHealth {
+                       endpoint: row.get("endpoint"),
+                       weight: row.get("weight"),
+                       max_rps: row.get("max_rps"),
+                       requests: row.get("requests"),
+                       failures: row.get("failures"),
+                       error_rate: row.get("error_rate"),
+                       latency_ms: row.get("latency_ms"),
+                       healthy: row.get("healthy"),
+                   },
+                   updated_at: row.get("updated_at"),
+               })
+               .collect())
+       }
+
+       pub async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<StoredOpportunity>> {
+           self.get_opportunities(&OpportunityFilter {
+               limit: Some(limit),
+               ..OpportunityFilter::default()
+           })
+           .await
+       }
+
+       /// Stored opportunities matching `filter`, newest first.
+       pub async fn get_opportunities(&self, filter: &OpportunityFilter) -> Result<Vec<StoredOpportunity>> {
+           let mut query = QueryBuilder::<MySql>::new(
+               r#"
+               SELECT id, token_pair, buy_dex, sell_dex, buy_price, sell_price, 
+                      profit_percentage, raw_spread, profit_usd, trade_size,
+                      amount_in_raw, amount_in_decimals, net_profit, gas_costs,
+                      block_number, block_hash, block_timestamp, native_price_usd, native_price_source, reorged, created_at
+               FROM arbitrage_opportunities
+               WHERE 1 = 1"#
+           );
+           if !filter.include_reorged {
+               query.push(" AND reorged = FALSE");
+           }
+           if let Some(pair) = &filter.token_pair {
+               query.push(" AND token_pair = ").push_bind(pair);
+           }
+           if let Some(dex) = &filter.dex {
+               query.push(" AND (buy_dex = ").push_bind(dex).push(" OR sell_dex = ").push_bind(dex).push(")");
+           }
+           if let Some(min_profit) = filter.min_profit {
+               query.push(" AND profit_percentage >= ").push_bind(min_profit);
+           }
+           if let Some(since) = filter.since {
+               query.push(" AND created_at >= ").push_bind(since);
+           }
+           query.push(" ORDER BY created_at DESC, id DESC");
+           if let Some(limit) = filter.limit {
+               query.push(" LIMIT ").push_bind(limit);
            }
 
-           Ok(opportunities)
+           let rows = query.build().fetch_all(&self.pool).await?;
+           rows.iter().map(Self::opportunity_from_row).collect()
+       }
+
+       fn opportunity_from_row(row: &MySqlRow) -> Result<StoredOpportunity> {
+           let amount_in_raw: Option<String> = row.get("amount_in_raw");
+           let amount_in_decimals: Option<u8> = row.get("amount_in_decimals");
+           let amount_in = match (amount_in_raw, amount_in_decimals) {
+               (Some(raw), Some(decimals)) => Some(TokenAmount::from_raw_str(&raw, decimals)?),
+               _ => None,
+           };
+
+           Ok(StoredOpportunity {
+               id: row.get("id"),
+               token_pair: row.get("token_pair"),
+               buy_dex: row.get("buy_dex"),
+               sell_dex: row.get("sell_dex"),
+               buy_price: row.get("buy_price"),
+               sell_price: row.get("sell_price"),
+               profit_percentage: row.get("profit_percentage"),
+               raw_spread: row.get("raw_spread"),
+               profit_usd: row.get("profit_usd"),
+               trade_size: row.get("trade_size"),
+               amount_in,
+               net_profit: row.get("net_profit"),
+               gas_costs: row.get("gas_costs"),
+               block_number: row.get("block_number"),
+               block_hash: row.get("block_hash"),
+               block_timestamp: row.get("block_timestamp"),
+               native_price_usd: row.get("native_price_usd"),
+               native_price_source: row.get("native_price_source"),
+               reorged: row.get("reorged"),
+               created_at: row.get("created_at"),
+           })
        }
 
        pub async fn get_stats(&self) -> Result<DatabaseStats> {
            let total_opportunities: (i64,) = sqlx::query_as(
-               "SELECT COUNT(*) FROM arbitrage_opportunities"
+               "SELECT COUNT(*) FROM arbitrage_opportunities WHERE reorged = FALSE"
+           )
+           .fetch_one(&self.pool)
+           .await?;
+
+           let reorged_opportunities: (i64,) = sqlx::query_as(
+               "SELECT COUNT(*) FROM arbitrage_opportunities WHERE reorged = TRUE"
            )
            .fetch_one(&self.pool)
            .await?;
 
-           let avg_profit: (Option<f64>,) = sqlx::query_as(
-               "SELECT AVG(profit_percentage) FROM arbitrage_opportunities WHERE created_at >= DATE_SUB(NOW(), INTERVAL 1 DAY)"
+           let avg_profit: (Option<Decimal>,) = sqlx::query_as(
+               "SELECT AVG(profit_percentage) FROM arbitrage_opportunities WHERE reorged = FALSE AND created_at >= DATE_SUB(NOW(), INTERVAL 1 DAY)"
            )
            .fetch_one(&self.pool)
            .await?;
 
-           let best_opportunity: Option<(String, f64)> = sqlx::query_as(
-               "SELECT token_pair, MAX(profit_percentage) FROM arbitrage_opportunities WHERE created_at >= DATE_SUB(NOW(), INTERVAL 1 DAY) GROUP BY token_pair ORDER BY MAX(profit_percentage) DESC LIMIT 1"
+           let best_opportunity: Option<(String, Decimal)> = sqlx::query_as(
+               "SELECT token_pair, MAX(profit_percentage) FROM arbitrage_opportunities WHERE reorged = FALSE AND created_at >= DATE_SUB(NOW(), INTERVAL 1 DAY) GROUP BY token_pair ORDER BY MAX(profit_percentage) DESC LIMIT 1"
            )
            .fetch_optional(&self.pool)
            .await?;
 
            Ok(DatabaseStats {
                total_opportunities: total_opportunities.0,
-               avg_daily_profit: avg_profit.0.unwrap_or(0.0),
-               best_daily_pair: best_opportunity.map(|(pair, profit)| (pair, profit)),
+               reorged_opportunities: reorged_opportunities.0,
+               avg_daily_profit: avg_profit.0.unwrap_or_default(),
+               best_daily_pair: best_opportunity,
            })
        }
+
+       /// Per-pair totals over opportunities stored since `since`, most frequent first.
+       pub async fn get_pair_stats(&self, since: DateTime<Utc>) -> Result<Vec<PairStats>> {
+           let rows = sqlx::query(
+               r#"
+               SELECT token_pair, COUNT(*) AS opportunities,
+                      AVG(profit_percentage) AS avg_profit, MAX(profit_percentage) AS max_profit,
+                      SUM(profit_usd) AS total_profit_usd, SUM(net_profit) AS total_net_profit
+               FROM arbitrage_opportunities
+               WHERE created_at >= ? AND reorged = FALSE
+               GROUP BY token_pair
+               ORDER BY opportunities DESC, token_pair
+               "#
+           )
+           .bind(since)
+           .fetch_all(&self.pool)
+           .await?;
+
+           Ok(rows
+               .iter()
+               .map(|row| PairStats {
+                   token_pair: row.get("token_pair"),
+                   opportunities: row.get("opportunities"),
+                   avg_profit: row.get::<Option<Decimal>, _>("avg_profit").unwrap_or_default(),
+                   max_profit: row.get::<Option<Decimal>, _>("max_profit").unwrap_or_default(),
+                   total_profit_usd: row.get::<Option<Decimal>, _>("total_profit_usd").unwrap_or_default(),
+                   total_net_profit: row.get("total_net_profit"),
+               })
+               .collect())
+       }
+   }
+
+   /// Narrows `get_opportunities`; unset fields match everything.
+   #[derive(Debug, Clone, Default)]
+   pub struct OpportunityFilter {
+       pub token_pair: Option<String>,
+       pub dex: Option<String>, // Either side of the trade
+       pub min_profit: Option<Decimal>,
+       pub since: Option<DateTime<Utc>>,
+       pub limit: Option<i32>,
+       pub include_reorged: bool, // Orphaned-block opportunities are left out otherwise
+   }
+
+   #[derive(Debug, Clone, Serialize)]
+   pub struct PairStats {
+       pub token_pair: String,
+       pub opportunities: i64,
+       pub avg_profit: Decimal,
+       pub max_profit: Decimal,
+       pub total_profit_usd: Decimal,
+       pub total_net_profit: Option<Decimal>,
+   }
+
+   #[derive(Debug, Clone, Serialize)]
+   pub struct StoredEndpointHealth {
+       #[serde(flatten)]
+       pub health: EndpointHealth,
+       pub updated_at: DateTime<Utc>,
    }
 
-   #[derive(Debug)]
+   #[derive(Debug, Serialize)]
    pub struct DatabaseStats {
-       pub total_opportunities: i64,
-       pub avg_daily_profit: f64,
-       pub best_daily_pair: Option<(String, f64)>,
+       pub total_opportunities: i64, // Excluding reorged ones
+       pub reorged_opportunities: i64,
+       pub avg_daily_profit: Decimal,
+       pub best_daily_pair: Option<(String, Decimal)>,
    }
\ No newline at end of file
@@ -1,3 +1,6 @@
 pub mod config;
 pub mod modules;
-pub mod database;
\ No newline at end of file
+pub mod database;
+pub mod scanner;
+pub mod cli;
+pub mod daemon;
\ No newline at end of file
@@ -0,0 +1,107 @@
+use anyhow::{Result, anyhow};
+   use ethers::types::{U256, U512};
+   use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+   use serde::{Deserialize, Serialize};
+   use std::fmt;
+
+   pub use rust_decimal::Decimal;
+
+   // Decimal stores a 96-bit mantissa with up to 28 digits after the point
+   const MAX_SCALE: u32 = 28;
+
+   /// A token amount in the token's smallest unit.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   pub struct TokenAmount {
+       pub raw: U256,
+       pub decimals: u8,
+   }
+
+   impl TokenAmount {
+       pub fn new(raw: U256, decimals: u8) -> Self {
+           Self { raw, decimals }
+       }
+
+       /// Nearest raw amount to `amount` whole tokens. None when negative or
+       /// too large.
+       pub fn from_f64(amount: f64, decimals: u8) -> Option<Self> {
+           let raw = (amount * 10f64.powi(decimals as i32)).round();
+           if !(0.0..u128::MAX as f64).contains(&raw) {
+               return None;
+           }
+           Some(Self::new(U256::from(raw as u128), decimals))
+       }
+
+       /// Parses a raw amount stored as a decimal string.
+       pub fn from_raw_str(raw: &str, decimals: u8) -> Result<Self> {
+           let raw = U256::from_dec_str(raw).map_err(|e| anyhow!("Invalid token amount {}: {}", raw, e))?;
+           Ok(Self::new(raw, decimals))
+       }
+
+       pub fn is_zero(self) -> bool {
+           self.raw.is_zero()
+       }
+
+       /// Whole tokens, exact to the 28 significant digits a Decimal holds.
+       /// None when the amount is beyond a Decimal's range.
+       pub fn to_decimal(self) -> Option<Decimal> {
+           ratio(self.raw, U256::exp10(self.decimals as usize))
+       }
+
+       /// Whole tokens as a float, for sizing and search math.
+       pub fn to_f64(self) -> f64 {
+           u256_to_f64(self.raw) / 10f64.powi(self.decimals as i32)
+       }
+   }
+
+   impl fmt::Display for TokenAmount {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+           match ethers::utils::format_units(self.raw, self.decimals as u32) {
+               Ok(amount) => write!(f, "{}", amount),
+               Err(_) => write!(f, "{} (raw)", self.raw),
+           }
+       }
+   }
+
+   /// `numerator / denominator`, truncated to the precision a Decimal holds.
+   /// None when dividing by zero or the quotient doesn't fit.
+   pub fn ratio(numerator: U256, denominator: U256) -> Option<Decimal> {
+       ratio_u512(U512::from(numerator), U512::from(denominator))
+   }
+
+   pub fn ratio_u512(numerator: U512, denominator: U512) -> Option<Decimal> {
+       if denominator.is_zero() {
+           return None;
+       }
+       let max_mantissa = U512::from(u128::MAX >> 32);
+
+       // Keep as many fractional digits as still fit the mantissa
+       for scale in (0..=MAX_SCALE).rev() {
+           let Some(scaled) = numerator.checked_mul(U512::exp10(scale as usize)) else {
+               continue;
+           };
+           let mantissa = scaled / denominator;
+           if mantissa <= max_mantissa {
+               return Some(Decimal::from_i128_with_scale(mantissa.as_u128() as i128, scale).normalize());
+           }
+       }
+
+       None
+   }
+
+   /// Converts a float (a fee, a search result) into a Decimal, dropping the
+   /// binary noise below its shortest representation.
+   pub fn decimal(value: f64) -> Decimal {
+       Decimal::from_f64(value).unwrap_or_default()
+   }
+
+   pub fn float(value: Decimal) -> f64 {
+       value.to_f64().unwrap_or(0.0)
+   }
+
+   pub fn u256_to_f64(value: U256) -> f64 {
+       value
+           .0
+           .iter()
+           .rev()
+           .fold(0.0, |acc, limb| acc * 2f64.powi(64) + *limb as f64)
+   }
\ No newline at end of file
@@ -1,5 +1,12 @@
 use anyhow::Result;
-   use crate::modules::price_fetcher::PriceData;
+   use crate::config::{Config, TokenPair};
+   use crate::modules::amounts::{self, Decimal, TokenAmount};
+   use crate::modules::gas_estimator::SwapLeg;
+   use crate::modules::price_fetcher::{BlockRef, PriceData};
+   use crate::modules::cycle_search::TokenGraph;
+   use crate::modules::route::Route;
+   use crate::modules::trade_sizing::{self, TradeSizing};
+   use crate::modules::triangular::{self, TriangularOpportunity};
    use serde::{Deserialize, Serialize};
 
    #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -7,17 +14,77 @@ use anyhow::Result;
        pub token_pair: String,
        pub buy_dex: String,
        pub sell_dex: String,
-       pub buy_price: f64,
-       pub sell_price: f64,
-       pub profit_percentage: f64,
-       pub profit_usd: f64,
-       pub trade_size: f64,
+       pub buy_price: Decimal,
+       pub sell_price: Decimal,
+       pub buy_fee: f64,
+       pub sell_fee: f64,
+       // Spread between quoted prices, before any swap fees
+       pub raw_spread: Decimal,
+       // Spread between effective execution prices, after both swap fees
+       pub profit_percentage: Decimal,
+       pub profit_usd: Decimal,
+       pub trade_size: Decimal, // USD
+       // Quote tokens into the buy leg; None when the quote token's decimals are unknown
+       pub amount_in: Option<TokenAmount>,
        pub timestamp: u64,
+       // Newest block of the two legs, and how many blocks apart they were read
+       pub block: Option<BlockRef>,
+       pub block_span: u64,
+       // Optimal size from pool reserves, in quote tokens; None when reserves are unknown
+       pub sizing: Option<TradeSizing>,
+       // Share of each leg's output lost to pool depth at this size
+       pub buy_impact: Option<f64>,
+       pub sell_impact: Option<f64>,
+   }
+
+   impl ArbitrageOpportunity {
+       /// The two swaps: quote -> base on the buy DEX, base -> quote on the sell DEX.
+       pub fn swap_legs(&self) -> Vec<SwapLeg> {
+           let (base, quote) = self.token_pair.split_once('/').unwrap_or((&self.token_pair, ""));
+           let quote_in = self.quote_amount().unwrap_or_default();
+           let base_in = quote_in * (1.0 - self.buy_fee) / amounts::float(self.buy_price);
+
+           vec![
+               SwapLeg {
+                   dex_name: self.buy_dex.clone(),
+                   token_in: quote.to_string(),
+                   token_out: base.to_string(),
+                   amount_in: quote_in,
+                   fee: self.buy_fee,
+                   price_impact: self.buy_impact,
+               },
+               SwapLeg {
+                   dex_name: self.sell_dex.clone(),
+                   token_in: base.to_string(),
+                   token_out: quote.to_string(),
+                   amount_in: base_in,
+                   fee: self.sell_fee,
+                   price_impact: self.sell_impact,
+               },
+           ]
+       }
+
+       /// Quote tokens put into the buy leg; None when the trade is unsized
+       /// and the quote token's USD price or decimals are unknown.
+       pub fn quote_amount(&self) -> Option<f64> {
+           match (&self.amount_in, &self.sizing) {
+               (Some(amount), _) => Some(amount.to_f64()),
+               (None, Some(sizing)) => Some(sizing.input_amount),
+               (None, None) => None,
+           }
+       }
+
+       /// Profit at the quoted prices after fees, before any price impact.
+       pub fn quoted_profit_usd(&self) -> Decimal {
+           self.trade_size * self.profit_percentage
+       }
    }
 
    pub struct ArbitrageDetector {
        min_profit_threshold: f64,
        default_trade_size: f64,
+       max_trade_size: Option<f64>,
+       max_block_lag: u64,
    }
 
    impl ArbitrageDetector {
@@ -25,6 +92,45 @@ use anyhow::Result;
            Self {
                min_profit_threshold,
                default_trade_size: 1000.0, // $1000 default trade size
+               max_trade_size: None,
+               max_block_lag: 0, // Only pair prices read from the same block
+           }
+       }
+
+       /// A detector with the config's threshold, trade sizes and block lag.
+       pub fn from_config(config: &Config) -> Self {
+           Self::new(config.min_profit_threshold)
+               .with_trade_size(config.trade_size)
+               .with_max_trade_size(config.max_trade_size)
+               .with_max_block_lag(config.max_block_lag)
+       }
+
+       /// Trade size, in USD, for quotes without the pool state to size against.
+       pub fn with_trade_size(mut self, usd: f64) -> Self {
+           self.default_trade_size = usd;
+           self
+       }
+
+       /// Caps sized trades at `usd`, whatever depth the pools offer.
+       pub fn with_max_trade_size(mut self, usd: Option<f64>) -> Self {
+           self.max_trade_size = usd;
+           self
+       }
+
+       /// Allows pairing prices read up to `blocks` blocks apart.
+       pub fn with_max_block_lag(mut self, blocks: u64) -> Self {
+           self.max_block_lag = blocks;
+           self
+       }
+
+       /// Whether two quotes describe chain states close enough to trade against
+       /// each other. Prices without block info only pair with each other.
+       fn blocks_compatible(&self, a: &Option<BlockRef>, b: &Option<BlockRef>) -> bool {
+           match (a, b) {
+               (None, None) => true,
+               (Some(a), Some(b)) if a.number == b.number => a.hash == b.hash,
+               (Some(a), Some(b)) => a.number.abs_diff(b.number) <= self.max_block_lag,
+               _ => false,
            }
        }
 
@@ -42,44 +148,104 @@ use anyhow::Result;
 
            // Check each token pair for arbitrage opportunities
            for (token_pair, pair_prices) in price_by_pair {
-               if let Some(opportunity) = self.find_best_arbitrage(token_pair, &pair_prices)? {
-                   if opportunity.profit_percentage >= self.min_profit_threshold {
+               let quote_usd_price = token_pair
+                   .split_once('/')
+                   .and_then(|(_, quote)| triangular::usd_price(quote, prices));
+               if let Some(opportunity) = self.find_best_arbitrage(token_pair, &pair_prices, quote_usd_price)? {
+                   if opportunity.profit_percentage >= amounts::decimal(self.min_profit_threshold) {
                        opportunities.push(opportunity);
                    }
                }
            }
 
            // Sort by profit percentage (highest first)
-           opportunities.sort_by(|a, b| b.profit_percentage.partial_cmp(&a.profit_percentage).unwrap());
+           opportunities.sort_by_key(|opportunity| std::cmp::Reverse(opportunity.profit_percentage));
 
            Ok(opportunities)
        }
 
-       fn find_best_arbitrage(&self, token_pair: &str, prices: &[&PriceData]) -> Result<Option<ArbitrageOpportunity>> {
+       fn find_best_arbitrage(
+           &self,
+           token_pair: &str,
+           prices: &[&PriceData],
+           quote_usd_price: Option<f64>,
+       ) -> Result<Option<ArbitrageOpportunity>> {
            if prices.len() < 2 {
                return Ok(None);
            }
 
-           let mut min_price = f64::MAX;
-           let mut max_price = f64::MIN;
-           let mut buy_dex = String::new();
-           let mut sell_dex = String::new();
-
-           // Find the lowest and highest prices
-           for price_data in prices {
-               if price_data.price < min_price {
-                   min_price = price_data.price;
-                   buy_dex = price_data.dex_name.clone();
-               }
-               if price_data.price > max_price {
-                   max_price = price_data.price;
-                   sell_dex = price_data.dex_name.clone();
+           // Find the widest after-fee spread between quotes from compatible blocks
+           let mut best: Option<(&PriceData, &PriceData)> = None;
+           for buy in prices {
+               for sell in prices {
+                   if buy.price <= Decimal::ZERO
+                       || std::ptr::eq(*buy, *sell)
+                       || !self.blocks_compatible(&buy.block, &sell.block)
+                   {
+                       continue;
+                   }
+                   let spread = effective_spread(buy, sell);
+                   if best.is_none_or(|(b, s)| spread > effective_spread(b, s)) {
+                       best = Some((buy, sell));
+                   }
                }
            }
 
+           let Some((buy, sell)) = best else {
+               return Ok(None);
+           };
+           let (min_price, max_price) = (buy.price, sell.price);
+           let buy_dex = buy.dex_name.clone();
+           let sell_dex = sell.dex_name.clone();
+
+           let block = match (buy.block, sell.block) {
+               (Some(a), Some(b)) => Some(if a.number >= b.number { a } else { b }),
+               _ => None,
+           };
+           let block_span = match (buy.block, sell.block) {
+               (Some(a), Some(b)) => a.number.abs_diff(b.number),
+               _ => 0,
+           };
+
            // Calculate profit
-           let profit_percentage = (max_price - min_price) / min_price;
-           let profit_usd = self.default_trade_size * profit_percentage;
+           let raw_spread = (max_price - min_price) / min_price;
+           let profit_percentage = effective_spread(buy, sell);
+           let mut trade_size = amounts::decimal(self.default_trade_size);
+           let mut profit_usd = trade_size * profit_percentage;
+
+           // With pool state on both sides, size the trade against price impact
+           let sizeable = trade_sizing::has_pool_state(buy) && trade_sizing::has_pool_state(sell);
+           let sizing = match quote_usd_price.filter(|_| sizeable) {
+               Some(quote_usd_price) => {
+                   let sized = trade_sizing::size_trade(buy, sell).and_then(|sizing| match self.max_trade_size {
+                       Some(max_usd) => trade_sizing::cap_trade(buy, sell, sizing, max_usd / quote_usd_price),
+                       None => Some(sizing),
+                   });
+                   let Some(sizing) = sized else {
+                       // The spread does not survive fees and price impact at any allowed size
+                       return Ok(None);
+                   };
+                   trade_size = amounts::decimal(sizing.input_amount * quote_usd_price);
+                   profit_usd = amounts::decimal(sizing.profit * quote_usd_price);
+                   Some(sizing)
+               }
+               None => None,
+           };
+
+           // Unsized trades convert the default USD size into quote tokens
+           let quote_in = match &sizing {
+               Some(sizing) => Some(sizing.input_amount),
+               None => quote_usd_price
+                   .filter(|price| *price > 0.0)
+                   .map(|price| self.default_trade_size / price),
+           };
+           let quote_decimals = buy.reserves.map(|reserves| reserves.quote.decimals);
+           let amount_in = quote_in.zip(quote_decimals).and_then(|(quote_in, decimals)| TokenAmount::from_f64(quote_in, decimals));
+           let buy_impact = quote_in.and_then(|quote_in| trade_sizing::price_impact(buy, false, quote_in));
+           let sell_impact = buy_impact.and_then(|_| {
+               let base_in = trade_sizing::swap_output(buy, false, quote_in?)?;
+               trade_sizing::price_impact(sell, true, base_in)
+           });
 
            Ok(Some(ArbitrageOpportunity {
                token_pair: token_pair.to_string(),
@@ -87,15 +253,164 @@ use anyhow::Result;
                sell_dex,
                buy_price: min_price,
                sell_price: max_price,
+               buy_fee: buy.fee,
+               sell_fee: sell.fee,
+               raw_spread,
                profit_percentage,
                profit_usd,
-               trade_size: self.default_trade_size,
+               trade_size,
+               amount_in,
                timestamp: std::time::SystemTime::now()
                    .duration_since(std::time::UNIX_EPOCH)?
                    .as_secs(),
+               block,
+               block_span,
+               sizing,
+               buy_impact,
+               sell_impact,
            }))
        }
 
+       pub fn detect_triangular_opportunities(
+           &self,
+           token_pairs: &[TokenPair],
+           prices: &[PriceData],
+       ) -> Result<Vec<TriangularOpportunity>> {
+           let mut opportunities = Vec::new();
+
+           for [a, b, c] in triangular::find_triangles(token_pairs) {
+               // Start from the stablecoin so profit is directly in USD terms
+               let [start, x, y] = if triangular::is_stablecoin(&b) {
+                   [b, c, a]
+               } else if triangular::is_stablecoin(&c) {
+                   [c, a, b]
+               } else {
+                   [a, b, c]
+               };
+
+               for cycle in [[&start, &x, &y], [&start, &y, &x]] {
+                   if let Some(opportunity) = self.find_best_cycle(cycle, prices)? {
+                       if opportunity.profit_percentage >= self.min_profit_threshold {
+                           opportunities.push(opportunity);
+                       }
+                   }
+               }
+           }
+
+           opportunities.sort_by(|a, b| b.profit_percentage.total_cmp(&a.profit_percentage));
+
+           Ok(opportunities)
+       }
+
+       fn find_best_cycle(&self, cycle: [&String; 3], prices: &[PriceData]) -> Result<Option<TriangularOpportunity>> {
+           let Some(start_usd_price) = triangular::usd_price(cycle[0], prices) else {
+               return Ok(None);
+           };
+
+           let first = triangular::edge_quotes(prices, cycle[0], cycle[1]);
+           let second = triangular::edge_quotes(prices, cycle[1], cycle[2]);
+           let third = triangular::edge_quotes(prices, cycle[2], cycle[0]);
+
+           // Try every DEX combination whose quotes come from compatible blocks
+           let mut best: Option<([(&PriceData, f64); 3], f64)> = None;
+           for &q1 in &first {
+               for &q2 in &second {
+                   if !self.blocks_compatible(&q1.0.block, &q2.0.block) {
+                       continue;
+                   }
+                   for &q3 in &third {
+                       if !self.blocks_compatible(&q1.0.block, &q3.0.block)
+                           || !self.blocks_compatible(&q2.0.block, &q3.0.block)
+                       {
+                           continue;
+                       }
+                       let product = q1.1 * q2.1 * q3.1;
+                       if best.is_none_or(|(_, best_product)| product > best_product) {
+                           best = Some(([q1, q2, q3], product));
+                       }
+                   }
+               }
+           }
+
+           let Some((quotes, _)) = best else {
+               return Ok(None);
+           };
+
+           Route::from_quotes(&cycle, &quotes, self.default_trade_size, start_usd_price).map(Some)
+       }
+
+       /// Profitable cycles of up to `max_hops` swaps anywhere in the token graph.
+       pub fn detect_cycles(&self, prices: &[PriceData], max_hops: usize) -> Result<Vec<Route>> {
+           let graph = TokenGraph::from_prices(prices);
+           let mut routes = Vec::new();
+
+           for cycle in graph.find_negative_cycles(max_hops) {
+               let edges: Vec<_> = cycle.iter().map(|&i| *graph.edge(i)).collect();
+
+               let compatible = edges.iter().enumerate().all(|(i, a)| {
+                   edges[i + 1..]
+                       .iter()
+                       .all(|b| self.blocks_compatible(&a.quote.block, &b.quote.block))
+               });
+               if !compatible {
+                   continue;
+               }
+
+               // Start at a stablecoin when the cycle has one, so profit is in USD terms
+               let start = edges
+                   .iter()
+                   .position(|edge| triangular::is_stablecoin(graph.token(edge.from)))
+                   .or_else(|| {
+                       edges
+                           .iter()
+                           .position(|edge| triangular::usd_price(graph.token(edge.from), prices).is_some())
+                   });
+               let Some(start) = start else {
+                   continue;
+               };
+               let edges: Vec<_> = edges[start..].iter().chain(&edges[..start]).collect();
+
+               let tokens: Vec<String> = edges.iter().map(|edge| graph.token(edge.from).to_string()).collect();
+               let Some(start_usd_price) = triangular::usd_price(&tokens[0], prices) else {
+                   continue;
+               };
+               let token_refs: Vec<&String> = tokens.iter().collect();
+               let quotes: Vec<(&PriceData, f64)> = edges.iter().map(|edge| (edge.quote, edge.rate)).collect();
+
+               let route = Route::from_quotes(&token_refs, &quotes, self.default_trade_size, start_usd_price)?;
+               if route.profit_percentage >= self.min_profit_threshold {
+                   routes.push(route);
+               }
+           }
+
+           routes.sort_by(|a, b| b.profit_percentage.total_cmp(&a.profit_percentage));
+
+           Ok(routes)
+       }
+
+       /// Prints cycle routes; `kind` labels them, e.g. "Triangular" or "Multi-hop".
+       pub fn print_routes(&self, kind: &str, routes: &[Route]) {
+           if routes.is_empty() {
+               println!("❌ No {} opportunities found above {:.2}% threshold", kind.to_lowercase(), self.min_profit_threshold * 100.0);
+               return;
+           }
+
+           println!("🔺 Found {} {} Opportunities:", routes.len(), kind);
+           println!("{}", "=".repeat(80));
+
+           for (i, opp) in routes.iter().enumerate() {
+               println!("🎯 Route #{}: {}", i + 1, opp.route());
+               for hop in &opp.hops {
+                   println!("   {:>6} -> {:<6} on {:<16} {:.6} in -> {:.6} out (fee {:.2}%)",
+                           hop.from_token, hop.to_token, hop.dex_name,
+                           hop.amount_in, hop.amount_out, hop.fee * 100.0);
+               }
+               println!("   Profit:     {:.3}% (${:.2} on ${:.0} trade)",
+                       opp.profit_percentage * 100.0, opp.profit_usd, opp.trade_size);
+               println!("{}", "-".repeat(50));
+           }
+       }
+
        pub fn print_opportunities(&self, opportunities: &[ArbitrageOpportunity]) {
            if opportunities.is_empty() {
                println!("❌ No arbitrage opportunities found above {:.2}% threshold", self.min_profit_threshold * 100.0);
@@ -110,9 +425,33 @@ use anyhow::Result;
                println!("   Token Pair: {}", opp.token_pair);
                println!("   Buy from:   {} at ${:.4}", opp.buy_dex, opp.buy_price);
                println!("   Sell on:    {} at ${:.4}", opp.sell_dex, opp.sell_price);
+               println!("   Spread:     {:.3}% raw, {:.3}% after {:.2}% + {:.2}% fees",
+                       opp.raw_spread * Decimal::ONE_HUNDRED, opp.profit_percentage * Decimal::ONE_HUNDRED,
+                       opp.buy_fee * 100.0, opp.sell_fee * 100.0);
                println!("   Profit:     {:.2}% (${:.2} on ${:.0} trade)", 
-                       opp.profit_percentage * 100.0, opp.profit_usd, opp.trade_size);
+                       opp.profit_percentage * Decimal::ONE_HUNDRED, opp.profit_usd, opp.trade_size);
+               if let Some(sizing) = &opp.sizing {
+                   println!("   Optimal in: {:.6} -> {:.6} out", sizing.input_amount, sizing.expected_output);
+               }
+               if let Some(block) = &opp.block {
+                   println!("   Block:      #{} ({:?})", block.number, block.hash);
+               }
                println!("{}", "-".repeat(50));
            }
        }
+   }
+
+   /// Quote tokens paid per base token bought, swap fee included.
+   pub fn effective_buy_price(price: &PriceData) -> Decimal {
+       price.price / (Decimal::ONE - amounts::decimal(price.fee))
+   }
+
+   /// Quote tokens received per base token sold, swap fee deducted.
+   pub fn effective_sell_price(price: &PriceData) -> Decimal {
+       price.price * (Decimal::ONE - amounts::decimal(price.fee))
+   }
+
+   fn effective_spread(buy: &PriceData, sell: &PriceData) -> Decimal {
+       let buy_price = effective_buy_price(buy);
+       (effective_sell_price(sell) - buy_price) / buy_price
    }
\ No newline at end of file
@@ -0,0 +1,158 @@
+use crate::modules::amounts::Decimal;
+   use crate::modules::price_fetcher::PriceData;
+   use std::collections::{BTreeSet, HashMap};
+
+   // Cycles must beat break-even by more than float noise
+   const EPSILON: f64 = 1e-12;
+
+   /// A directed swap between two tokens, weighted by -ln(rate after fees)
+   /// so that a profitable cycle is a negative-weight cycle.
+   #[derive(Debug, Clone, Copy)]
+   pub struct GraphEdge<'a> {
+       pub from: usize,
+       pub to: usize,
+       pub rate: f64,
+       pub weight: f64,
+       pub quote: &'a PriceData,
+   }
+
+   /// Tokens as nodes, every quote as a pair of directed edges.
+   pub struct TokenGraph<'a> {
+       tokens: Vec<String>,
+       index: HashMap<String, usize>,
+       edges: Vec<GraphEdge<'a>>,
+   }
+
+   impl<'a> TokenGraph<'a> {
+       pub fn from_prices(prices: &'a [PriceData]) -> Self {
+           let mut graph = TokenGraph {
+               tokens: Vec::new(),
+               index: HashMap::new(),
+               edges: Vec::new(),
+           };
+
+           for price in prices.iter().filter(|price| price.price > Decimal::ZERO) {
+               let Some((base, quote)) = price.token_pair.split_once('/') else {
+                   continue;
+               };
+               let base = graph.token_index(base);
+               let quote = graph.token_index(quote);
+
+               graph.add_edge(base, quote, price.price_f64() * (1.0 - price.fee), price);
+               graph.add_edge(quote, base, (1.0 / price.price_f64()) * (1.0 - price.fee), price);
+           }
+
+           graph
+       }
+
+       fn token_index(&mut self, token: &str) -> usize {
+           if let Some(index) = self.index.get(token) {
+               return *index;
+           }
+           self.tokens.push(token.to_string());
+           self.index.insert(token.to_string(), self.tokens.len() - 1);
+           self.tokens.len() - 1
+       }
+
+       fn add_edge(&mut self, from: usize, to: usize, rate: f64, quote: &'a PriceData) {
+           if rate <= 0.0 {
+               return;
+           }
+           self.edges.push(GraphEdge {
+               from,
+               to,
+               rate,
+               weight: -rate.ln(),
+               quote,
+           });
+       }
+
+       pub fn token(&self, index: usize) -> &str {
+           &self.tokens[index]
+       }
+
+       pub fn edge(&self, index: usize) -> &GraphEdge<'a> {
+           &self.edges[index]
+       }
+
+       pub fn token_count(&self) -> usize {
+           self.tokens.len()
+       }
+
+       pub fn edge_count(&self) -> usize {
+           self.edges.len()
+       }
+
+       /// Profitable simple cycles of 2..=`max_hops` swaps, as lists of edge
+       /// indices. Runs a hop-limited Bellman-Ford from every token: layer `k`
+       /// holds the cheapest path of exactly `k` edges, so a negative distance
+       /// back to the source at layer `k` is a negative cycle of length `k`.
+       pub fn find_negative_cycles(&self, max_hops: usize) -> Vec<Vec<usize>> {
+           let n = self.tokens.len();
+           let mut seen = BTreeSet::new();
+           let mut cycles = Vec::new();
+
+           for source in 0..n {
+               let mut dist = vec![vec![f64::INFINITY; n]; max_hops + 1];
+               let mut pred: Vec<Vec<Option<usize>>> = vec![vec![None; n]; max_hops + 1];
+               dist[0][source] = 0.0;
+
+               for k in 1..=max_hops {
+                   for (edge_index, edge) in self.edges.iter().enumerate() {
+                       let candidate = dist[k - 1][edge.from] + edge.weight;
+                       if candidate < dist[k][edge.to] {
+                           dist[k][edge.to] = candidate;
+                           pred[k][edge.to] = Some(edge_index);
+                       }
+                   }
+
+                   if k < 2 || dist[k][source] >= -EPSILON {
+                       continue;
+                   }
+                   let Some(cycle) = self.walk_back(&pred, source, k) else {
+                       continue;
+                   };
+
+                   // The same cycle is found from each of its tokens
+                   if seen.insert(canonical(&cycle)) {
+                       cycles.push(cycle);
+                   }
+               }
+           }
+
+           cycles
+       }
+
+       // Rebuilds the k-edge path ending at `source`; None if it revisits a token
+       fn walk_back(&self, pred: &[Vec<Option<usize>>], source: usize, k: usize) -> Option<Vec<usize>> {
+           let mut cycle = Vec::with_capacity(k);
+           let mut visited = BTreeSet::new();
+           let mut node = source;
+
+           for level in (1..=k).rev() {
+               let edge_index = pred[level][node]?;
+               cycle.push(edge_index);
+               node = self.edges[edge_index].from;
+               if !visited.insert(node) {
+                   return None;
+               }
+           }
+
+           if node != source {
+               return None;
+           }
+           cycle.reverse();
+           Some(cycle)
+       }
+   }
+
+   // Rotation-independent key for a cycle of edge indices
+   fn canonical(cycle: &[usize]) -> Vec<usize> {
+       let start = cycle
+           .iter()
+           .enumerate()
+           .min_by_key(|(_, edge)| **edge)
+           .map(|(i, _)| i)
+           .unwrap_or(0);
+       cycle[start..].iter().chain(&cycle[..start]).copied().collect()
+   }
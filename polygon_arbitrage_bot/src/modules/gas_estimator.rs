@@ -0,0 +1,282 @@
+use anyhow::{Result, anyhow};
+   use ethers::abi::AbiEncode;
+   use ethers::prelude::*;
+   use ethers::types::transaction::eip2718::TypedTransaction;
+   use serde::{Deserialize, Serialize};
+   use std::collections::HashMap;
+   use std::sync::{Arc, Mutex};
+   use crate::config::Config;
+   use crate::modules::price_fetcher;
+   use crate::modules::price_source::DexKind;
+   use crate::modules::rpc_pool::RpcClient;
+   use crate::modules::token_registry::{self, TokenRegistry};
+   use crate::modules::routers::{ExactInputSingleCall, ExactInputSingleParams, SwapExactTokensForTokensCall};
+
+   // How far ahead of now a simulated swap's deadline is set
+   const DEADLINE_SECS: u64 = 300;
+
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   #[serde(rename_all = "snake_case")]
+   pub enum GasEstimateSource {
+       Simulated,
+       Model,
+       Default,
+   }
+
+   impl GasEstimateSource {
+       pub fn as_str(&self) -> &'static str {
+           match self {
+               GasEstimateSource::Simulated => "simulated",
+               GasEstimateSource::Model => "model",
+               GasEstimateSource::Default => "default",
+           }
+       }
+   }
+
+   /// Gas units a single swap is expected to use.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   pub struct GasEstimate {
+       pub units: u64,
+       pub source: GasEstimateSource,
+   }
+
+   /// One swap of a trade: `amount_in` whole `token_in` for `token_out` on `dex_name`.
+   #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+   pub struct SwapLeg {
+       pub dex_name: String,
+       pub token_in: String,
+       pub token_out: String,
+       pub amount_in: f64, // Zero when the trade's size in tokens is unknown
+       pub fee: f64, // Pool fee, which picks the V3 fee tier
+       // Share of the output lost to pool depth; None when the pool state is unknown
+       pub price_impact: Option<f64>,
+   }
+
+   /// Gas per swap when it can't be simulated: a default per kind of DEX,
+   /// replaced by the average of the estimates seen for a DEX once there are any.
+   #[derive(Debug, Clone)]
+   pub struct GasModel {
+       defaults: HashMap<DexKind, u64>,
+       fallback: u64,
+       observed: HashMap<String, (u64, u64)>, // dex -> (total units, samples)
+   }
+
+   impl GasModel {
+       pub fn new() -> Self {
+           Self {
+               defaults: HashMap::from([
+                   (DexKind::UniswapV2, 130_000),
+                   (DexKind::UniswapV3, 160_000), // Before any tick crossings
+               ]),
+               fallback: 200_000,
+               observed: HashMap::new(),
+           }
+       }
+
+       pub fn with_default(mut self, kind: DexKind, units: u64) -> Self {
+           self.defaults.insert(kind, units);
+           self
+       }
+
+       pub fn units(&self, dex_name: &str, kind: Option<DexKind>) -> u64 {
+           if let Some((total, samples)) = self.observed.get(dex_name) {
+               return total / samples;
+           }
+           kind.and_then(|kind| self.defaults.get(&kind).copied())
+               .unwrap_or(self.fallback)
+       }
+
+       pub fn record(&mut self, dex_name: &str, units: u64) {
+           let entry = self.observed.entry(dex_name.to_string()).or_insert((0, 0));
+           entry.0 += units;
+           entry.1 += 1;
+       }
+   }
+
+   impl Default for GasModel {
+       fn default() -> Self {
+           Self::new()
+       }
+   }
+
+   /// Estimates swap gas with `eth_estimateGas` on the real router calldata,
+   /// sent from a configured account. Falls back to the `GasModel` when there
+   /// is no sender or the simulation reverts (e.g. missing balance or allowance).
+   pub struct GasEstimator {
+       client: Arc<RpcClient>,
+       sender: Option<Address>,
+       kinds: HashMap<String, DexKind>,
+       routers: HashMap<String, Address>,
+       tokens: HashMap<String, Address>,
+       registry: Arc<TokenRegistry>,
+       model: Mutex<GasModel>,
+   }
+
+   impl GasEstimator {
+       pub fn new(client: Arc<RpcClient>) -> Self {
+           Self {
+               registry: Arc::new(TokenRegistry::new(client.clone())),
+               client,
+               sender: None,
+               kinds: HashMap::new(),
+               routers: HashMap::new(),
+               tokens: HashMap::new(),
+               model: Mutex::new(GasModel::new()),
+           }
+       }
+
+       /// Routers from the configured DEXes and token addresses from the pairs.
+       pub fn from_config(client: Arc<RpcClient>, config: &Config) -> Result<Self> {
+           let mut estimator = Self::new(client);
+           if let Some(sender) = &config.gas_estimate_sender {
+               estimator = estimator.with_sender(sender)?;
+           }
+           for dex in &config.dexes {
+               estimator = estimator.with_dex(&dex.name, dex.kind, &dex.router)?;
+           }
+           for pair in &config.token_pairs {
+               estimator = estimator
+                   .with_token(&pair.base, &pair.base_address)?
+                   .with_token(&pair.quote, &pair.quote_address)?;
+           }
+
+           Ok(estimator)
+       }
+
+       /// Account swaps are simulated from; it needs the input balances and allowances.
+       pub fn with_sender(mut self, sender: &str) -> Result<Self> {
+           self.sender = Some(price_fetcher::parse_address(sender)?);
+           Ok(self)
+       }
+
+       pub fn with_dex(mut self, name: &str, kind: DexKind, router: &str) -> Result<Self> {
+           self.kinds.insert(name.to_string(), kind);
+           if kind != DexKind::Simulated {
+               self.routers.insert(name.to_string(), price_fetcher::parse_address(router)?);
+           }
+           Ok(self)
+       }
+
+       pub fn with_token(mut self, symbol: &str, address: &str) -> Result<Self> {
+           self.tokens.insert(symbol.to_string(), token_registry::parse_checksummed(address)?);
+           Ok(self)
+       }
+
+       /// Shares token metadata with the price sources instead of reading it again.
+       pub fn with_token_registry(mut self, registry: Arc<TokenRegistry>) -> Self {
+           self.registry = registry;
+           self
+       }
+
+       pub fn with_model(self, model: GasModel) -> Self {
+           *self.model.lock().unwrap() = model;
+           self
+       }
+
+       /// Gas for `leg` as of `block`: simulated when possible, from the model otherwise.
+       pub async fn estimate(&self, leg: &SwapLeg, block: Option<u64>) -> GasEstimate {
+           let dex = self.configured_dex(&leg.dex_name);
+           match self.simulate(leg, block).await {
+               Ok(units) => {
+                   // Successful simulations calibrate the model for later fallbacks
+                   self.model.lock().unwrap().record(dex, units);
+                   GasEstimate {
+                       units,
+                       source: GasEstimateSource::Simulated,
+                   }
+               }
+               Err(_) => GasEstimate {
+                   units: self.model.lock().unwrap().units(dex, self.kinds.get(dex).copied()),
+                   source: GasEstimateSource::Model,
+               },
+           }
+       }
+
+       async fn simulate(&self, leg: &SwapLeg, block: Option<u64>) -> Result<u64> {
+           let sender = self.sender.ok_or_else(|| anyhow!("No sender configured for gas estimation"))?;
+           if leg.amount_in <= 0.0 {
+               return Err(anyhow!("No amount to simulate a swap on {}", leg.dex_name));
+           }
+           let (router, calldata) = self.swap_calldata(leg, sender).await?;
+
+           let tx: TypedTransaction = TransactionRequest::new()
+               .from(sender)
+               .to(router)
+               .data(calldata)
+               .into();
+           let units = self
+               .client
+               .estimate_gas(&tx, block.map(|block| BlockId::Number(block.into())))
+               .await?;
+
+           Ok(units.as_u64())
+       }
+
+       /// Router address and calldata swapping `leg` exactly-in, paying `recipient`.
+       pub async fn swap_calldata(&self, leg: &SwapLeg, recipient: Address) -> Result<(Address, Bytes)> {
+           let dex = self.configured_dex(&leg.dex_name);
+           let router = *self
+               .routers
+               .get(dex)
+               .ok_or_else(|| anyhow!("No router known for {}", leg.dex_name))?;
+           let token_in = self.token(&leg.token_in)?;
+           let token_out = self.token(&leg.token_out)?;
+           let amount_in = to_units(leg.amount_in, self.registry.decimals(token_in).await?)?;
+           let deadline = U256::from(price_fetcher::unix_timestamp()? + DEADLINE_SECS);
+
+           let calldata = match self.kinds.get(dex) {
+               Some(DexKind::UniswapV2) => SwapExactTokensForTokensCall {
+                   amount_in,
+                   amount_out_min: U256::zero(),
+                   path: vec![token_in, token_out],
+                   to: recipient,
+                   deadline,
+               }
+               .encode(),
+               Some(DexKind::UniswapV3) => ExactInputSingleCall {
+                   params: ExactInputSingleParams {
+                       token_in,
+                       token_out,
+                       fee: (leg.fee * 1_000_000.0).round() as u32,
+                       recipient,
+                       deadline,
+                       amount_in,
+                       amount_out_minimum: U256::zero(),
+                       sqrt_price_limit_x96: U256::zero(),
+                   },
+               }
+               .encode(),
+               _ => return Err(anyhow!("{} has no router to simulate against", leg.dex_name)),
+           };
+
+           Ok((router, Bytes::from(calldata)))
+       }
+
+       // The configured DEX a quote came from: V3 quotes are named per fee
+       // tier, like uniswap_v3_500, and share their DEX's router and gas
+       fn configured_dex<'a>(&self, dex_name: &'a str) -> &'a str {
+           if self.kinds.contains_key(dex_name) {
+               return dex_name;
+           }
+           match dex_name.rsplit_once('_') {
+               Some((dex, fee))
+                   if fee.parse::<u32>().is_ok() && self.kinds.get(dex) == Some(&DexKind::UniswapV3) => dex,
+               _ => dex_name,
+           }
+       }
+
+       fn token(&self, symbol: &str) -> Result<Address> {
+           self.tokens
+               .get(symbol)
+               .copied()
+               .ok_or_else(|| anyhow!("Unknown token {}", symbol))
+       }
+   }
+
+   fn to_units(amount: f64, decimals: u8) -> Result<U256> {
+       let raw = amount * 10f64.powi(decimals as i32);
+       if !(0.0..u128::MAX as f64).contains(&raw) {
+           return Err(anyhow!("Amount {} is out of range", amount));
+       }
+       Ok(U256::from(raw as u128))
+   }
\ No newline at end of file
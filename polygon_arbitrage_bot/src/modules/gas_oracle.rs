@@ -0,0 +1,156 @@
+use anyhow::{Result, anyhow};
+   use ethers::prelude::*;
+   use serde::{Deserialize, Serialize};
+   use std::sync::{Arc, Mutex};
+   use crate::modules::rpc_pool::RpcClient;
+
+   // Blocks of fee history sampled for the priority fee
+   const FEE_HISTORY_BLOCKS: u64 = 5;
+
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   #[serde(rename_all = "snake_case")]
+   pub enum GasPriceSource {
+       FeeHistory,
+       GasPrice,
+       Default,
+   }
+
+   /// Fee parameters a transaction would pay, in wei.
+   #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+   pub struct GasPrice {
+       pub base_fee_wei: U256,
+       pub priority_fee_wei: U256,
+       pub reward_percentile: Option<f64>,
+       pub block_number: Option<u64>, // Block the fees were read at
+       pub source: GasPriceSource,
+   }
+
+   impl GasPrice {
+       /// Fixed price used when no oracle is available.
+       pub fn fixed(gas_price_gwei: f64) -> Self {
+           Self {
+               base_fee_wei: ethers::utils::parse_units(gas_price_gwei, "gwei")
+                   .map(U256::from)
+                   .unwrap_or_default(),
+               priority_fee_wei: U256::zero(),
+               reward_percentile: None,
+               block_number: None,
+               source: GasPriceSource::Default,
+           }
+       }
+
+       pub fn gas_price_wei(&self) -> U256 {
+           self.base_fee_wei.saturating_add(self.priority_fee_wei)
+       }
+
+       pub fn gas_price_gwei(&self) -> f64 {
+           wei_to_gwei(self.gas_price_wei())
+       }
+
+       pub fn base_fee_gwei(&self) -> f64 {
+           wei_to_gwei(self.base_fee_wei)
+       }
+
+       pub fn priority_fee_gwei(&self) -> f64 {
+           wei_to_gwei(self.priority_fee_wei)
+       }
+   }
+
+   /// Reads gas prices from the node: next block's base fee plus a percentile
+   /// of recent priority fees, cached per block.
+   pub struct GasOracle {
+       client: Arc<RpcClient>,
+       reward_percentile: f64,
+       cached: Mutex<Option<GasPrice>>,
+   }
+
+   impl GasOracle {
+       pub fn new(client: Arc<RpcClient>) -> Self {
+           Self {
+               client,
+               reward_percentile: 50.0,
+               cached: Mutex::new(None),
+           }
+       }
+
+       pub fn with_reward_percentile(mut self, percentile: f64) -> Self {
+           self.reward_percentile = percentile;
+           self
+       }
+
+       /// Gas price as of `block`, or the latest block when None.
+       pub async fn gas_price(&self, block: Option<u64>) -> Result<GasPrice> {
+           let block = match block {
+               Some(block) => block,
+               None => self.client.get_block_number().await?.as_u64(),
+           };
+           if let Some(cached) = *self.cached.lock().unwrap() {
+               if cached.block_number == Some(block) {
+                   return Ok(cached);
+               }
+           }
+
+           match self.read_fee_history(block).await {
+               Ok(gas_price) => {
+                   *self.cached.lock().unwrap() = Some(gas_price);
+                   Ok(gas_price)
+               }
+               // Nodes without EIP-1559 support still answer eth_gasPrice
+               Err(e) => {
+                   eprintln!("⚠️  Fee history at block #{} failed, using eth_gasPrice: {}", block, e);
+                   self.read_gas_price().await
+               }
+           }
+       }
+
+       async fn read_fee_history(&self, block: u64) -> Result<GasPrice> {
+           let history = self
+               .client
+               .fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Number(block.into()), &[self.reward_percentile])
+               .await?;
+
+           // The last entry is the base fee of the block after `block`
+           let base_fee = history
+               .base_fee_per_gas
+               .last()
+               .ok_or_else(|| anyhow!("Fee history has no base fee"))?;
+           // Median over the sampled blocks of each block's percentile reward
+           let mut rewards: Vec<U256> = history
+               .reward
+               .iter()
+               .filter_map(|rewards| rewards.first().copied())
+               .collect();
+           if rewards.is_empty() {
+               return Err(anyhow!("Fee history has no priority fee rewards"));
+           }
+           rewards.sort();
+
+           Ok(GasPrice {
+               base_fee_wei: *base_fee,
+               priority_fee_wei: rewards[rewards.len() / 2],
+               reward_percentile: Some(self.reward_percentile),
+               block_number: Some(block),
+               source: GasPriceSource::FeeHistory,
+           })
+       }
+
+       // eth_gasPrice can't be pinned to a block, so it is neither stamped nor cached as one
+       async fn read_gas_price(&self) -> Result<GasPrice> {
+           let gas_price = self.client.get_gas_price().await?;
+
+           Ok(GasPrice {
+               base_fee_wei: gas_price,
+               priority_fee_wei: U256::zero(),
+               reward_percentile: None,
+               block_number: None,
+               source: GasPriceSource::GasPrice,
+           })
+       }
+   }
+
+   fn wei_to_gwei(wei: U256) -> f64 {
+       ethers::utils::format_units(wei, "gwei")
+           .ok()
+           .and_then(|s| s.parse::<f64>().ok())
+           .unwrap_or(0.0)
+   }
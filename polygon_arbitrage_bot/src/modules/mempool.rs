@@ -0,0 +1,453 @@
+use anyhow::{Result, anyhow};
+   use ethers::abi::AbiDecode;
+   use ethers::prelude::*;
+   use ethers::utils::rlp;
+   use serde::Serialize;
+   use std::collections::HashMap;
+   use std::sync::atomic::{AtomicU64, Ordering};
+   use std::sync::Arc;
+   use tokio::sync::mpsc;
+   use tokio::task::JoinHandle;
+   use crate::config::{Config, DexConfig, TokenPair};
+   use crate::modules::amounts::{self, Decimal};
+   use crate::modules::arbitrage_detector::{ArbitrageDetector, ArbitrageOpportunity};
+   use crate::modules::new_heads::Backoff;
+   use crate::modules::price_fetcher::{self, PriceData};
+   use crate::modules::price_source::DexKind;
+   use crate::modules::routers::{ExactInputCall, ExactInputSingleCall, ISwapRouterCalls, MulticallCall, SwapExactTokensForTokensCall};
+   use crate::modules::uniswap_v2::{self, PairReserves};
+   use crate::modules::uniswap_v3::{self, PoolState};
+
+   // Pending transactions held for a reader that falls behind
+   const PENDING_BUFFER: usize = 4096;
+
+   /// One pool a pending swap trades through.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+   pub struct SwapHop {
+       pub token_in: Address,
+       pub token_out: Address,
+       pub fee: Option<u32>, // V3 fee tier; None on V2 routers
+   }
+
+   /// An exact-input swap a pending transaction sends to a configured router.
+   #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+   pub struct PendingSwap {
+       pub dex: String,
+       pub hops: Vec<SwapHop>,
+       pub amount_in: U256,
+   }
+
+   /// A market's price before and after the pending swaps land.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct MovedMarket {
+       pub dex_name: String,
+       pub token_pair: String,
+       pub price_before: Decimal,
+       pub price_after: Decimal,
+   }
+
+   impl MovedMarket {
+       pub fn change(&self) -> f64 {
+           amounts::float(self.price_after) / amounts::float(self.price_before) - 1.0
+       }
+   }
+
+   /// What one pending transaction would do to the quoted markets.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct PendingReport {
+       pub tx_hash: H256,
+       pub from: Address,
+       pub swaps: Vec<PendingSwap>,
+       pub moved: Vec<MovedMarket>,
+       // Opportunities on the projected prices that involve a moved market
+       pub opportunities: Vec<ArbitrageOpportunity>,
+   }
+
+   /// Decodes pending router calls and projects them onto current prices to
+   /// find the arbitrage they would open once mined.
+   pub struct MempoolWatcher {
+       routers: HashMap<Address, (String, DexKind)>,
+       pairs: HashMap<String, (Address, Address)>, // "BASE/QUOTE" -> addresses
+       detector: ArbitrageDetector,
+   }
+
+   impl MempoolWatcher {
+       pub fn new(dexes: &[DexConfig], pairs: &[TokenPair], detector: ArbitrageDetector) -> Result<Self> {
+           let mut routers = HashMap::new();
+           for dex in dexes.iter().filter(|dex| dex.kind != DexKind::Simulated) {
+               routers.insert(price_fetcher::parse_address(&dex.router)?, (dex.name.clone(), dex.kind));
+           }
+
+           let mut addresses = HashMap::new();
+           for pair in pairs {
+               addresses.insert(
+                   format!("{}/{}", pair.base, pair.quote),
+                   (price_fetcher::parse_address(&pair.base_address)?, price_fetcher::parse_address(&pair.quote_address)?),
+               );
+           }
+
+           Ok(Self {
+               routers,
+               pairs: addresses,
+               detector,
+           })
+       }
+
+       pub fn from_config(config: &Config) -> Result<Self> {
+           Self::new(&config.dexes, &config.token_pairs, ArbitrageDetector::from_config(config))
+       }
+
+       /// Swaps `tx` asks a configured router for. Empty for any other
+       /// transaction, including router calls that are not exact-input swaps.
+       pub fn decode(&self, tx: &Transaction) -> Vec<PendingSwap> {
+           let Some((dex, kind)) = tx.to.and_then(|to| self.routers.get(&to)) else {
+               return Vec::new();
+           };
+
+           let mut routes: Vec<(Vec<SwapHop>, U256)> = Vec::new();
+           match kind {
+               DexKind::UniswapV2 => {
+                   if let Ok(call) = SwapExactTokensForTokensCall::decode(&tx.input) {
+                       let hops = call
+                           .path
+                           .windows(2)
+                           .map(|tokens| SwapHop {
+                               token_in: tokens[0],
+                               token_out: tokens[1],
+                               fee: None,
+                           })
+                           .collect();
+                       routes.push((hops, call.amount_in));
+                   }
+               }
+               DexKind::UniswapV3 => decode_v3_call(&tx.input, &mut routes),
+               DexKind::Simulated => {}
+           }
+
+           routes
+               .into_iter()
+               .filter(|(hops, _)| !hops.is_empty())
+               .map(|(hops, amount_in)| PendingSwap {
+                   dex: dex.clone(),
+                   hops,
+                   amount_in,
+               })
+               .collect()
+       }
+
+       /// Prices after `swaps` execute in order, with the markets they moved.
+       /// A route stops at the first hop through a market that is not quoted
+       /// or can't be simulated, since its output is unknown from there on.
+       pub fn project(&self, prices: &[PriceData], swaps: &[PendingSwap]) -> (Vec<PriceData>, Vec<MovedMarket>) {
+           let mut projected = prices.to_vec();
+           for swap in swaps {
+               let mut amount = swap.amount_in;
+               for hop in &swap.hops {
+                   let Some((index, sell_base)) = self.market(&projected, &swap.dex, hop) else {
+                       break;
+                   };
+                   let Some(amount_out) = apply_hop(&mut projected[index], hop, sell_base, amount) else {
+                       break;
+                   };
+                   amount = amount_out;
+               }
+           }
+
+           let moved = prices
+               .iter()
+               .zip(&projected)
+               .filter(|(before, after)| before.price != after.price)
+               .map(|(before, after)| MovedMarket {
+                   dex_name: before.dex_name.clone(),
+                   token_pair: before.token_pair.clone(),
+                   price_before: before.price,
+                   price_after: after.price,
+               })
+               .collect();
+           (projected, moved)
+       }
+
+       /// Decodes `tx`, projects its swaps onto `prices` and detects the
+       /// opportunities that involve a market it moves. None when it swaps
+       /// through none of the configured routers.
+       pub fn evaluate(&self, prices: &[PriceData], tx: &Transaction) -> Result<Option<PendingReport>> {
+           let swaps = self.decode(tx);
+           if swaps.is_empty() {
+               return Ok(None);
+           }
+
+           let (projected, moved) = self.project(prices, &swaps);
+           let mut opportunities = Vec::new();
+           if !moved.is_empty() {
+               opportunities = self.detector.detect_opportunities(&projected)?;
+               opportunities.retain(|opportunity| {
+                   moved.iter().any(|market| {
+                       market.token_pair == opportunity.token_pair
+                           && (market.dex_name == opportunity.buy_dex || market.dex_name == opportunity.sell_dex)
+                   })
+               });
+           }
+
+           Ok(Some(PendingReport {
+               tx_hash: tx.hash,
+               from: tx.from,
+               swaps,
+               moved,
+               opportunities,
+           }))
+       }
+
+       /// Like `evaluate`, for a signed transaction in its raw broadcast form.
+       pub fn evaluate_raw(&self, prices: &[PriceData], raw: &[u8]) -> Result<Option<PendingReport>> {
+           self.evaluate(prices, &decode_raw_transaction(raw)?)
+       }
+
+       pub fn print_report(&self, report: &PendingReport) {
+           println!(
+               "🔮 Pending tx {:?} from {:?}: {} swap(s), {} market(s) moved",
+               report.tx_hash,
+               report.from,
+               report.swaps.len(),
+               report.moved.len()
+           );
+           for market in &report.moved {
+               println!(
+                   "  - {} on {}: ${:.4} -> ${:.4} ({:+.3}%)",
+                   market.token_pair,
+                   market.dex_name,
+                   market.price_before,
+                   market.price_after,
+                   market.change() * 100.0
+               );
+           }
+           if !report.opportunities.is_empty() {
+               self.detector.print_opportunities(&report.opportunities);
+           }
+       }
+
+       // Index of the quoted market a hop trades through, and whether it sells the base token
+       fn market(&self, prices: &[PriceData], dex: &str, hop: &SwapHop) -> Option<(usize, bool)> {
+           let dex_name = match hop.fee {
+               Some(fee) => format!("{}_{}", dex, fee),
+               None => dex.to_string(),
+           };
+           prices.iter().enumerate().find_map(|(index, price)| {
+               if price.dex_name != dex_name {
+                   return None;
+               }
+               let &(base, quote) = self.pairs.get(&price.token_pair)?;
+               match (hop.token_in, hop.token_out) {
+                   tokens if tokens == (base, quote) => Some((index, true)),
+                   tokens if tokens == (quote, base) => Some((index, false)),
+                   _ => None,
+               }
+           })
+       }
+   }
+
+   /// Parses a signed transaction as sent with `eth_sendRawTransaction` and
+   /// recovers its sender.
+   pub fn decode_raw_transaction(raw: &[u8]) -> Result<Transaction> {
+       let mut tx: Transaction = rlp::decode(raw).map_err(|e| anyhow!("Invalid raw transaction: {}", e))?;
+       tx.from = tx
+           .recover_from()
+           .map_err(|e| anyhow!("Invalid transaction signature: {}", e))?;
+       Ok(tx)
+   }
+
+   /// Hops of a packed V3 path: a token, then a 3-byte fee and the next
+   /// token for every hop. None when the encoding is malformed.
+   pub fn decode_path(path: &[u8]) -> Option<Vec<SwapHop>> {
+       if path.len() < 43 || !(path.len() - 20).is_multiple_of(23) {
+           return None;
+       }
+
+       Some(
+           path[..path.len() - 20]
+               .chunks(23)
+               .enumerate()
+               .map(|(hop, chunk)| {
+                   let next = (hop + 1) * 23;
+                   SwapHop {
+                       token_in: Address::from_slice(&chunk[..20]),
+                       token_out: Address::from_slice(&path[next..next + 20]),
+                       fee: Some(u32::from_be_bytes([0, chunk[20], chunk[21], chunk[22]])),
+                   }
+               })
+               .collect(),
+       )
+   }
+
+   // Swaps in a V3 router call, unpacking multicalls
+   fn decode_v3_call(input: &[u8], routes: &mut Vec<(Vec<SwapHop>, U256)>) {
+       match ISwapRouterCalls::decode(input) {
+           Ok(ISwapRouterCalls::ExactInputSingle(ExactInputSingleCall { params })) => {
+               let hop = SwapHop {
+                   token_in: params.token_in,
+                   token_out: params.token_out,
+                   fee: Some(params.fee),
+               };
+               routes.push((vec![hop], params.amount_in));
+           }
+           Ok(ISwapRouterCalls::ExactInput(ExactInputCall { params })) => {
+               if let Some(hops) = decode_path(&params.path) {
+                   routes.push((hops, params.amount_in));
+               }
+           }
+           Ok(ISwapRouterCalls::Multicall(MulticallCall { data })) => {
+               for call in data {
+                   decode_v3_call(&call, routes);
+               }
+           }
+           _ => {}
+       }
+   }
+
+   // Moves `price` as the swap would and returns the hop's output
+   fn apply_hop(price: &mut PriceData, hop: &SwapHop, sell_base: bool, amount_in: U256) -> Option<U256> {
+       match (&mut price.v3_liquidity, hop.fee) {
+           (Some(pool), Some(_)) => {
+               let result = pool
+                   .simulate_swap(sell_base == pool.base_is_token0, amount_in)
+                   .ok()
+                   .filter(|result| result.filled)?;
+               let state = PoolState {
+                   pool_address: pool.pool,
+                   fee: pool.fee,
+                   sqrt_price_x96: result.sqrt_price_x96,
+                   tick: result.tick,
+                   liquidity: result.liquidity,
+                   base_is_token0: pool.base_is_token0,
+               };
+               let liquidity = uniswap_v3::pool_liquidity(&state, pool.quote_decimals)?;
+               pool.sqrt_price_x96 = result.sqrt_price_x96;
+               pool.tick = result.tick;
+               pool.liquidity = result.liquidity;
+
+               price.price = uniswap_v3::pool_price(&state, pool.base_decimals, pool.quote_decimals);
+               price.liquidity = liquidity;
+               price.reserves = Some(uniswap_v3::pool_virtual_reserves(&state, pool.base_decimals, pool.quote_decimals));
+               Some(result.amount_out)
+           }
+           (None, None) => {
+               let reserves = price.reserves?;
+               let (reserve_in, reserve_out) = if sell_base {
+                   (reserves.base.raw, reserves.quote.raw)
+               } else {
+                   (reserves.quote.raw, reserves.base.raw)
+               };
+               let amount_out = uniswap_v2::amount_out(amount_in, reserve_in, reserve_out, price.fee)?;
+               let (reserve_in, reserve_out) = (reserve_in.checked_add(amount_in)?, reserve_out - amount_out);
+
+               let (reserve_base, reserve_quote) = if sell_base { (reserve_in, reserve_out) } else { (reserve_out, reserve_in) };
+               let pair = PairReserves {
+                   pair_address: Address::zero(),
+                   reserve_base,
+                   reserve_quote,
+               };
+               let (base_decimals, quote_decimals) = (reserves.base.decimals, reserves.quote.decimals);
+               let moved = uniswap_v2::reserves_to_price(&pair, base_decimals, quote_decimals)?;
+               let liquidity = uniswap_v2::reserves_to_liquidity(&pair, quote_decimals)?;
+               price.price = moved;
+               price.liquidity = liquidity;
+               price.reserves = Some(uniswap_v2::reserves_to_amounts(&pair, base_decimals, quote_decimals));
+               Some(amount_out)
+           }
+           _ => None,
+       }
+   }
+
+   /// Full pending transactions from a `newPendingTransactions` WebSocket
+   /// subscription, kept up in the background and reconnected with backoff.
+   /// Transactions arriving while the buffer is full are dropped and counted.
+   pub struct PendingTransactions {
+       receiver: mpsc::Receiver<Transaction>,
+       dropped: Arc<AtomicU64>,
+       reconnects: Arc<AtomicU64>,
+       task: JoinHandle<()>,
+   }
+
+   impl PendingTransactions {
+       pub fn subscribe(url: &str) -> Self {
+           Self::with_backoff(url, Backoff::default())
+       }
+
+       pub fn with_backoff(url: &str, backoff: Backoff) -> Self {
+           let (sender, receiver) = mpsc::channel(PENDING_BUFFER);
+           let dropped = Arc::new(AtomicU64::new(0));
+           let reconnects = Arc::new(AtomicU64::new(0));
+           let task = tokio::spawn(follow(url.to_string(), sender, backoff, dropped.clone(), reconnects.clone()));
+           Self {
+               receiver,
+               dropped,
+               reconnects,
+               task,
+           }
+       }
+
+       /// The next pending transaction, in arrival order.
+       pub async fn next(&mut self) -> Option<Transaction> {
+           self.receiver.recv().await
+       }
+
+       /// Transactions lost to a full buffer.
+       pub fn dropped(&self) -> u64 {
+           self.dropped.load(Ordering::Relaxed)
+       }
+
+       /// Times the socket has been re-opened since subscribing.
+       pub fn reconnects(&self) -> u64 {
+           self.reconnects.load(Ordering::Relaxed)
+       }
+   }
+
+   impl Drop for PendingTransactions {
+       fn drop(&mut self) {
+           self.task.abort();
+       }
+   }
+
+   async fn follow(
+       url: String,
+       sender: mpsc::Sender<Transaction>,
+       mut backoff: Backoff,
+       dropped: Arc<AtomicU64>,
+       reconnects: Arc<AtomicU64>,
+   ) {
+       loop {
+           match stream_pending(&url, &sender, &mut backoff, &dropped).await {
+               Ok(()) if sender.is_closed() => return,
+               Ok(()) => eprintln!("⚠️  newPendingTransactions subscription to {} closed", url),
+               Err(e) => eprintln!("⚠️  newPendingTransactions subscription to {} failed: {}", url, e),
+           }
+
+           let delay = backoff.next_delay();
+           eprintln!("🔌 Reconnecting in {:.1}s", delay.as_secs_f64());
+           tokio::time::sleep(delay).await;
+           reconnects.fetch_add(1, Ordering::Relaxed);
+       }
+   }
+
+   async fn stream_pending(url: &str, sender: &mpsc::Sender<Transaction>, backoff: &mut Backoff, dropped: &AtomicU64) -> Result<()> {
+       // Reconnecting is ours to pace, so the transport gives up on the first drop
+       let provider = Provider::<Ws>::connect_with_reconnects(url, 0)
+           .await
+           .map_err(|e| anyhow!("connect: {}", e))?;
+       let mut stream = provider
+           .subscribe_full_pending_txs()
+           .await
+           .map_err(|e| anyhow!("eth_subscribe: {}", e))?;
+       backoff.reset();
+
+       while let Some(tx) = stream.next().await {
+           match sender.try_send(tx) {
+               Ok(()) => {}
+               Err(mpsc::error::TrySendError::Full(_)) => {
+                   dropped.fetch_add(1, Ordering::Relaxed);
+               }
+               Err(mpsc::error::TrySendError::Closed(_)) => return Ok(()),
+           }
+       }
+
+       Ok(())
+   }
\ No newline at end of file
@@ -1,6 +1,28 @@
+pub mod amounts;
 pub mod price_fetcher;
 pub mod arbitrage_detector;
 pub mod profit_calculator;
+pub mod gas_oracle;
+pub mod gas_estimator;
+pub mod native_price;
+pub mod price_source;
+pub mod token_registry;
+pub mod mempool;
+pub mod multicall;
+pub mod new_heads;
+pub mod pool_cache;
+pub mod pool_discovery;
+pub mod reorg;
+pub mod rpc_pool;
+pub mod routers;
+pub mod triangular;
+pub mod route;
+pub mod cycle_search;
+pub mod trade_sizing;
+pub mod uniswap_v2;
+pub mod uniswap_v3;
+pub mod v3_math;
+pub mod v3_swap;
 
 pub use price_fetcher::*;
 pub use arbitrage_detector::*;
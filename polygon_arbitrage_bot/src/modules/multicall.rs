@@ -0,0 +1,123 @@
+use anyhow::{Result, anyhow};
+   use ethers::abi::{self, AbiDecode, ParamType, Token};
+   use ethers::types::transaction::eip2718::TypedTransaction;
+   use ethers::prelude::*;
+   use crate::modules::price_fetcher::{BlockRef, PriceData};
+   use crate::modules::rpc_pool::RpcClient;
+
+   // Multicall3 is deployed at the same address on Polygon and most EVM chains
+   pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028662bEcA7e4e4D6A6e";
+
+   const AGGREGATE3_SIGNATURE: &str = "aggregate3((address,bool,bytes)[])";
+
+   /// Decodes a source's share of a batch once it has been executed.
+   pub type PendingQuote = Box<dyn FnOnce(&BatchResults) -> Result<Vec<PriceData>> + Send>;
+
+   /// Contract reads collected from every price source for one cycle.
+   #[derive(Debug, Default)]
+   pub struct CallBatch {
+       calls: Vec<(Address, Bytes)>,
+   }
+
+   impl CallBatch {
+       pub fn new() -> Self {
+           Self::default()
+       }
+
+       /// Queues a read and returns its index in the batch results.
+       pub fn push(&mut self, target: Address, calldata: impl Into<Bytes>) -> usize {
+           self.calls.push((target, calldata.into()));
+           self.calls.len() - 1
+       }
+
+       pub fn len(&self) -> usize {
+           self.calls.len()
+       }
+
+       pub fn is_empty(&self) -> bool {
+           self.calls.is_empty()
+       }
+
+       /// Runs every queued read through a single `aggregate3` call, pinned to
+       /// `block` by hash so every result comes from the same chain state.
+       pub async fn execute(&self, client: &RpcClient, block: BlockRef) -> Result<BatchResults> {
+           let multicall = MULTICALL3_ADDRESS.parse::<Address>()?;
+           let tx: TypedTransaction = TransactionRequest::new()
+               .to(multicall)
+               .data(self.encode())
+               .into();
+
+           let output = client
+               .call(&tx, Some(BlockId::Hash(block.hash)))
+               .await
+               .map_err(|e| anyhow!("Multicall aggregate3 failed: {}", e))?;
+
+           Ok(BatchResults {
+               block,
+               results: Self::decode(&output)?,
+           })
+       }
+
+       fn encode(&self) -> Bytes {
+           let calls = self
+               .calls
+               .iter()
+               .map(|(target, calldata)| {
+                   Token::Tuple(vec![
+                       Token::Address(*target),
+                       Token::Bool(true), // allowFailure: one bad pool must not sink the batch
+                       Token::Bytes(calldata.to_vec()),
+                   ])
+               })
+               .collect();
+
+           let mut data = ethers::utils::id(AGGREGATE3_SIGNATURE).to_vec();
+           data.extend(abi::encode(&[Token::Array(calls)]));
+           Bytes::from(data)
+       }
+
+       fn decode(output: &Bytes) -> Result<Vec<(bool, Bytes)>> {
+           let result_type = ParamType::Array(Box::new(ParamType::Tuple(vec![
+               ParamType::Bool,
+               ParamType::Bytes,
+           ])));
+
+           let tokens = abi::decode(&[result_type], output)?;
+           let Some(Token::Array(entries)) = tokens.into_iter().next() else {
+               return Err(anyhow!("Unexpected aggregate3 return data"));
+           };
+
+           entries
+               .into_iter()
+               .map(|entry| match entry {
+                   Token::Tuple(fields) => match fields.as_slice() {
+                       [Token::Bool(success), Token::Bytes(data)] => {
+                           Ok((*success, Bytes::from(data.clone())))
+                       }
+                       _ => Err(anyhow!("Unexpected aggregate3 result entry")),
+                   },
+                   _ => Err(anyhow!("Unexpected aggregate3 result entry")),
+               })
+               .collect()
+       }
+   }
+
+   #[derive(Debug, Clone)]
+   pub struct BatchResults {
+       pub block: BlockRef,
+       results: Vec<(bool, Bytes)>,
+   }
+
+   impl BatchResults {
+       pub fn new(block: BlockRef, results: Vec<(bool, Bytes)>) -> Self {
+           Self { block, results }
+       }
+
+       pub fn decode<T: AbiDecode>(&self, index: usize) -> Result<T> {
+           match self.results.get(index) {
+               Some((true, data)) => Ok(T::decode(data)?),
+               Some((false, _)) => Err(anyhow!("Batched call #{} reverted", index)),
+               None => Err(anyhow!("Batched call #{} missing from results", index)),
+           }
+       }
+   }
@@ -0,0 +1,262 @@
+use anyhow::{Result, anyhow};
+   use ethers::prelude::*;
+   use serde::{Deserialize, Serialize};
+   use std::sync::{Arc, Mutex};
+   use crate::config::Config;
+   use crate::modules::amounts::{Decimal, TokenAmount};
+   use crate::modules::price_fetcher::{self, BlockRef};
+   use crate::modules::price_source::DexKind;
+   use crate::modules::rpc_pool::RpcClient;
+   use crate::modules::token_registry::{self, TokenRegistry};
+   use crate::modules::triangular;
+   use crate::modules::uniswap_v2::{self, UniswapV2Pricer};
+
+   abigen!(
+       IChainlinkAggregator,
+       r#"[
+           function decimals() external view returns (uint8)
+           function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound)
+       ]"#
+   );
+
+   // Chainlink MATIC/USD feed on Polygon mainnet
+   pub const MATIC_USD_FEED: &str = "0xAB594600376Ec9fD91F8e885dADF0CE036862dE0";
+
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   #[serde(rename_all = "snake_case")]
+   pub enum NativePriceSource {
+       Chainlink,
+       Pool,
+       Default,
+   }
+
+   impl NativePriceSource {
+       pub fn as_str(&self) -> &'static str {
+           match self {
+               NativePriceSource::Chainlink => "chainlink",
+               NativePriceSource::Pool => "pool",
+               NativePriceSource::Default => "default",
+           }
+       }
+   }
+
+   /// USD price of the chain's gas token (MATIC/POL).
+   #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+   pub struct NativePrice {
+       pub usd: Decimal,
+       pub source: NativePriceSource,
+       pub updated_at: Option<u64>, // When the source last updated, unix seconds
+       pub block_number: Option<u64>,
+       #[serde(default)]
+       pub cached: bool, // Reused from an earlier read because both sources failed
+   }
+
+   impl NativePrice {
+       /// Fixed price used when no oracle is available.
+       pub fn fixed(usd: Decimal) -> Self {
+           Self {
+               usd,
+               source: NativePriceSource::Default,
+               updated_at: None,
+               block_number: None,
+               cached: false,
+           }
+       }
+
+       /// Source name, marked when the price is a reused earlier read.
+       pub fn label(&self) -> String {
+           if self.cached {
+               format!("{} (cached)", self.source.as_str())
+           } else {
+               self.source.as_str().to_string()
+           }
+       }
+   }
+
+   /// A V2 pool pricing the native token against a stablecoin.
+   struct NativePool {
+       pricer: UniswapV2Pricer,
+       native: Address,
+       stable: Address,
+   }
+
+   /// Reads the native token price from a Chainlink feed, falling back to a
+   /// DEX pool when the feed is stale or unreachable (and the other way round).
+   pub struct NativePriceOracle {
+       client: Arc<RpcClient>,
+       feed: Option<Address>,
+       feed_decimals: Mutex<Option<u8>>,
+       pool: Option<NativePool>,
+       tokens: Arc<TokenRegistry>,
+       max_age_secs: u64,
+       cached: Mutex<Option<NativePrice>>,
+   }
+
+   impl NativePriceOracle {
+       pub fn new(client: Arc<RpcClient>) -> Self {
+           Self {
+               tokens: Arc::new(TokenRegistry::new(client.clone())),
+               client,
+               feed: None,
+               feed_decimals: Mutex::new(None),
+               pool: None,
+               max_age_secs: 3600,
+               cached: Mutex::new(None),
+           }
+       }
+
+       /// Chainlink feed from config, plus the first V2 DEX's pool for the
+       /// configured WMATIC/stablecoin pair as fallback.
+       pub fn from_config(client: Arc<RpcClient>, config: &Config) -> Result<Self> {
+           let mut oracle = Self::new(client).with_max_age(config.native_price_max_age);
+           if let Some(feed) = &config.native_price_feed {
+               oracle = oracle.with_chainlink_feed(feed)?;
+           }
+
+           let pair = config
+               .token_pairs
+               .iter()
+               .find(|pair| pair.base == "WMATIC" && triangular::is_stablecoin(&pair.quote));
+           let dex = config
+               .dexes
+               .iter()
+               .find(|dex| dex.kind == DexKind::UniswapV2 && dex.factory.is_some());
+           if let (Some(pair), Some(dex)) = (pair, dex) {
+               oracle = oracle.with_pool(dex.factory.as_deref().unwrap_or_default(), &pair.base_address, &pair.quote_address)?;
+           }
+
+           Ok(oracle)
+       }
+
+       pub fn with_chainlink_feed(mut self, feed: &str) -> Result<Self> {
+           self.feed = Some(
+               feed.parse::<Address>()
+                   .map_err(|e| anyhow!("Invalid price feed address {}: {}", feed, e))?,
+           );
+           Ok(self)
+       }
+
+       pub fn with_pool(mut self, factory: &str, native: &str, stable: &str) -> Result<Self> {
+           self.pool = Some(NativePool {
+               pricer: UniswapV2Pricer::new(self.client.clone(), factory)?,
+               native: token_registry::parse_checksummed(native)?,
+               stable: token_registry::parse_checksummed(stable)?,
+           });
+           Ok(self)
+       }
+
+       /// Shares token metadata with the price sources instead of reading it again.
+       pub fn with_token_registry(mut self, tokens: Arc<TokenRegistry>) -> Self {
+           self.tokens = tokens;
+           self
+       }
+
+       /// Feed answers older than this are treated as stale.
+       pub fn with_max_age(mut self, secs: u64) -> Self {
+           self.max_age_secs = secs;
+           self
+       }
+
+       /// Price as of `block` (latest when None), cached per block. If both
+       /// sources fail, the last good price is reused while it is younger
+       /// than the max age.
+       pub async fn price(&self, block: Option<BlockRef>) -> Result<NativePrice> {
+           let block_number = block.map(|b| b.number);
+           if let Some(cached) = *self.cached.lock().unwrap() {
+               if block_number.is_some() && cached.block_number == block_number {
+                   return Ok(cached);
+               }
+           }
+
+           let feed_error = match self.read_feed(block).await {
+               Ok(price) => return Ok(self.remember(price)),
+               Err(e) => e,
+           };
+           let pool_error = match self.read_pool(block).await {
+               Ok(price) => return Ok(self.remember(price)),
+               Err(e) => e,
+           };
+
+           let now = match block {
+               Some(block) => block.timestamp,
+               None => price_fetcher::unix_timestamp()?,
+           };
+           match *self.cached.lock().unwrap() {
+               Some(last) if now.saturating_sub(last.updated_at.unwrap_or_default()) <= self.max_age_secs => {
+                   Ok(NativePrice { cached: true, ..last })
+               }
+               _ => Err(anyhow!("No native token price: feed: {}; pool: {}", feed_error, pool_error)),
+           }
+       }
+
+       fn remember(&self, price: NativePrice) -> NativePrice {
+           *self.cached.lock().unwrap() = Some(price);
+           price
+       }
+
+       async fn read_feed(&self, block: Option<BlockRef>) -> Result<NativePrice> {
+           let feed = self.feed.ok_or_else(|| anyhow!("no Chainlink feed configured"))?;
+           let aggregator = IChainlinkAggregator::new(feed, self.client.clone());
+
+           let cached_decimals = *self.feed_decimals.lock().unwrap();
+           let decimals = match cached_decimals {
+               Some(decimals) => decimals,
+               None => {
+                   let decimals = aggregator.decimals().call().await?;
+                   *self.feed_decimals.lock().unwrap() = Some(decimals);
+                   decimals
+               }
+           };
+
+           let mut round = aggregator.latest_round_data();
+           if let Some(block) = block {
+               round = round.block(BlockId::Number(block.number.into()));
+           }
+           let (_, answer, _, updated_at, _) = round.call().await?;
+           if answer <= I256::zero() {
+               return Err(anyhow!("feed answered {}", answer));
+           }
+
+           // Judge staleness against the block's time when we know it
+           let now = match block {
+               Some(block) => block.timestamp,
+               None => price_fetcher::unix_timestamp()?,
+           };
+           let updated_at = updated_at.as_u64();
+           if now.saturating_sub(updated_at) > self.max_age_secs {
+               return Err(anyhow!("feed is stale, last updated {}s ago", now - updated_at));
+           }
+
+           Ok(NativePrice {
+               usd: TokenAmount::new(answer.into_raw(), decimals)
+                   .to_decimal()
+                   .ok_or_else(|| anyhow!("feed answered {}", answer))?,
+               source: NativePriceSource::Chainlink,
+               updated_at: Some(updated_at),
+               block_number: block.map(|b| b.number),
+               cached: false,
+           })
+       }
+
+       async fn read_pool(&self, block: Option<BlockRef>) -> Result<NativePrice> {
+           let pool = self.pool.as_ref().ok_or_else(|| anyhow!("no fallback pool configured"))?;
+
+           let reserves = pool.pricer.fetch_reserves(pool.native, pool.stable, block.map(|b| b.number)).await?;
+           let native_decimals = self.tokens.decimals(pool.native).await?;
+           let stable_decimals = self.tokens.decimals(pool.stable).await?;
+           let usd = uniswap_v2::reserves_to_price(&reserves, native_decimals, stable_decimals)
+               .ok_or_else(|| anyhow!("pool {:?} is empty", reserves.pair_address))?;
+
+           let updated_at = match block {
+               Some(block) => block.timestamp,
+               None => price_fetcher::unix_timestamp()?,
+           };
+           Ok(NativePrice {
+               usd,
+               source: NativePriceSource::Pool,
+               updated_at: Some(updated_at),
+               block_number: block.map(|b| b.number),
+               cached: false,
+           })
+       }
+   }
@@ -0,0 +1,134 @@
+use anyhow::{Result, anyhow};
+   use ethers::prelude::*;
+   use std::sync::atomic::{AtomicU64, Ordering};
+   use std::sync::Arc;
+   use std::time::Duration;
+   use tokio::sync::watch;
+   use tokio::task::JoinHandle;
+   use crate::modules::price_fetcher::BlockRef;
+
+   /// Reconnect delay that doubles after each failure, up to `max`.
+   #[derive(Debug, Clone, Copy)]
+   pub struct Backoff {
+       initial: Duration,
+       max: Duration,
+       current: Duration,
+   }
+
+   impl Backoff {
+       pub fn new(initial: Duration, max: Duration) -> Self {
+           Self {
+               initial,
+               max,
+               current: initial,
+           }
+       }
+
+       /// The delay before the next attempt.
+       pub fn next_delay(&mut self) -> Duration {
+           let delay = self.current;
+           self.current = (self.current * 2).min(self.max);
+           delay
+       }
+
+       /// Starts over after a connection that worked.
+       pub fn reset(&mut self) {
+           self.current = self.initial;
+       }
+   }
+
+   impl Default for Backoff {
+       fn default() -> Self {
+           Self::new(Duration::from_secs(1), Duration::from_secs(30))
+       }
+   }
+
+   /// Latest block header from a `newHeads` WebSocket subscription, kept up
+   /// in the background. Dropped sockets are reconnected with backoff. Only
+   /// the newest head is held, so a slow reader sees one head, not a queue.
+   pub struct NewHeads {
+       heads: watch::Receiver<Option<BlockRef>>,
+       reconnects: Arc<AtomicU64>,
+       task: JoinHandle<()>,
+   }
+
+   impl NewHeads {
+       pub fn subscribe(url: &str) -> Self {
+           Self::with_backoff(url, Backoff::default())
+       }
+
+       pub fn with_backoff(url: &str, backoff: Backoff) -> Self {
+           let (sender, heads) = watch::channel(None);
+           let reconnects = Arc::new(AtomicU64::new(0));
+           let task = tokio::spawn(follow(url.to_string(), sender, backoff, reconnects.clone()));
+           Self { heads, reconnects, task }
+       }
+
+       pub fn latest(&self) -> Option<BlockRef> {
+           *self.heads.borrow()
+       }
+
+       /// A receiver that wakes on each new head.
+       pub fn receiver(&self) -> watch::Receiver<Option<BlockRef>> {
+           self.heads.clone()
+       }
+
+       /// Times the socket has been re-opened since subscribing.
+       pub fn reconnects(&self) -> u64 {
+           self.reconnects.load(Ordering::Relaxed)
+       }
+   }
+
+   impl Drop for NewHeads {
+       fn drop(&mut self) {
+           self.task.abort();
+       }
+   }
+
+   async fn follow(url: String, sender: watch::Sender<Option<BlockRef>>, mut backoff: Backoff, reconnects: Arc<AtomicU64>) {
+       loop {
+           match stream_heads(&url, &sender, &mut backoff).await {
+               Ok(()) if sender.is_closed() => return,
+               Ok(()) => eprintln!("⚠️  newHeads subscription to {} closed", url),
+               Err(e) => eprintln!("⚠️  newHeads subscription to {} failed: {}", url, e),
+           }
+
+           let delay = backoff.next_delay();
+           eprintln!("🔌 Reconnecting in {:.1}s", delay.as_secs_f64());
+           tokio::time::sleep(delay).await;
+           reconnects.fetch_add(1, Ordering::Relaxed);
+       }
+   }
+
+   async fn stream_heads(url: &str, sender: &watch::Sender<Option<BlockRef>>, backoff: &mut Backoff) -> Result<()> {
+       // Reconnecting is ours to pace, so the transport gives up on the first drop
+       let provider = Provider::<Ws>::connect_with_reconnects(url, 0)
+           .await
+           .map_err(|e| anyhow!("connect: {}", e))?;
+       let mut stream = provider
+           .subscribe_blocks()
+           .await
+           .map_err(|e| anyhow!("eth_subscribe: {}", e))?;
+       backoff.reset();
+
+       while let Some(header) = stream.next().await {
+           match BlockRef::from_block(&header) {
+               // A reorg can repeat a height, so any new hash is a new head
+               Ok(head) => {
+                   sender.send_if_modified(|latest| {
+                       let changed = latest.is_none_or(|latest| latest.hash != head.hash);
+                       if changed {
+                           *latest = Some(head);
+                       }
+                       changed
+                   });
+               }
+               Err(e) => eprintln!("⚠️  Ignoring malformed block header: {}", e),
+           }
+           if sender.is_closed() {
+               return Ok(());
+           }
+       }
+
+       Ok(())
+   }
\ No newline at end of file
@@ -0,0 +1,398 @@
+use anyhow::{Result, anyhow};
+   use ethers::abi::RawLog;
+   use ethers::prelude::*;
+   use serde::Serialize;
+   use std::collections::{BTreeMap, HashMap, VecDeque};
+   use std::sync::Mutex;
+   use crate::modules::price_fetcher::BlockRef;
+   use crate::modules::rpc_pool::RpcClient;
+   use crate::modules::v3_swap;
+
+   abigen!(
+       IPoolEvents,
+       r#"[
+           event Sync(uint112 reserve0, uint112 reserve1)
+           event Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick)
+           event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)
+           event Burn(address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)
+       ]"#
+   );
+
+   // Blocks of undo history kept for rolling back a reorg
+   pub const REORG_DEPTH: u64 = 64;
+
+   // Widest eth_getLogs range asked for at once; public nodes cap it
+   pub const MAX_LOG_RANGE: u64 = 1000;
+
+   /// Pool state as the pool contract stores it, in token0/token1 order.
+   #[derive(Debug, Clone, PartialEq)]
+   pub enum CachedState {
+       V2 {
+           reserve0: u128,
+           reserve1: u128,
+       },
+       V3 {
+           sqrt_price_x96: U256,
+           tick: i32,
+           liquidity: u128,
+           tick_spacing: i32,
+           // Initialized tick -> liquidityNet, for the words in `loaded_words`
+           ticks: BTreeMap<i32, i128>,
+           loaded_words: Option<(i16, i16)>,
+       },
+   }
+
+   #[derive(Debug, Clone, PartialEq)]
+   pub struct CachedPool {
+       pub address: Address,
+       pub state: CachedState,
+       pub seeded_at: u64, // Block the state was read at; older logs are already in it
+       pub updated_at: u64, // Block of the last log applied
+   }
+
+   impl CachedPool {
+       /// Whether the cached ticks cover the current tick's bitmap word, so a
+       /// swap can be simulated from it. Always true for V2.
+       pub fn ticks_current(&self) -> bool {
+           match &self.state {
+               CachedState::V2 { .. } => true,
+               CachedState::V3 { tick, tick_spacing, loaded_words, .. } => loaded_words.is_some_and(|(first, last)| {
+                   (first..=last).contains(&v3_swap::word_position(*tick, *tick_spacing))
+               }),
+           }
+       }
+
+       fn apply(&mut self, event: IPoolEventsEvents) -> bool {
+           match (&mut self.state, event) {
+               (CachedState::V2 { reserve0, reserve1 }, IPoolEventsEvents::SyncFilter(sync)) => {
+                   *reserve0 = sync.reserve_0;
+                   *reserve1 = sync.reserve_1;
+               }
+               (CachedState::V3 { sqrt_price_x96, tick, liquidity, .. }, IPoolEventsEvents::SwapFilter(swap)) => {
+                   *sqrt_price_x96 = swap.sqrt_price_x96;
+                   *tick = swap.tick;
+                   *liquidity = swap.liquidity;
+               }
+               (state @ CachedState::V3 { .. }, IPoolEventsEvents::MintFilter(mint)) => {
+                   update_position(state, mint.tick_lower, mint.tick_upper, mint.amount as i128);
+               }
+               (state @ CachedState::V3 { .. }, IPoolEventsEvents::BurnFilter(burn)) => {
+                   update_position(state, burn.tick_lower, burn.tick_upper, -(burn.amount as i128));
+               }
+               // A V2 pair shares no other event signature with this set
+               _ => return false,
+           }
+           true
+       }
+   }
+
+   // Adds `delta` of liquidity between two ticks, as Pool.modifyPosition does
+   fn update_position(state: &mut CachedState, tick_lower: i32, tick_upper: i32, delta: i128) {
+       let CachedState::V3 { tick, liquidity, tick_spacing, ticks, loaded_words, .. } = state else {
+           return;
+       };
+
+       if (tick_lower..tick_upper).contains(tick) {
+           *liquidity = liquidity.saturating_add_signed(delta);
+       }
+       // Ticks outside the loaded words are left to the next full read
+       if let Some((first, last)) = *loaded_words {
+           for (boundary, net) in [(tick_lower, delta), (tick_upper, -delta)] {
+               if !(first..=last).contains(&v3_swap::word_position(boundary, *tick_spacing)) {
+                   continue;
+               }
+               let entry = ticks.entry(boundary).or_insert(0);
+               *entry += net;
+               if *entry == 0 {
+                   ticks.remove(&boundary);
+               }
+           }
+       }
+   }
+
+   /// What one `sync_to` did.
+   #[derive(Debug, Clone, Copy, Default, Serialize)]
+   pub struct SyncReport {
+       pub logs: usize,
+       pub applied: usize,
+       pub rolled_back_to: Option<u64>, // Set after a reorg
+       pub reset: bool, // The reorg was deeper than the undo history; pools must be re-read
+   }
+
+   #[derive(Debug, Default)]
+   struct CacheState {
+       pools: HashMap<Address, CachedPool>,
+       // Each pool as it was before its first change in a block
+       journal: BTreeMap<u64, Vec<CachedPool>>,
+       // Heads the cache was synced to, oldest first
+       heads: VecDeque<BlockRef>,
+   }
+
+   /// Pool state seeded once from contract reads, then kept current from
+   /// V2 `Sync` and V3 `Swap`/`Mint`/`Burn` logs. Every change is journaled
+   /// per block so a reorg rolls the cache back instead of invalidating it.
+   pub struct PoolStateCache {
+       state: Mutex<CacheState>,
+       depth: u64,
+   }
+
+   impl PoolStateCache {
+       pub fn new() -> Self {
+           Self {
+               state: Mutex::new(CacheState::default()),
+               depth: REORG_DEPTH,
+           }
+       }
+
+       /// Blocks of history kept for rollback.
+       pub fn with_depth(mut self, blocks: u64) -> Self {
+           self.depth = blocks.max(1);
+           self
+       }
+
+       /// The head the cache state is current as of.
+       pub fn head(&self) -> Option<BlockRef> {
+           self.state.lock().unwrap().heads.back().copied()
+       }
+
+       pub fn get(&self, pool: Address) -> Option<CachedPool> {
+           self.state.lock().unwrap().pools.get(&pool).cloned()
+       }
+
+       pub fn addresses(&self) -> Vec<Address> {
+           self.state.lock().unwrap().pools.keys().copied().collect()
+       }
+
+       pub fn len(&self) -> usize {
+           self.state.lock().unwrap().pools.len()
+       }
+
+       pub fn is_empty(&self) -> bool {
+           self.len() == 0
+       }
+
+       /// Stores state read at `block`. Reads older than the cache head are
+       /// refused, since the logs between them were never applied.
+       pub fn seed(&self, pool: Address, state: CachedState, block: BlockRef) -> bool {
+           let mut cache = self.state.lock().unwrap();
+           match cache.heads.back() {
+               Some(head) if head.number > block.number => return false,
+               Some(_) => {}
+               None => cache.heads.push_back(block),
+           }
+
+           cache.pools.insert(
+               pool,
+               CachedPool {
+                   address: pool,
+                   state,
+                   seeded_at: block.number,
+                   updated_at: block.number,
+               },
+           );
+           true
+       }
+
+       /// Adds the tick liquidity read for a V3 pool seeded at `block`.
+       pub fn seed_ticks(&self, pool: Address, tick_liquidity: &BTreeMap<i32, i128>, words: (i16, i16), block: BlockRef) -> bool {
+           let mut cache = self.state.lock().unwrap();
+           match cache.pools.get_mut(&pool) {
+               Some(CachedPool {
+                   state: CachedState::V3 { ticks, loaded_words, .. },
+                   updated_at,
+                   ..
+               }) if *updated_at == block.number => {
+                   *ticks = tick_liquidity.clone();
+                   *loaded_words = Some(words);
+                   true
+               }
+               _ => false,
+           }
+       }
+
+       /// A pool's state with the head it is current at, if the cache can
+       /// serve it. With `at` given, only when the cache is synced to exactly
+       /// that head.
+       pub fn snapshot(&self, pool: Address, at: Option<BlockRef>) -> Option<(CachedPool, BlockRef)> {
+           let cache = self.state.lock().unwrap();
+           let head = *cache.heads.back()?;
+           if at.is_some_and(|at| at != head) {
+               return None;
+           }
+
+           cache
+               .pools
+               .get(&pool)
+               .filter(|pool| pool.seeded_at <= head.number)
+               .map(|pool| (pool.clone(), head))
+       }
+
+       /// Applies one log from `eth_getLogs` or a logs subscription. A log
+       /// flagged `removed` rolls the cache back to before its block. Returns
+       /// whether any pool changed.
+       pub fn apply_log(&self, log: &Log) -> Result<bool> {
+           let block = log
+               .block_number
+               .ok_or_else(|| anyhow!("Log from {:?} is missing its block number", log.address))?
+               .as_u64();
+           if log.removed == Some(true) {
+               return Ok(self.rollback_to(block.saturating_sub(1)).is_some());
+           }
+
+           let mut cache = self.state.lock().unwrap();
+           let Some(pool) = cache.pools.get(&log.address).filter(|pool| block > pool.seeded_at) else {
+               return Ok(false);
+           };
+           let raw = RawLog {
+               topics: log.topics.clone(),
+               data: log.data.to_vec(),
+           };
+           let Ok(event) = IPoolEventsEvents::decode_log(&raw) else {
+               return Ok(false);
+           };
+
+           let mut updated = pool.clone();
+           if !updated.apply(event) {
+               return Ok(false);
+           }
+           updated.updated_at = block;
+           let previous = cache.pools.insert(log.address, updated);
+           let journal = cache.journal.entry(block).or_default();
+           if let Some(previous) = previous.filter(|previous| journal.iter().all(|saved| saved.address != previous.address)) {
+               journal.push(previous);
+           }
+
+           Ok(true)
+       }
+
+       /// Marks the cache current as of `head` once its logs are applied, and
+       /// drops undo history beyond the reorg depth.
+       pub fn advance(&self, head: BlockRef) {
+           let mut cache = self.state.lock().unwrap();
+           cache.heads.push_back(head);
+
+           let oldest = head.number.saturating_sub(self.depth);
+           while cache.heads.len() > 1 && cache.heads[0].number < oldest {
+               cache.heads.pop_front();
+           }
+           let kept = cache.heads[0].number + 1;
+           cache.journal = cache.journal.split_off(&kept);
+       }
+
+       /// Undoes every change after `block`, back to the newest synced head
+       /// at or below it. Clears the cache and returns None when that is
+       /// beyond the undo history.
+       pub fn rollback_to(&self, block: u64) -> Option<BlockRef> {
+           let mut cache = self.state.lock().unwrap();
+           let Some(target) = cache.heads.iter().rev().find(|head| head.number <= block).copied() else {
+               *cache = CacheState::default();
+               return None;
+           };
+
+           let undone = cache.journal.split_off(&(target.number + 1));
+           for (_, previous) in undone.into_iter().rev() {
+               for pool in previous {
+                   cache.pools.insert(pool.address, pool);
+               }
+           }
+           cache.pools.retain(|_, pool| pool.seeded_at <= target.number);
+           while cache.heads.back().is_some_and(|head| head.number > target.number) {
+               cache.heads.pop_back();
+           }
+
+           Some(target)
+       }
+
+       pub fn clear(&self) {
+           *self.state.lock().unwrap() = CacheState::default();
+       }
+
+       /// Brings every cached pool up to `head` with `eth_getLogs`, first
+       /// rolling back to the newest synced head still on the canonical chain.
+       pub async fn sync_to(&self, client: &RpcClient, head: BlockRef) -> Result<SyncReport> {
+           let mut report = SyncReport::default();
+           let Some(synced) = self.head() else {
+               self.advance(head);
+               return Ok(report);
+           };
+           if synced == head {
+               return Ok(report);
+           }
+
+           let from = match self.common_ancestor(client, head).await? {
+               Some(ancestor) if ancestor == synced => ancestor,
+               Some(ancestor) => {
+                   report.rolled_back_to = self.rollback_to(ancestor.number).map(|block| block.number);
+                   ancestor
+               }
+               None => {
+                   self.clear();
+                   self.advance(head);
+                   report.reset = true;
+                   return Ok(report);
+               }
+           };
+
+           let addresses = self.addresses();
+           let mut start = from.number + 1;
+           while !addresses.is_empty() && start <= head.number {
+               let end = head.number.min(start + MAX_LOG_RANGE - 1);
+               let filter = Filter::new()
+                   .address(addresses.clone())
+                   .topic0(vec![
+                       SyncFilter::signature(),
+                       SwapFilter::signature(),
+                       MintFilter::signature(),
+                       BurnFilter::signature(),
+                   ])
+                   .from_block(start)
+                   .to_block(end);
+               let mut logs = client
+                   .get_logs(&filter)
+                   .await
+                   .map_err(|e| anyhow!("eth_getLogs {}..{}: {}", start, end, e))?;
+               logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+               for log in &logs {
+                   if self.apply_log(log)? {
+                       report.applied += 1;
+                   }
+               }
+               report.logs += logs.len();
+               start = end + 1;
+           }
+           self.advance(head);
+
+           Ok(report)
+       }
+
+       // Newest synced head the chain still agrees with
+       async fn common_ancestor(&self, client: &RpcClient, head: BlockRef) -> Result<Option<BlockRef>> {
+           let heads: Vec<BlockRef> = self.state.lock().unwrap().heads.iter().rev().copied().collect();
+           for synced in heads {
+               if synced.number > head.number {
+                   continue;
+               }
+               let canonical = if synced.number == head.number {
+                   head.hash
+               } else {
+                   let block = client
+                       .get_block(synced.number)
+                       .await?
+                       .ok_or_else(|| anyhow!("RPC returned no block #{}", synced.number))?;
+                   BlockRef::from_block(&block)?.hash
+               };
+               if canonical == synced.hash {
+                   return Ok(Some(synced));
+               }
+           }
+
+           Ok(None)
+       }
+   }
+
+   impl Default for PoolStateCache {
+       fn default() -> Self {
+           Self::new()
+       }
+   }
\ No newline at end of file
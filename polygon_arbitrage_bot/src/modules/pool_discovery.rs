@@ -0,0 +1,365 @@
+use anyhow::{Result, anyhow};
+   use ethers::abi::{AbiEncode, RawLog};
+   use ethers::prelude::*;
+   use serde::Serialize;
+   use std::collections::{HashMap, HashSet};
+   use std::sync::Arc;
+   use crate::config::{Config, DexConfig, DiscoveryToken, TokenPair};
+   use crate::modules::amounts::{self, Decimal, TokenAmount};
+   use crate::modules::multicall::CallBatch;
+   use crate::modules::pool_cache::MAX_LOG_RANGE;
+   use crate::modules::price_fetcher::{self, BlockRef, PriceData};
+   use crate::modules::price_source::DexKind;
+   use crate::modules::rpc_pool::RpcClient;
+   use crate::modules::token_registry::TokenRegistry;
+
+   abigen!(
+       IFactoryEvents,
+       r#"[
+           event PairCreated(address indexed token0, address indexed token1, address pair, uint256 pairCount)
+           event PoolCreated(address indexed token0, address indexed token1, uint24 indexed fee, int24 tickSpacing, address pool)
+       ]"#
+   );
+
+   abigen!(
+       IERC20Balance,
+       r#"[
+           function balanceOf(address account) external view returns (uint256)
+       ]"#
+   );
+
+   // Pools whose balances are read in one multicall
+   const BALANCE_BATCH: usize = 200;
+
+   /// A factory-created pool between two whitelisted tokens.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct DiscoveredPool {
+       pub pool: Address,
+       pub dex: String,
+       pub pair: TokenPair, // Oriented like a configured pair, else by whitelist order
+       pub fee: Option<u32>, // V3 fee tier
+       pub created_block: u64,
+       // Whole tokens the pool held when it was checked
+       pub base_balance: Decimal,
+       pub quote_balance: Decimal,
+       pub enabled: bool,
+   }
+
+   impl DiscoveredPool {
+       pub fn token_pair(&self) -> String {
+           format!("{}/{}", self.pair.base, self.pair.quote)
+       }
+
+       /// Name its quotes carry: the DEX, plus the fee tier for V3 pools.
+       pub fn market(&self) -> String {
+           match self.fee {
+               Some(fee) => format!("{}_{}", self.dex, fee),
+               None => self.dex.clone(),
+           }
+       }
+   }
+
+   /// What one discovery pass found.
+   #[derive(Debug, Clone, Default, Serialize)]
+   pub struct DiscoveryReport {
+       pub from_block: u64,
+       pub to_block: u64,
+       pub created: usize, // Pools created in the range, whitelisted or not
+       pub too_shallow: usize, // Whitelisted pools under the minimum liquidity
+       pub pools: Vec<DiscoveredPool>,
+   }
+
+   struct WhitelistToken {
+       symbol: String,
+       address: Address,
+       min_liquidity: Decimal,
+   }
+
+   /// Finds pools from the configured factories' `PairCreated`/`PoolCreated`
+   /// logs. The first pass backfills recent history; each later one picks up
+   /// where the previous stopped. Pools must be between whitelisted tokens and
+   /// hold at least each token's minimum balance.
+   pub struct PoolDiscovery {
+       client: Arc<RpcClient>,
+       tokens: Arc<TokenRegistry>,
+       factories: HashMap<Address, (String, DexKind)>,
+       whitelist: Vec<WhitelistToken>,
+       configured: Vec<TokenPair>,
+       backfill_blocks: u64,
+       scanned_to: Option<u64>,
+   }
+
+   impl PoolDiscovery {
+       pub fn new(
+           client: Arc<RpcClient>,
+           tokens: Arc<TokenRegistry>,
+           dexes: &[DexConfig],
+           whitelist: &[DiscoveryToken],
+       ) -> Result<Self> {
+           let mut factories = HashMap::new();
+           for dex in dexes.iter().filter(|dex| dex.kind != DexKind::Simulated) {
+               let factory = dex
+                   .factory
+                   .as_deref()
+                   .ok_or_else(|| anyhow!("DEX {} has no factory to discover pools from", dex.name))?;
+               factories.insert(price_fetcher::parse_address(factory)?, (dex.name.clone(), dex.kind));
+           }
+
+           let whitelist = whitelist
+               .iter()
+               .map(|token| {
+                   Ok(WhitelistToken {
+                       symbol: token.symbol.clone(),
+                       address: price_fetcher::parse_address(&token.address)?,
+                       min_liquidity: amounts::decimal(token.min_liquidity),
+                   })
+               })
+               .collect::<Result<_>>()?;
+
+           Ok(Self {
+               client,
+               tokens,
+               factories,
+               whitelist,
+               configured: Vec::new(),
+               backfill_blocks: 100_000,
+               scanned_to: None,
+           })
+       }
+
+       pub fn from_config(client: Arc<RpcClient>, tokens: Arc<TokenRegistry>, config: &Config) -> Result<Self> {
+           Ok(Self::new(client, tokens, &config.dexes, &config.discovery_tokens)?
+               .with_configured_pairs(&config.token_pairs)
+               .with_backfill(config.discovery_backfill_blocks))
+       }
+
+       /// Pairs whose base/quote order discovered pools of the same tokens keep.
+       pub fn with_configured_pairs(mut self, pairs: &[TokenPair]) -> Self {
+           self.configured = pairs.to_vec();
+           self
+       }
+
+       /// Blocks behind the head the first pass searches.
+       pub fn with_backfill(mut self, blocks: u64) -> Self {
+           self.backfill_blocks = blocks;
+           self
+       }
+
+       /// Last block searched; None before the first pass.
+       pub fn scanned_to(&self) -> Option<u64> {
+           self.scanned_to
+       }
+
+       /// Searches the blocks since the last pass up to `head` and returns the
+       /// qualifying pools created in them. Balances are read at `head`.
+       pub async fn scan_to(&mut self, head: BlockRef) -> Result<DiscoveryReport> {
+           let from = match self.scanned_to {
+               Some(scanned_to) => scanned_to + 1,
+               None => head.number.saturating_sub(self.backfill_blocks),
+           };
+           let mut report = DiscoveryReport {
+               from_block: from,
+               to_block: head.number,
+               ..DiscoveryReport::default()
+           };
+           if from > head.number || self.factories.is_empty() {
+               return Ok(report);
+           }
+
+           let mut candidates = Vec::new();
+           let mut start = from;
+           while start <= head.number {
+               let end = head.number.min(start + MAX_LOG_RANGE - 1);
+               let filter = Filter::new()
+                   .address(self.factories.keys().copied().collect::<Vec<_>>())
+                   .topic0(vec![PairCreatedFilter::signature(), PoolCreatedFilter::signature()])
+                   .from_block(start)
+                   .to_block(end);
+               let logs = self
+                   .client
+                   .get_logs(&filter)
+                   .await
+                   .map_err(|e| anyhow!("eth_getLogs {}..{}: {}", start, end, e))?;
+
+               for log in logs.iter().filter(|log| log.removed != Some(true)) {
+                   let Some((dex, pool, token0, token1, fee)) = self.decode_creation(log) else {
+                       continue;
+                   };
+                   report.created += 1;
+                   if let Some(pair) = self.orient(token0, token1) {
+                       candidates.push(DiscoveredPool {
+                           pool,
+                           dex,
+                           pair,
+                           fee,
+                           created_block: log.block_number.map(|number| number.as_u64()).unwrap_or(start),
+                           base_balance: Decimal::ZERO,
+                           quote_balance: Decimal::ZERO,
+                           enabled: true,
+                       });
+                   }
+               }
+               start = end + 1;
+           }
+
+           let found = candidates.len();
+           for chunk in candidates.chunks(BALANCE_BATCH) {
+               report.pools.extend(self.check_liquidity(chunk, head).await?);
+           }
+           report.too_shallow = found - report.pools.len();
+           self.scanned_to = Some(head.number);
+
+           Ok(report)
+       }
+
+       // The DEX, pool, tokens and fee tier of a creation log from a known factory
+       fn decode_creation(&self, log: &Log) -> Option<(String, Address, Address, Address, Option<u32>)> {
+           let (dex, kind) = self.factories.get(&log.address)?;
+           let raw = RawLog {
+               topics: log.topics.clone(),
+               data: log.data.to_vec(),
+           };
+           match (IFactoryEventsEvents::decode_log(&raw).ok()?, kind) {
+               (IFactoryEventsEvents::PairCreatedFilter(event), DexKind::UniswapV2) => {
+                   Some((dex.clone(), event.pair, event.token_0, event.token_1, None))
+               }
+               (IFactoryEventsEvents::PoolCreatedFilter(event), DexKind::UniswapV3) => {
+                   Some((dex.clone(), event.pool, event.token_0, event.token_1, Some(event.fee)))
+               }
+               _ => None,
+           }
+       }
+
+       // The pair two tokens form, when both are whitelisted
+       fn orient(&self, token0: Address, token1: Address) -> Option<TokenPair> {
+           for pair in &self.configured {
+               let (Ok(base), Ok(quote)) = (
+                   price_fetcher::parse_address(&pair.base_address),
+                   price_fetcher::parse_address(&pair.quote_address),
+               ) else {
+                   continue;
+               };
+               if (base, quote) == (token0, token1) || (base, quote) == (token1, token0) {
+                   return Some(pair.clone());
+               }
+           }
+
+           let index0 = self.whitelist.iter().position(|token| token.address == token0)?;
+           let index1 = self.whitelist.iter().position(|token| token.address == token1)?;
+           let (quote, base) = if index0 < index1 {
+               (&self.whitelist[index0], &self.whitelist[index1])
+           } else {
+               (&self.whitelist[index1], &self.whitelist[index0])
+           };
+           Some(TokenPair {
+               base: base.symbol.clone(),
+               quote: quote.symbol.clone(),
+               base_address: ethers::utils::to_checksum(&base.address, None),
+               quote_address: ethers::utils::to_checksum(&quote.address, None),
+           })
+       }
+
+       // Reads both token balances of each pool at `head`, keeping the deep enough ones
+       async fn check_liquidity(&self, pools: &[DiscoveredPool], head: BlockRef) -> Result<Vec<DiscoveredPool>> {
+           let mut batch = CallBatch::new();
+           let mut calls = Vec::new();
+           for pool in pools {
+               let base = price_fetcher::parse_address(&pool.pair.base_address)?;
+               let quote = price_fetcher::parse_address(&pool.pair.quote_address)?;
+               let base_call = batch.push(base, BalanceOfCall { account: pool.pool }.encode());
+               let quote_call = batch.push(quote, BalanceOfCall { account: pool.pool }.encode());
+               calls.push((base, quote, base_call, quote_call));
+           }
+           let results = batch.execute(&self.client, head).await?;
+
+           let mut qualifying = Vec::new();
+           for (pool, (base, quote, base_call, quote_call)) in pools.iter().zip(calls) {
+               let BalanceOfReturn(base_raw) = results.decode(base_call)?;
+               let BalanceOfReturn(quote_raw) = results.decode(quote_call)?;
+               let base_balance = TokenAmount::new(base_raw, self.tokens.decimals(base).await?).to_decimal();
+               let quote_balance = TokenAmount::new(quote_raw, self.tokens.decimals(quote).await?).to_decimal();
+               // Balances beyond a Decimal's range come from broken or hostile tokens
+               let (Some(base_balance), Some(quote_balance)) = (base_balance, quote_balance) else {
+                   continue;
+               };
+
+               if base_balance >= self.min_liquidity(base) && quote_balance >= self.min_liquidity(quote) {
+                   qualifying.push(DiscoveredPool {
+                       base_balance,
+                       quote_balance,
+                       ..pool.clone()
+                   });
+               }
+           }
+
+           Ok(qualifying)
+       }
+
+       // Configured pairs may use tokens outside the whitelist; those need no minimum
+       fn min_liquidity(&self, token: Address) -> Decimal {
+           self.whitelist
+               .iter()
+               .find(|whitelisted| whitelisted.address == token)
+               .map_or(Decimal::ZERO, |whitelisted| whitelisted.min_liquidity)
+       }
+   }
+
+   /// The pairs discovery adds to the configured ones, and which of their
+   /// markets are quoted: configured pairs keep every market but the
+   /// disabled pools, discovered pairs only their enabled pools.
+   #[derive(Debug, Clone, Default)]
+   pub struct DiscoveredMarkets {
+       configured: HashSet<String>,
+       pairs: Vec<TokenPair>,
+       enabled: HashSet<(String, String)>, // (market, "BASE/QUOTE")
+       disabled: HashSet<(String, String)>,
+   }
+
+   impl DiscoveredMarkets {
+       pub fn new(configured: &[TokenPair]) -> Self {
+           Self {
+               configured: configured.iter().map(|pair| format!("{}/{}", pair.base, pair.quote)).collect(),
+               ..Self::default()
+           }
+       }
+
+       pub fn from_pools(configured: &[TokenPair], pools: &[DiscoveredPool]) -> Self {
+           let mut markets = Self::new(configured);
+           for pool in pools {
+               markets.insert(pool);
+           }
+           markets
+       }
+
+       /// Records `pool`, returning its pair when that pair is newly monitored.
+       pub fn insert(&mut self, pool: &DiscoveredPool) -> Option<TokenPair> {
+           let key = (pool.market(), pool.token_pair());
+           if !pool.enabled {
+               self.enabled.remove(&key);
+               self.disabled.insert(key);
+               return None;
+           }
+           self.disabled.remove(&key);
+           self.enabled.insert(key);
+
+           let name = pool.token_pair();
+           if self.configured.contains(&name) || self.pairs.iter().any(|pair| format!("{}/{}", pair.base, pair.quote) == name) {
+               return None;
+           }
+           self.pairs.push(pool.pair.clone());
+           Some(pool.pair.clone())
+       }
+
+       /// Discovered pairs that are not configured.
+       pub fn pairs(&self) -> &[TokenPair] {
+           &self.pairs
+       }
+
+       pub fn allows(&self, price: &PriceData) -> bool {
+           let key = (price.dex_name.clone(), price.token_pair.clone());
+           if self.disabled.contains(&key) {
+               return false;
+           }
+           self.configured.contains(&price.token_pair) || self.enabled.contains(&key)
+       }
+   }
\ No newline at end of file
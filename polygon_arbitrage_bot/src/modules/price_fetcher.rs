@@ -1,88 +1,194 @@
 use anyhow::{Result, anyhow};
    use ethers::prelude::*;
    use serde::{Deserialize, Serialize};
-   use std::collections::HashMap;
-   use std::sync::Arc;
+   use crate::config::TokenPair;
+   use crate::modules::amounts::{self, Decimal, TokenAmount};
+   use crate::modules::multicall::{CallBatch, PendingQuote};
+   use crate::modules::price_source::{PriceSource, PriceSourceRegistry};
+   use crate::modules::v3_swap::V3Liquidity;
 
    #[derive(Debug, Clone, Serialize, Deserialize)]
    pub struct PriceData {
        pub dex_name: String,
        pub token_pair: String,
-       pub price: f64,
+       pub price: Decimal, // Quote tokens per base token
        pub timestamp: u64,
-       pub liquidity: f64,
+       pub liquidity: Decimal, // Pool depth in quote tokens
+       pub fee: f64, // Swap fee as a fraction of the input amount
+       pub block: Option<BlockRef>,
+       pub reserves: Option<Reserves>,
+       pub v3_liquidity: Option<V3Liquidity>,
    }
 
-   pub struct PriceFetcher {
-       client: Arc<Provider<Http>>,
-       dex_contracts: HashMap<String, String>,
+   impl PriceData {
+       /// Spot price as a float, for graph search and sizing math.
+       pub fn price_f64(&self) -> f64 {
+           amounts::float(self.price)
+       }
    }
 
-   impl PriceFetcher {
-       pub fn new(rpc_url: &str, dex_contracts: HashMap<String, String>) -> Result<Self> {
-           let provider = Provider::<Http>::try_from(rpc_url)
-               .map_err(|e| anyhow!("Failed to connect to RPC: {}", e))?;
-           
+   /// Pool balances. Virtual (in-range) balances for V3 pools.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   pub struct Reserves {
+       pub base: TokenAmount,
+       pub quote: TokenAmount,
+   }
+
+   /// The chain state a price was read from.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+   pub struct BlockRef {
+       pub number: u64,
+       pub hash: H256,
+       pub timestamp: u64,
+   }
+
+   impl BlockRef {
+       pub fn from_block<T>(block: &Block<T>) -> Result<Self> {
            Ok(Self {
-               client: Arc::new(provider),
-               dex_contracts,
+               number: block
+                   .number
+                   .ok_or_else(|| anyhow!("Block is missing its number"))?
+                   .as_u64(),
+               hash: block.hash.ok_or_else(|| anyhow!("Block is missing its hash"))?,
+               timestamp: block.timestamp.as_u64(),
            })
        }
+   }
+
+   pub struct PriceFetcher {
+       registry: PriceSourceRegistry,
+       verbose: bool,
+   }
 
-       pub async fn fetch_all_prices(&self, token_pairs: &[crate::config::TokenPair]) -> Result<Vec<PriceData>> {
+   impl PriceFetcher {
+       pub fn new(registry: PriceSourceRegistry) -> Self {
+           Self { registry, verbose: true }
+       }
+
+       /// Turns the per-quote progress lines on or off.
+       pub fn with_verbose(mut self, verbose: bool) -> Self {
+           self.verbose = verbose;
+           self
+       }
+
+       pub fn registry(&self) -> &PriceSourceRegistry {
+           &self.registry
+       }
+
+       pub async fn fetch_all_prices(&self, token_pairs: &[TokenPair]) -> Result<Vec<PriceData>> {
+           self.fetch_all_prices_at(token_pairs, None).await
+       }
+
+       /// Reads every quote at `block`, or at the latest block when None.
+       pub async fn fetch_all_prices_at(&self, token_pairs: &[TokenPair], block: Option<BlockRef>) -> Result<Vec<PriceData>> {
            let mut all_prices = Vec::new();
-           
+           let mut batch = CallBatch::new();
+           let mut pending: Vec<(&TokenPair, &dyn PriceSource, PendingQuote)> = Vec::new();
+           let mut unbatched: Vec<(&TokenPair, &dyn PriceSource)> = Vec::new();
+           let mut cached: Vec<(&TokenPair, &dyn PriceSource, Result<Vec<PriceData>>)> = Vec::new();
+
+           // Collect every source's reads so the whole cycle is one RPC round-trip
            for pair in token_pairs {
-               println!("  Fetching prices for {}/{}", pair.base, pair.quote);
-               
-               // Fetch from each DEX
-               for (dex_name, _contract_address) in &self.dex_contracts {
-                   match self.fetch_dex_price(dex_name, pair).await {
-                       Ok(price_data) => {
-                           println!("    {}: ${:.4}", dex_name, price_data.price);
-                           all_prices.push(price_data);
-                       }
-                       Err(e) => {
-                           println!("    {}: Error - {}", dex_name, e);
-                       }
+               for source in self.registry.sources() {
+                   if let Some(prices) = source.cached_quote(pair, block) {
+                       cached.push((pair, source.as_ref(), prices));
+                       continue;
+                   }
+                   match source.queue_reads(pair, &mut batch).await {
+                       Ok(Some(quote)) => pending.push((pair, source.as_ref(), quote)),
+                       Ok(None) => unbatched.push((pair, source.as_ref())),
+                       Err(e) => self.log(format_args!("    {} {}/{}: Error - {}", source.name(), pair.base, pair.quote, e)),
+                   }
+               }
+           }
+
+           if let Some(head) = self.registry.pool_cache().and_then(|cache| cache.head()).filter(|_| !cached.is_empty()) {
+               self.log(format_args!("  Served {} quotes from the pool cache at block #{}", cached.len(), head.number));
+           }
+           for (pair, source, prices) in cached {
+               self.collect(pair, source, prices, &mut all_prices);
+           }
+
+           if !pending.is_empty() {
+               let client = self
+                   .registry
+                   .client()
+                   .ok_or_else(|| anyhow!("Batched price reads need an RPC client"))?;
+               let block = match block {
+                   Some(block) => block,
+                   None => {
+                       let latest = client
+                           .get_block(BlockNumber::Latest)
+                           .await?
+                           .ok_or_else(|| anyhow!("RPC returned no latest block"))?;
+                       BlockRef::from_block(&latest)?
                    }
+               };
+               let results = batch.execute(&client, block).await?;
+               self.log(format_args!("  Read {} contract calls at block #{} ({:?})", batch.len(), block.number, block.hash));
+
+               let mut decoded: Vec<_> = pending
+                   .into_iter()
+                   .map(|(pair, source, quote)| (pair, source, quote(&results)))
+                   .collect();
+
+               // Follow-up reads that depend on the batch, pinned to the same block
+               for source in self.registry.sources() {
+                   let mut prices: Vec<&mut PriceData> = decoded
+                       .iter_mut()
+                       .filter(|(_, quoted_by, _)| std::ptr::addr_eq(*quoted_by, source.as_ref()))
+                       .filter_map(|(_, _, prices)| prices.as_mut().ok())
+                       .flatten()
+                       .collect();
+                   if prices.is_empty() {
+                       continue;
+                   }
+                   if let Err(e) = source.load_liquidity(&mut prices, block).await {
+                       self.log(format_args!("    {}: Could not load pool liquidity - {}", source.name(), e));
+                   }
+               }
+
+               for (pair, source, prices) in decoded {
+                   self.collect(pair, source, prices, &mut all_prices);
                }
            }
-           
+
+           for (pair, source) in unbatched {
+               self.collect(pair, source, source.quote(pair).await, &mut all_prices);
+           }
+
            Ok(all_prices)
        }
 
-       async fn fetch_dex_price(&self, dex_name: &str, pair: &crate::config::TokenPair) -> Result<PriceData> {
-           // For now, we'll simulate price fetching
-           // In a real implementation, you'd query the actual DEX contracts
-           let simulated_price = self.simulate_price(dex_name, pair);
-           
-           Ok(PriceData {
-               dex_name: dex_name.to_string(),
-               token_pair: format!("{}/{}", pair.base, pair.quote),
-               price: simulated_price,
-               timestamp: std::time::SystemTime::now()
-                   .duration_since(std::time::UNIX_EPOCH)?
-                   .as_secs(),
-               liquidity: 100000.0, // Simulated liquidity
-           })
+       fn collect(&self, pair: &TokenPair, source: &dyn PriceSource, prices: Result<Vec<PriceData>>, all_prices: &mut Vec<PriceData>) {
+           match prices {
+               Ok(prices) => {
+                   for price_data in prices {
+                       self.log(format_args!("    {} {}: ${:.4}", price_data.token_pair, price_data.dex_name, price_data.price));
+                       all_prices.push(price_data);
+                   }
+               }
+               Err(e) => {
+                   self.log(format_args!("    {} {}/{}: Error - {}", source.name(), pair.base, pair.quote, e));
+               }
+           }
        }
 
-       fn simulate_price(&self, dex_name: &str, pair: &crate::config::TokenPair) -> f64 {
-           // Simulate different prices on different DEXes
-           let base_price = match pair.base.as_str() {
-               "WETH" => 2500.0,
-               "WBTC" => 45000.0,
-               _ => 1.0,
-           };
-           
-           // Add small variations per DEX to simulate arbitrage opportunities
-           let variation = match dex_name {
-               "uniswap_v3" => 0.0,
-               "sushiswap" => 0.002, // 0.2% difference
-               _ => 0.001,
-           };
-           
-           base_price * (1.0 + variation)
+       fn log(&self, line: std::fmt::Arguments) {
+           if self.verbose {
+               println!("{}", line);
+           }
        }
-   }
\ No newline at end of file
+   }
+
+   pub fn parse_address(address: &str) -> Result<Address> {
+       address
+           .parse::<Address>()
+           .map_err(|e| anyhow!("Invalid token address {}: {}", address, e))
+   }
+
+   pub fn unix_timestamp() -> Result<u64> {
+       Ok(std::time::SystemTime::now()
+           .duration_since(std::time::UNIX_EPOCH)?
+           .as_secs())
+   }
@@ -0,0 +1,288 @@
+use anyhow::{Result, anyhow};
+   use async_trait::async_trait;
+   use ethers::prelude::*;
+   use serde::{Deserialize, Serialize};
+   use std::collections::HashMap;
+   use std::sync::Arc;
+   use crate::config::{Config, DexConfig, TokenPair};
+   use crate::modules::amounts::{self, TokenAmount};
+   use crate::modules::multicall::{CallBatch, PendingQuote};
+   use crate::modules::pool_cache::PoolStateCache;
+   use crate::modules::price_fetcher::{self, BlockRef, PriceData, Reserves};
+   use crate::modules::rpc_pool::{RpcClient, RpcPool};
+   use crate::modules::token_registry::{self, TokenRegistry};
+   use crate::modules::uniswap_v2::{UniswapV2Source, SWAP_FEE};
+   use crate::modules::uniswap_v3::UniswapV3Source;
+
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+   #[serde(rename_all = "snake_case")]
+   pub enum DexKind {
+       UniswapV2,
+       UniswapV3,
+       Simulated,
+   }
+
+   /// A venue that can quote token pairs. One source may report several
+   /// markets for a pair (e.g. one per V3 fee tier).
+   #[async_trait]
+   pub trait PriceSource: Send + Sync {
+       fn name(&self) -> &str;
+
+       fn kind(&self) -> DexKind;
+
+       /// Fee every pool of this source charges, as a fraction of the input.
+       /// None when it differs per pool, like V3 fee tiers.
+       fn swap_fee(&self) -> Option<f64> {
+           None
+       }
+
+       async fn quote(&self, pair: &TokenPair) -> Result<Vec<PriceData>>;
+
+       /// Queues this source's state reads for `pair` into a shared batch.
+       /// Sources that return `None` are quoted one by one through `quote`.
+       async fn queue_reads(&self, _pair: &TokenPair, _batch: &mut CallBatch) -> Result<Option<PendingQuote>> {
+           Ok(None)
+       }
+
+       /// Fills in pool detail that depends on the batched reads, such as V3
+       /// tick liquidity, read at the same `block`.
+       async fn load_liquidity(&self, _prices: &mut [&mut PriceData], _block: BlockRef) -> Result<()> {
+           Ok(())
+       }
+
+       /// Quotes `pair` from a log-fed pool cache without any RPC call. None
+       /// when the cache holds no current state for it at `block` (its head
+       /// when None), so the pair is read from the node instead.
+       fn cached_quote(&self, _pair: &TokenPair, _block: Option<BlockRef>) -> Option<Result<Vec<PriceData>>> {
+           None
+       }
+   }
+
+   #[derive(Default)]
+   pub struct PriceSourceRegistry {
+       sources: Vec<Box<dyn PriceSource>>,
+       client: Option<Arc<RpcClient>>,
+       rpc_pool: Option<RpcPool>,
+       tokens: Option<Arc<TokenRegistry>>,
+       pool_cache: Option<Arc<PoolStateCache>>,
+   }
+
+   impl PriceSourceRegistry {
+       pub fn new() -> Self {
+           Self::default()
+       }
+
+       pub fn from_config(config: &Config) -> Result<Self> {
+           let cache = config
+               .pool_cache
+               .then(|| Arc::new(PoolStateCache::new().with_depth(config.reorg_depth)));
+           Self::build(RpcPool::from_config(config)?, &config.dexes, cache)
+       }
+
+       pub fn from_dexes(rpc_url: &str, dexes: &[DexConfig]) -> Result<Self> {
+           Self::build(RpcPool::single(rpc_url)?, dexes, None)
+       }
+
+       /// Like `from_dexes`, with the on-chain sources seeding and quoting
+       /// from `cache`.
+       pub fn from_dexes_cached(rpc_url: &str, dexes: &[DexConfig], cache: Arc<PoolStateCache>) -> Result<Self> {
+           Self::build(RpcPool::single(rpc_url)?, dexes, Some(cache))
+       }
+
+       fn build(pool: RpcPool, dexes: &[DexConfig], cache: Option<Arc<PoolStateCache>>) -> Result<Self> {
+           let client = Arc::new(Provider::new(pool.clone()));
+           let tokens = Arc::new(TokenRegistry::new(client.clone()));
+
+           let mut registry = Self::new();
+           // Simulated DEXes alone never touch the node
+           if dexes.iter().any(|dex| dex.kind != DexKind::Simulated) {
+               registry.client = Some(client.clone());
+               registry.rpc_pool = Some(pool);
+           }
+           registry.tokens = Some(tokens.clone());
+           registry.pool_cache = cache.clone();
+           for dex in dexes {
+               let source: Box<dyn PriceSource> = match dex.kind {
+                   DexKind::UniswapV2 => {
+                       let mut source = UniswapV2Source::new(&dex.name, client.clone(), required_factory(dex)?, tokens.clone())?
+                           .with_fee(dex.fee.map(|fee| checked_fee(dex, fee)).transpose()?.unwrap_or(SWAP_FEE));
+                       if let Some(cache) = &cache {
+                           source = source.with_pool_cache(cache.clone());
+                       }
+                       Box::new(source)
+                   }
+                   DexKind::UniswapV3 if dex.fee.is_some() => {
+                       return Err(anyhow!("DEX {} is Uniswap V3; its fees come from the pool fee tiers", dex.name));
+                   }
+                   DexKind::UniswapV3 => {
+                       let mut source = UniswapV3Source::new(&dex.name, client.clone(), required_factory(dex)?, tokens.clone())?;
+                       if let Some(cache) = &cache {
+                           source = source.with_pool_cache(cache.clone());
+                       }
+                       Box::new(source)
+                   }
+                   DexKind::Simulated => Box::new(
+                       SimulatedSource::new(&dex.name, tokens.clone())
+                           .with_fee(dex.fee.map(|fee| checked_fee(dex, fee)).transpose()?.unwrap_or(SWAP_FEE)),
+                   ),
+               };
+               registry.register(source);
+           }
+
+           Ok(registry)
+       }
+
+       pub fn register(&mut self, source: Box<dyn PriceSource>) {
+           self.sources.push(source);
+       }
+
+       pub fn get(&self, name: &str) -> Option<&dyn PriceSource> {
+           self.sources
+               .iter()
+               .find(|source| source.name() == name)
+               .map(|source| source.as_ref())
+       }
+
+       /// Provider shared by the on-chain sources, if any were built from config.
+       pub fn client(&self) -> Option<Arc<RpcClient>> {
+           self.client.clone()
+       }
+
+       /// The endpoints behind `client`, for their health.
+       pub fn rpc_pool(&self) -> Option<&RpcPool> {
+           self.rpc_pool.as_ref()
+       }
+
+       /// Token metadata shared by the sources built from config.
+       pub fn tokens(&self) -> Option<Arc<TokenRegistry>> {
+           self.tokens.clone()
+       }
+
+       /// Pool state shared by the on-chain sources, when enabled.
+       pub fn pool_cache(&self) -> Option<Arc<PoolStateCache>> {
+           self.pool_cache.clone()
+       }
+
+       pub fn sources(&self) -> &[Box<dyn PriceSource>] {
+           &self.sources
+       }
+
+       pub fn len(&self) -> usize {
+           self.sources.len()
+       }
+
+       pub fn is_empty(&self) -> bool {
+           self.sources.is_empty()
+       }
+   }
+
+   fn checked_fee(dex: &DexConfig, fee: f64) -> Result<f64> {
+       if !(0.0..1.0).contains(&fee) {
+           return Err(anyhow!("DEX {} has fee {}; expected a fraction in [0, 1)", dex.name, fee));
+       }
+       Ok(fee)
+   }
+
+   fn required_factory(dex: &DexConfig) -> Result<&str> {
+       dex.factory
+           .as_deref()
+           .ok_or_else(|| anyhow!("DEX {} ({:?}) needs a factory address", dex.name, dex.kind))
+   }
+
+   // USD prices the simulation quotes around: WETH, WBTC and WMATIC on Polygon
+   const REFERENCE_PRICES: [(&str, f64); 3] = [
+       ("0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", 2500.0),
+       ("0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6", 45000.0),
+       ("0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270", 0.8),
+   ];
+
+   /// Deterministic prices for running without a node. Token decimals come
+   /// from the registry when already known, else 18 is assumed.
+   pub struct SimulatedSource {
+       name: String,
+       fee: f64,
+       tokens: Arc<TokenRegistry>,
+       reference_prices: HashMap<Address, f64>,
+   }
+
+   impl SimulatedSource {
+       pub fn new(name: &str, tokens: Arc<TokenRegistry>) -> Self {
+           Self {
+               name: name.to_string(),
+               fee: SWAP_FEE,
+               tokens,
+               reference_prices: REFERENCE_PRICES
+                   .iter()
+                   .filter_map(|(token, usd)| Some((token.parse().ok()?, *usd)))
+                   .collect(),
+           }
+       }
+
+       pub fn with_fee(mut self, fee: f64) -> Self {
+           self.fee = fee;
+           self
+       }
+
+       /// USD price to simulate `token` around; unlisted tokens count as $1.
+       pub fn with_reference_price(mut self, token: Address, usd: f64) -> Self {
+           self.reference_prices.insert(token, usd);
+           self
+       }
+
+       fn simulate_price(&self, base: Address, quote: Address) -> f64 {
+           // Simulate different prices on different DEXes
+           let usd_price = |token: Address| self.reference_prices.get(&token).copied().unwrap_or(1.0);
+           let base_price = usd_price(base) / usd_price(quote);
+
+           // Add small variations per DEX to simulate arbitrage opportunities
+           let variation = match self.name.as_str() {
+               "uniswap_v3" => 0.0,
+               "sushiswap" => 0.002, // 0.2% difference
+               _ => 0.001,
+           };
+
+           base_price * (1.0 + variation)
+       }
+   }
+
+   #[async_trait]
+   impl PriceSource for SimulatedSource {
+       fn name(&self) -> &str {
+           &self.name
+       }
+
+       fn kind(&self) -> DexKind {
+           DexKind::Simulated
+       }
+
+       fn swap_fee(&self) -> Option<f64> {
+           Some(self.fee)
+       }
+
+       async fn quote(&self, pair: &TokenPair) -> Result<Vec<PriceData>> {
+           let base = token_registry::parse_checksummed(&pair.base_address)?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address)?;
+           // Only metadata already known is used, so simulation needs no node
+           let decimals = |token: Address| self.tokens.get(token).map_or(18, |info| info.decimals);
+           let price = self.simulate_price(base, quote);
+           let liquidity = 100000.0; // Simulated liquidity
+           let amount = |whole: f64, decimals: u8| {
+               TokenAmount::from_f64(whole, decimals).ok_or_else(|| anyhow!("Simulated amount {} is out of range", whole))
+           };
+
+           Ok(vec![PriceData {
+               dex_name: self.name.clone(),
+               token_pair: format!("{}/{}", pair.base, pair.quote),
+               price: amounts::decimal(price),
+               timestamp: price_fetcher::unix_timestamp()?,
+               liquidity: amounts::decimal(liquidity),
+               fee: self.fee,
+               block: None,
+               reserves: Some(Reserves {
+                   quote: amount(liquidity / 2.0, decimals(quote))?,
+                   base: amount(liquidity / 2.0 / price, decimals(base))?,
+               }),
+               v3_liquidity: None,
+           }])
+       }
+   }
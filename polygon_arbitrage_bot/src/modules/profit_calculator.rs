@@ -1,90 +1,282 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+   use crate::modules::amounts::{self, Decimal, TokenAmount};
    use crate::modules::arbitrage_detector::ArbitrageOpportunity;
+   use crate::modules::gas_estimator::{GasEstimate, GasEstimateSource, GasEstimator, SwapLeg};
+   use crate::modules::gas_oracle::{GasOracle, GasPrice};
+   use crate::modules::native_price::{NativePrice, NativePriceOracle};
+   use crate::modules::price_fetcher::BlockRef;
+   use crate::modules::route::Route;
+   use ethers::types::U256;
    use serde::{Deserialize, Serialize};
 
    #[derive(Debug, Clone, Serialize, Deserialize)]
    pub struct ProfitAnalysis {
-       pub gross_profit: f64,
-       pub gas_costs: f64,
-       pub slippage_cost: f64,
-       pub net_profit: f64,
-       pub roi_percentage: f64,
+       pub gross_profit: Decimal,
+       pub gas_costs: Decimal,
+       pub gas_units: u64,
+       pub price_impact_cost: Decimal, // Deterministic, from pool depth at the trade size
+       pub risk_buffer: Decimal, // Allowance for reserves moving before inclusion
+       pub slippage_cost: Decimal, // price_impact_cost + risk_buffer
+       pub net_profit: Decimal,
+       pub roi_percentage: Decimal,
        pub execution_time_estimate: u64, // seconds
+       pub legs: Vec<LegCost>,
+       pub gas_price: GasPrice,
+       pub native_price: NativePrice, // MATIC/USD used to price the gas
+   }
+
+   /// Costs attributed to a single swap of the trade.
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   pub struct LegCost {
+       pub dex_name: String,
+       pub gas_units: u64,
+       pub gas_source: GasEstimateSource,
+       pub gas_cost_wei: U256,
+       pub gas_cost: Decimal,
+       pub price_impact: Option<f64>, // None when the pool state was unknown
+       pub price_impact_cost: Decimal,
+       pub risk_buffer: Decimal,
+       pub slippage_cost: Decimal,
    }
 
    pub struct ProfitCalculator {
        gas_price_gwei: f64,
+       gas_oracle: Option<GasOracle>,
+       matic_price: Decimal,
+       native_price_oracle: Option<NativePriceOracle>,
+       gas_estimator: Option<GasEstimator>,
        slippage_percentage: f64,
+       reserve_tolerance: f64,
        swap_gas_limit: u64,
    }
 
    impl ProfitCalculator {
        pub fn new() -> Self {
            Self {
-               gas_price_gwei: 30.0, // Average Polygon gas price
-               slippage_percentage: 0.005, // 0.5% slippage
-               swap_gas_limit: 200_000, // Estimated gas for DEX swaps
+               gas_price_gwei: 30.0, // Average Polygon gas price, used without an oracle
+               gas_oracle: None,
+               matic_price: Decimal::new(8, 1), // Approximate MATIC price in USD, used without an oracle
+               native_price_oracle: None,
+               gas_estimator: None,
+               slippage_percentage: 0.005, // 0.5% round trip, for pools of unknown depth
+               reserve_tolerance: 0.001, // Reserves may move 0.1% against us before inclusion
+               swap_gas_limit: 200_000, // Estimated gas for DEX swaps, used without an estimator
            }
        }
 
-       pub fn calculate_detailed_profit(&self, opportunity: &ArbitrageOpportunity) -> Result<ProfitAnalysis> {
-           // Calculate gross profit
-           let gross_profit = opportunity.profit_usd;
+       /// Prices gas from the node instead of the fixed 30 gwei.
+       pub fn with_gas_oracle(mut self, oracle: GasOracle) -> Self {
+           self.gas_oracle = Some(oracle);
+           self
+       }
+
+       /// Converts gas to USD at the on-chain MATIC price instead of a fixed $0.80.
+       pub fn with_native_price_oracle(mut self, oracle: NativePriceOracle) -> Self {
+           self.native_price_oracle = Some(oracle);
+           self
+       }
+
+       /// Estimates gas per swap instead of a flat 200k.
+       pub fn with_gas_estimator(mut self, estimator: GasEstimator) -> Self {
+           self.gas_estimator = Some(estimator);
+           self
+       }
+
+       /// How far each pool's reserves may move against the trade between
+       /// detection and inclusion, as a fraction of the reserves.
+       pub fn with_reserve_tolerance(mut self, tolerance: f64) -> Self {
+           self.reserve_tolerance = tolerance;
+           self
+       }
+
+       /// Gas price at `block` from the oracle, or the fixed default.
+       pub async fn gas_price(&self, block: Option<u64>) -> Result<GasPrice> {
+           match &self.gas_oracle {
+               Some(oracle) => oracle.gas_price(block).await,
+               None => Ok(GasPrice::fixed(self.gas_price_gwei)),
+           }
+       }
+
+       /// MATIC price at `block` from the oracle, or the fixed default.
+       pub async fn native_price(&self, block: Option<BlockRef>) -> Result<NativePrice> {
+           match &self.native_price_oracle {
+               Some(oracle) => oracle.price(block).await,
+               None => Ok(NativePrice::fixed(self.matic_price)),
+           }
+       }
+
+       /// Gas units for one swap from the estimator, or the flat default.
+       pub async fn gas_units(&self, leg: &SwapLeg, block: Option<u64>) -> GasEstimate {
+           match &self.gas_estimator {
+               Some(estimator) => estimator.estimate(leg, block).await,
+               None => GasEstimate {
+                   units: self.swap_gas_limit,
+                   source: GasEstimateSource::Default,
+               },
+           }
+       }
+
+       pub async fn calculate_detailed_profit(&self, opportunity: &ArbitrageOpportunity) -> Result<ProfitAnalysis> {
+           let gas_price = self.gas_price(opportunity.block.map(|b| b.number)).await?;
+           let native_price = self.native_price(opportunity.block).await?;
+           self.calculate_profit_with(opportunity, gas_price, native_price).await
+       }
 
-           // Calculate gas costs (in USD)
+       /// Like `calculate_detailed_profit`, at gas and MATIC prices read once
+       /// for many opportunities.
+       pub async fn calculate_profit_with(
+           &self,
+           opportunity: &ArbitrageOpportunity,
+           gas_price: GasPrice,
+           native_price: NativePrice,
+       ) -> Result<ProfitAnalysis> {
+           let mut legs = Vec::new();
+           for leg in opportunity.swap_legs() {
+               let gas = self.gas_units(&leg, opportunity.block.map(|b| b.number)).await;
+               legs.push(self.leg_cost(&leg, opportunity.trade_size, gas, &gas_price, &native_price)?);
+           }
+
+           // Price impact is charged per leg below, so start from the quoted-price profit
+           self.analysis(opportunity.quoted_profit_usd(), opportunity.trade_size, legs, gas_price, native_price)
+       }
+
+       /// Prices a cycle hop by hop: every swap pays its own gas and slippage.
+       pub async fn calculate_route_profit(&self, route: &Route) -> Result<ProfitAnalysis> {
+           if route.start_amount <= 0.0 {
+               anyhow::bail!("Route {} has no starting amount", route.route());
+           }
+
+           let gas_price = self.gas_price(route.block.map(|b| b.number)).await?;
+           let native_price = self.native_price(route.block).await?;
+           // A cycle is close to break-even, so every hop moves roughly trade_size worth
+           let mut legs = Vec::new();
+           for leg in route.swap_legs() {
+               let gas = self.gas_units(&leg, route.block.map(|b| b.number)).await;
+               legs.push(self.leg_cost(&leg, amounts::decimal(route.trade_size), gas, &gas_price, &native_price)?);
+           }
+
+           self.analysis(
+               amounts::decimal(route.quoted_profit_usd()),
+               amounts::decimal(route.trade_size),
+               legs,
+               gas_price,
+               native_price,
+           )
+       }
+
+       fn leg_cost(&self, leg: &SwapLeg, value_usd: Decimal, gas: GasEstimate, gas_price: &GasPrice, native_price: &NativePrice) -> Result<LegCost> {
            // Polygon gas costs are very low compared to Ethereum
-           let gas_cost_matic = (self.gas_price_gwei * self.swap_gas_limit as f64 * 2.0) / 1_000_000_000.0; // 2 swaps
-           let matic_price = 0.8; // Approximate MATIC price in USD
-           let gas_costs = gas_cost_matic * matic_price;
+           let out_of_range = || anyhow!("Gas cost out of range: {} gas at {} wei on {}", gas.units, gas_price.gas_price_wei(), leg.dex_name);
+           let gas_cost_wei = gas_price.gas_price_wei().checked_mul(U256::from(gas.units)).ok_or_else(out_of_range)?;
+           let gas_cost = TokenAmount::new(gas_cost_wei, 18)
+               .to_decimal()
+               .and_then(|matic| matic.checked_mul(native_price.usd))
+               .ok_or_else(out_of_range)?;
 
-           // Calculate slippage costs
-           let slippage_cost = opportunity.trade_size * self.slippage_percentage;
+           // Pools of unknown depth get the flat allowance, calibrated for a two-swap round trip
+           let price_impact_cost = value_usd.checked_mul(amounts::decimal(leg.price_impact.unwrap_or(self.slippage_percentage / 2.0)));
+           // Reserves moving by t shift an x*y=k price by (1 + t)^2
+           let risk_buffer = value_usd.checked_mul(amounts::decimal((1.0 + self.reserve_tolerance).powi(2) - 1.0));
+           let (Some(price_impact_cost), Some(risk_buffer)) = (price_impact_cost, risk_buffer) else {
+               return Err(anyhow!("Slippage cost out of range for ${} on {}", value_usd, leg.dex_name));
+           };
+
+           Ok(LegCost {
+               dex_name: leg.dex_name.clone(),
+               gas_units: gas.units,
+               gas_source: gas.source,
+               gas_cost_wei,
+               gas_cost,
+               price_impact: leg.price_impact,
+               price_impact_cost,
+               risk_buffer,
+               slippage_cost: checked_sum([price_impact_cost, risk_buffer])?,
+           })
+       }
+
+       fn analysis(&self, gross_profit: Decimal, trade_size: Decimal, legs: Vec<LegCost>, gas_price: GasPrice, native_price: NativePrice) -> Result<ProfitAnalysis> {
+           let gas_costs = checked_sum(legs.iter().map(|leg| leg.gas_cost))?;
+           let gas_units = legs.iter().map(|leg| leg.gas_units).sum::<u64>();
+           let price_impact_cost = checked_sum(legs.iter().map(|leg| leg.price_impact_cost))?;
+           let risk_buffer = checked_sum(legs.iter().map(|leg| leg.risk_buffer))?;
+           let slippage_cost = checked_sum([price_impact_cost, risk_buffer])?;
 
            // Calculate net profit
-           let net_profit = gross_profit - gas_costs - slippage_cost;
+           let net_profit = gross_profit
+               .checked_sub(gas_costs)
+               .and_then(|profit| profit.checked_sub(slippage_cost))
+               .ok_or_else(|| anyhow!("Net profit out of range"))?;
 
            // Calculate ROI
-           let roi_percentage = (net_profit / opportunity.trade_size) * 100.0;
+           let roi_percentage = net_profit
+               .checked_div(trade_size)
+               .and_then(|roi| roi.checked_mul(Decimal::ONE_HUNDRED))
+               .unwrap_or(Decimal::ZERO);
 
            Ok(ProfitAnalysis {
                gross_profit,
                gas_costs,
+               gas_units,
+               price_impact_cost,
+               risk_buffer,
                slippage_cost,
                net_profit,
                roi_percentage,
                execution_time_estimate: 30, // Estimated 30 seconds for execution
+               legs,
+               gas_price,
+               native_price,
            })
        }
 
-       pub fn print_detailed_analysis(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
-           let analysis = self.calculate_detailed_profit(opportunity)?;
-
+       /// Prints an analysis already computed for `opportunity`.
+       pub fn print_detailed_analysis(&self, opportunity: &ArbitrageOpportunity, analysis: &ProfitAnalysis) {
            println!("💰 Detailed Profit Analysis for {}", opportunity.token_pair);
            println!("{}", "=".repeat(60));
            println!("📊 Trade Details:");
            println!("   Buy from:     {} at ${:.4}", opportunity.buy_dex, opportunity.buy_price);
            println!("   Sell on:      {} at ${:.4}", opportunity.sell_dex, opportunity.sell_price);
            println!("   Trade size:   ${:.0}", opportunity.trade_size);
+           println!("   Spread:       {:.3}% raw, {:.3}% after fees",
+                   opportunity.raw_spread * Decimal::ONE_HUNDRED, opportunity.profit_percentage * Decimal::ONE_HUNDRED);
            println!();
            println!("💵 Profit Breakdown:");
            println!("   Gross profit:   ${:.2}", analysis.gross_profit);
-           println!("   Gas costs:      ${:.2}", analysis.gas_costs);
-           println!("   Slippage:       ${:.2}", analysis.slippage_cost);
+           println!("   Gas costs:      ${:.2} ({:.2} gwei base + {:.2} gwei priority)",
+                   analysis.gas_costs, analysis.gas_price.base_fee_gwei(), analysis.gas_price.priority_fee_gwei());
+           for leg in &analysis.legs {
+               println!("     - {}: {} gas units ({})", leg.dex_name, leg.gas_units, leg.gas_source.as_str());
+           }
+           println!("   MATIC price:    ${:.4} ({})", analysis.native_price.usd, analysis.native_price.label());
+           println!("   Price impact:   ${:.2}", analysis.price_impact_cost);
+           for leg in &analysis.legs {
+               match leg.price_impact {
+                   Some(impact) => println!("     - {}: {:.3}%", leg.dex_name, impact * 100.0),
+                   None => println!("     - {}: unknown depth, flat estimate", leg.dex_name),
+               }
+           }
+           println!("   Risk buffer:    ${:.2} ({:.2}% reserve tolerance)",
+                   analysis.risk_buffer, self.reserve_tolerance * 100.0);
            println!("   NET PROFIT:     ${:.2}", analysis.net_profit);
            println!("   ROI:            {:.3}%", analysis.roi_percentage);
            println!();
            println!("⏱️  Estimated execution time: {} seconds", analysis.execution_time_estimate);
            
-           if analysis.net_profit > 0.0 {
+           if analysis.net_profit > Decimal::ZERO {
                println!("✅ PROFITABLE OPPORTUNITY!");
            } else {
                println!("❌ Not profitable after costs");
            }
-
-           Ok(())
        }
    }
 
+   fn checked_sum(costs: impl IntoIterator<Item = Decimal>) -> Result<Decimal> {
+       costs
+           .into_iter()
+           .try_fold(Decimal::ZERO, |total, cost| total.checked_add(cost))
+           .ok_or_else(|| anyhow!("Cost total out of range"))
+   }
+
    impl Default for ProfitCalculator {
        fn default() -> Self {
            Self::new()
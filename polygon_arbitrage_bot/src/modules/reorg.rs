@@ -0,0 +1,177 @@
+use anyhow::{Result, anyhow};
+   use chrono::{DateTime, Utc};
+   use ethers::prelude::*;
+   use serde::Serialize;
+   use std::collections::VecDeque;
+   use std::sync::Arc;
+   use tokio::sync::broadcast;
+   use crate::modules::rpc_pool::RpcClient;
+
+   // Reorg events held for subscribers that fall behind
+   const EVENT_BUFFER: usize = 16;
+
+   /// A block header's place in the chain.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+   pub struct ChainLink {
+       pub number: u64,
+       pub hash: H256,
+       pub parent_hash: H256,
+   }
+
+   impl ChainLink {
+       pub fn from_block<T>(block: &Block<T>) -> Result<Self> {
+           Ok(Self {
+               number: block
+                   .number
+                   .ok_or_else(|| anyhow!("Block is missing its number"))?
+                   .as_u64(),
+               hash: block.hash.ok_or_else(|| anyhow!("Block is missing its hash"))?,
+               parent_hash: block.parent_hash,
+           })
+       }
+   }
+
+   /// Blocks the scanner saw that are no longer on the canonical chain.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct ReorgEvent {
+       pub detected_at: DateTime<Utc>,
+       pub common_ancestor: Option<u64>, // None when the fork is older than the tracked history
+       pub orphaned: Vec<ChainLink>, // Oldest first
+       pub new_head: ChainLink,
+       pub invalidated_opportunities: u64,
+       pub invalidated_snapshots: u64,
+   }
+
+   impl ReorgEvent {
+       pub fn orphaned_hashes(&self) -> Vec<H256> {
+           self.orphaned.iter().map(|block| block.hash).collect()
+       }
+
+       pub fn print(&self) {
+           let ancestor = self.common_ancestor.map_or("beyond tracked history".to_string(), |number| format!("#{}", number));
+           println!(
+               "↩️  Reorg: {} block(s) orphaned since {}; new head #{} ({:?})",
+               self.orphaned.len(),
+               ancestor,
+               self.new_head.number,
+               self.new_head.hash
+           );
+           if self.invalidated_opportunities > 0 || self.invalidated_snapshots > 0 {
+               println!(
+                   "  - Marked {} opportunities and {} price snapshots as reorged",
+                   self.invalidated_opportunities, self.invalidated_snapshots
+               );
+           }
+       }
+   }
+
+   /// Remembers the recent heads the scanner worked on and notices when one
+   /// of them is replaced: a new head whose parent is not the last one seen.
+   pub struct ReorgDetector {
+       client: Arc<RpcClient>,
+       seen: VecDeque<ChainLink>, // Oldest first, one per height
+       depth: u64,
+       events: broadcast::Sender<ReorgEvent>,
+   }
+
+   impl ReorgDetector {
+       pub fn new(client: Arc<RpcClient>) -> Self {
+           Self {
+               client,
+               seen: VecDeque::new(),
+               depth: 64,
+               events: broadcast::channel(EVENT_BUFFER).0,
+           }
+       }
+
+       /// Blocks of history kept for finding where a fork started.
+       pub fn with_depth(mut self, blocks: u64) -> Self {
+           self.depth = blocks.max(1);
+           self
+       }
+
+       /// Receives every reorg published after subscribing.
+       pub fn subscribe(&self) -> broadcast::Receiver<ReorgEvent> {
+           self.events.subscribe()
+       }
+
+       /// Sends `event` to subscribers; having none is fine.
+       pub fn publish(&self, event: &ReorgEvent) {
+           let _ = self.events.send(event.clone());
+       }
+
+       pub fn recent(&self) -> Vec<ChainLink> {
+           self.seen.iter().copied().collect()
+       }
+
+       /// Records `head`. When it does not extend the last head seen, the
+       /// tracked blocks are checked against the node to find the newest one
+       /// still canonical, and everything newer is reported as orphaned.
+       pub async fn observe(&mut self, head: ChainLink) -> Result<Option<ReorgEvent>> {
+           if self.seen.iter().any(|seen| seen.hash == head.hash) {
+               return Ok(None);
+           }
+           let extends_last = self
+               .seen
+               .back()
+               .is_none_or(|last| last.number + 1 == head.number && last.hash == head.parent_hash);
+           if extends_last {
+               self.push(head);
+               return Ok(None);
+           }
+
+           let ancestor = self.common_ancestor(head).await?;
+           if ancestor.is_some_and(|ancestor| ancestor.number >= head.number) {
+               // A lagging node's view of a chain we already followed further
+               return Ok(None);
+           }
+           let orphaned: Vec<ChainLink> = self
+               .seen
+               .iter()
+               .filter(|seen| ancestor.is_none_or(|ancestor| seen.number > ancestor.number))
+               .copied()
+               .collect();
+           self.seen.retain(|seen| ancestor.is_some_and(|ancestor| seen.number <= ancestor.number));
+           self.push(head);
+
+           if orphaned.is_empty() {
+               // A gap in the heads seen, not a fork
+               return Ok(None);
+           }
+           Ok(Some(ReorgEvent {
+               detected_at: Utc::now(),
+               common_ancestor: ancestor.map(|ancestor| ancestor.number),
+               orphaned,
+               new_head: head,
+               invalidated_opportunities: 0,
+               invalidated_snapshots: 0,
+           }))
+       }
+
+       fn push(&mut self, head: ChainLink) {
+           self.seen.push_back(head);
+           let oldest = head.number.saturating_sub(self.depth);
+           while self.seen.front().is_some_and(|seen| seen.number <= oldest) {
+               self.seen.pop_front();
+           }
+       }
+
+       // Newest block seen that the chain ending at `head` still contains
+       async fn common_ancestor(&self, head: ChainLink) -> Result<Option<ChainLink>> {
+           for seen in self.seen.iter().rev() {
+               let canonical = if seen.number == head.number {
+                   Some(head.hash)
+               } else if seen.number + 1 == head.number {
+                   Some(head.parent_hash)
+               } else {
+                   // Heights above the new head may be gone altogether
+                   self.client.get_block(seen.number).await?.and_then(|block| block.hash)
+               };
+               if canonical == Some(seen.hash) {
+                   return Ok(Some(*seen));
+               }
+           }
+
+           Ok(None)
+       }
+   }
\ No newline at end of file
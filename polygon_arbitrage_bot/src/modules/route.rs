@@ -0,0 +1,120 @@
+use anyhow::Result;
+   use crate::modules::gas_estimator::SwapLeg;
+   use crate::modules::price_fetcher::{BlockRef, PriceData};
+   use crate::modules::trade_sizing;
+   use serde::{Deserialize, Serialize};
+
+   /// One swap along an arbitrage route.
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   pub struct TradeHop {
+       pub from_token: String,
+       pub to_token: String,
+       pub dex_name: String,
+       pub rate: f64, // to_token received per from_token, after the swap fee
+       pub fee: f64,
+       pub amount_in: f64,
+       pub amount_out: f64,
+       // Share of the output lost to pool depth; None when the pool state is unknown
+       pub price_impact: Option<f64>,
+   }
+
+   /// A closed cycle of swaps that starts and ends in the same token.
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   pub struct Route {
+       pub start_token: String,
+       pub hops: Vec<TradeHop>,
+       pub start_amount: f64,
+       pub end_amount: f64,
+       pub profit_percentage: f64,
+       pub profit_usd: f64,
+       pub trade_size: f64,
+       pub timestamp: u64,
+       // Newest block among the hops, and how many blocks apart they were read
+       pub block: Option<BlockRef>,
+       pub block_span: u64,
+   }
+
+   impl Route {
+       /// Walks `trade_size` USD worth of `tokens[0]` through each quote in turn.
+       /// `quotes[i]` swaps `tokens[i]` into `tokens[i + 1]` (wrapping around).
+       /// Hops through pools with known state pay their price impact; others
+       /// trade at the quoted rate.
+       pub fn from_quotes(
+           tokens: &[&String],
+           quotes: &[(&PriceData, f64)],
+           trade_size: f64,
+           start_usd_price: f64,
+       ) -> Result<Self> {
+           let start_amount = trade_size / start_usd_price;
+           let mut amount = start_amount;
+           let mut hops = Vec::new();
+           for (i, (price_data, rate)) in quotes.iter().enumerate() {
+               let sell_base = price_data
+                   .token_pair
+                   .split_once('/')
+                   .is_some_and(|(base, _)| base == tokens[i].as_str());
+               let amount_out = trade_sizing::swap_output(price_data, sell_base, amount).unwrap_or(amount * rate);
+               hops.push(TradeHop {
+                   from_token: tokens[i].clone(),
+                   to_token: tokens[(i + 1) % tokens.len()].clone(),
+                   dex_name: price_data.dex_name.clone(),
+                   rate: *rate,
+                   fee: price_data.fee,
+                   amount_in: amount,
+                   amount_out,
+                   price_impact: trade_sizing::price_impact(price_data, sell_base, amount),
+               });
+               amount = amount_out;
+           }
+
+           let blocks: Vec<BlockRef> = quotes.iter().filter_map(|(price_data, _)| price_data.block).collect();
+           let block = blocks.iter().max_by_key(|block| block.number).copied();
+           let block_span = match (blocks.iter().map(|b| b.number).min(), block) {
+               (Some(oldest), Some(newest)) => newest.number - oldest,
+               _ => 0,
+           };
+
+           let profit_percentage = amount / start_amount - 1.0;
+           Ok(Route {
+               start_token: tokens[0].clone(),
+               hops,
+               start_amount,
+               end_amount: amount,
+               profit_percentage,
+               profit_usd: trade_size * profit_percentage,
+               trade_size,
+               timestamp: std::time::SystemTime::now()
+                   .duration_since(std::time::UNIX_EPOCH)?
+                   .as_secs(),
+               block,
+               block_span,
+           })
+       }
+
+       pub fn swap_legs(&self) -> Vec<SwapLeg> {
+           self.hops
+               .iter()
+               .map(|hop| SwapLeg {
+                   dex_name: hop.dex_name.clone(),
+                   token_in: hop.from_token.clone(),
+                   token_out: hop.to_token.clone(),
+                   amount_in: hop.amount_in,
+                   fee: hop.fee,
+                   price_impact: hop.price_impact,
+               })
+               .collect()
+       }
+
+       /// Profit at the quoted rates, before any price impact.
+       pub fn quoted_profit_usd(&self) -> f64 {
+           let rate: f64 = self.hops.iter().map(|hop| hop.rate).product();
+           self.trade_size * (rate - 1.0)
+       }
+
+       /// Route as "USDC -> WETH -> WMATIC -> USDC".
+       pub fn route(&self) -> String {
+           let mut tokens = vec![self.start_token.clone()];
+           tokens.extend(self.hops.iter().map(|hop| hop.to_token.clone()));
+           tokens.join(" -> ")
+       }
+   }
@@ -0,0 +1,20 @@
+use ethers::prelude::*;
+
+   // Swap entry points of the routers the bot prices against
+   abigen!(
+       IUniswapV2Router,
+       r#"[
+           function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external returns (uint256[] amounts)
+       ]"#
+   );
+
+   abigen!(
+       ISwapRouter,
+       r#"[
+           struct ExactInputSingleParams { address tokenIn; address tokenOut; uint24 fee; address recipient; uint256 deadline; uint256 amountIn; uint256 amountOutMinimum; uint160 sqrtPriceLimitX96; }
+           struct ExactInputParams { bytes path; address recipient; uint256 deadline; uint256 amountIn; uint256 amountOutMinimum; }
+           function exactInputSingle(ExactInputSingleParams params) external payable returns (uint256 amountOut)
+           function exactInput(ExactInputParams params) external payable returns (uint256 amountOut)
+           function multicall(bytes[] data) external payable returns (bytes[] results)
+       ]"#
+   );
\ No newline at end of file
@@ -0,0 +1,352 @@
+use anyhow::{Result, anyhow};
+   use async_trait::async_trait;
+   use ethers::providers::{Http, HttpClientError, JsonRpcClient, JsonRpcError, Provider};
+   use serde::de::DeserializeOwned;
+   use serde::Serialize;
+   use serde_json::Value;
+   use std::fmt::Debug;
+   use std::sync::{Arc, Mutex};
+   use std::time::{Duration, Instant};
+   use crate::config::{Config, RpcEndpoint};
+
+   /// A provider whose requests go through an `RpcPool`.
+   pub type RpcClient = Provider<RpcPool>;
+
+   // Reads that return the same answer from any node, so a failed one can be
+   // sent again elsewhere. Filter polling and transaction submission can't.
+   const IDEMPOTENT_METHODS: [&str; 19] = [
+       "eth_blockNumber",
+       "eth_call",
+       "eth_chainId",
+       "eth_estimateGas",
+       "eth_feeHistory",
+       "eth_gasPrice",
+       "eth_getBalance",
+       "eth_getBlockByHash",
+       "eth_getBlockByNumber",
+       "eth_getCode",
+       "eth_getLogs",
+       "eth_getStorageAt",
+       "eth_getTransactionByHash",
+       "eth_getTransactionCount",
+       "eth_getTransactionReceipt",
+       "eth_maxPriorityFeePerGas",
+       "eth_syncing",
+       "net_version",
+       "web3_clientVersion",
+   ];
+
+   // Weight of the newest sample in the smoothed latency and error rate
+   const LATENCY_SMOOTHING: f64 = 0.2;
+   const ERROR_SMOOTHING: f64 = 0.1;
+   // Latency at which an endpoint's share of the reads halves
+   const LATENCY_SCALE_MS: f64 = 100.0;
+   // An endpoint that fails is skipped for this long, doubling per consecutive failure
+   const BASE_COOLDOWN: Duration = Duration::from_millis(500);
+   const MAX_COOLDOWN: Duration = Duration::from_secs(30);
+
+   /// How an endpoint has been doing since the pool was built.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct EndpointHealth {
+       pub endpoint: String, // Host only: paths and queries often carry API keys
+       pub weight: f64,
+       pub max_rps: Option<f64>,
+       pub requests: u64,
+       pub failures: u64, // Transport errors and throttling; node-side errors like reverts don't count
+       pub error_rate: f64, // Smoothed share of recent requests that failed
+       pub latency_ms: Option<f64>, // Smoothed, over successful requests
+       pub healthy: bool, // False while cooling down after a failure
+   }
+
+   impl EndpointHealth {
+       pub fn summary(&self) -> String {
+           format!(
+               "{} {} {} requests, {} failed ({:.1}% recent), {}{}",
+               if self.healthy { "🟢" } else { "🔴" },
+               self.endpoint,
+               self.requests,
+               self.failures,
+               self.error_rate * 100.0,
+               self.latency_ms.map_or("no latency yet".to_string(), |latency| format!("{:.0}ms", latency)),
+               self.max_rps.map_or(String::new(), |rps| format!(", {} rps budget", rps)),
+           )
+       }
+   }
+
+   #[derive(Debug)]
+   struct Endpoint {
+       label: String,
+       http: Http,
+       weight: f64,
+       max_rps: Option<f64>,
+   }
+
+   #[derive(Debug)]
+   struct EndpointState {
+       budget: f64, // Requests that may be sent right now
+       refilled_at: Instant,
+       requests: u64,
+       failures: u64,
+       consecutive_failures: u32,
+       error_rate: f64,
+       latency_ms: Option<f64>,
+       cooldown_until: Option<Instant>,
+       current_weight: f64, // Smooth weighted round-robin position
+   }
+
+   impl EndpointState {
+       fn healthy(&self, now: Instant) -> bool {
+           self.cooldown_until.is_none_or(|until| until <= now)
+       }
+
+       fn score(&self, endpoint: &Endpoint) -> f64 {
+           let reliability = (1.0 - self.error_rate).max(0.05);
+           let speed = LATENCY_SCALE_MS / (LATENCY_SCALE_MS + self.latency_ms.unwrap_or_default());
+           endpoint.weight * reliability * speed
+       }
+
+       fn refill(&mut self, endpoint: &Endpoint, now: Instant) {
+           if let Some(rps) = endpoint.max_rps {
+               let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+               // A second's worth of requests may go out in a burst
+               self.budget = (self.budget + elapsed * rps).min(rps.max(1.0));
+           }
+           self.refilled_at = now;
+       }
+
+       // Time until the budget allows another request
+       fn wait(&self, endpoint: &Endpoint) -> Duration {
+           match endpoint.max_rps {
+               Some(rps) if self.budget < 1.0 => Duration::from_secs_f64((1.0 - self.budget) / rps),
+               _ => Duration::ZERO,
+           }
+       }
+   }
+
+   /// Spreads JSON-RPC requests over several HTTP endpoints in proportion to
+   /// their weights, scaled down by their recent error rate and latency.
+   /// Each endpoint's requests-per-second budget is respected, waiting when
+   /// every candidate is spent. Endpoints that fail or throttle are skipped
+   /// for a growing cooldown, and idempotent reads that fail are retried on
+   /// an endpoint not tried yet.
+   #[derive(Debug, Clone)]
+   pub struct RpcPool {
+       endpoints: Arc<Vec<Endpoint>>,
+       states: Arc<Mutex<Vec<EndpointState>>>,
+   }
+
+   impl RpcPool {
+       pub fn new(endpoints: &[RpcEndpoint]) -> Result<Self> {
+           if endpoints.is_empty() {
+               return Err(anyhow!("An RPC pool needs at least one endpoint"));
+           }
+
+           let now = Instant::now();
+           let mut pool = Vec::new();
+           let mut states = Vec::new();
+           for endpoint in endpoints {
+               let url = reqwest::Url::parse(&endpoint.url).map_err(|e| anyhow!("Invalid RPC URL {}: {}", endpoint.url, e))?;
+               let mut label = match (url.host_str(), url.port()) {
+                   (Some(host), Some(port)) => format!("{}:{}", host, port),
+                   (Some(host), None) => host.to_string(),
+                   (None, _) => endpoint.url.clone(),
+               };
+               // Keys in the path may be all that tells two endpoints apart
+               if pool.iter().any(|other: &Endpoint| other.label == label) {
+                   label = format!("{}#{}", label, pool.len() + 1);
+               }
+
+               pool.push(Endpoint {
+                   label,
+                   http: Http::new(url),
+                   weight: endpoint.weight,
+                   max_rps: endpoint.max_rps,
+               });
+               states.push(EndpointState {
+                   budget: endpoint.max_rps.map_or(0.0, |rps| rps.max(1.0)),
+                   refilled_at: now,
+                   requests: 0,
+                   failures: 0,
+                   consecutive_failures: 0,
+                   error_rate: 0.0,
+                   latency_ms: None,
+                   cooldown_until: None,
+                   current_weight: 0.0,
+               });
+           }
+
+           Ok(Self {
+               endpoints: Arc::new(pool),
+               states: Arc::new(Mutex::new(states)),
+           })
+       }
+
+       pub fn from_config(config: &Config) -> Result<Self> {
+           Self::new(&config.endpoints())
+       }
+
+       /// A pool of one unlimited endpoint.
+       pub fn single(url: &str) -> Result<Self> {
+           Self::new(&[RpcEndpoint {
+               url: url.to_string(),
+               weight: 1.0,
+               max_rps: None,
+           }])
+       }
+
+       pub fn health(&self) -> Vec<EndpointHealth> {
+           let now = Instant::now();
+           let states = self.states.lock().unwrap();
+           self.endpoints
+               .iter()
+               .zip(states.iter())
+               .map(|(endpoint, state)| EndpointHealth {
+                   endpoint: endpoint.label.clone(),
+                   weight: endpoint.weight,
+                   max_rps: endpoint.max_rps,
+                   requests: state.requests,
+                   failures: state.failures,
+                   error_rate: state.error_rate,
+                   latency_ms: state.latency_ms,
+                   healthy: state.healthy(now),
+               })
+               .collect()
+       }
+
+       // Waits for an untried endpoint with budget left and takes a request from it
+       async fn acquire(&self, tried: &[usize]) -> usize {
+           loop {
+               match self.pick(tried) {
+                   Ok(index) => return index,
+                   Err(wait) => tokio::time::sleep(wait).await,
+               }
+           }
+       }
+
+       // Healthy endpoints first; when all are cooling down the least bad is
+       // still better than failing outright
+       fn pick(&self, tried: &[usize]) -> std::result::Result<usize, Duration> {
+           let now = Instant::now();
+           let mut states = self.states.lock().unwrap();
+           for (endpoint, state) in self.endpoints.iter().zip(states.iter_mut()) {
+               state.refill(endpoint, now);
+           }
+
+           let untried: Vec<usize> = (0..self.endpoints.len()).filter(|index| !tried.contains(index)).collect();
+           let healthy: Vec<usize> = untried.iter().copied().filter(|&index| states[index].healthy(now)).collect();
+           let group = if healthy.is_empty() { untried } else { healthy };
+
+           let ready: Vec<usize> = group
+               .iter()
+               .copied()
+               .filter(|&index| states[index].wait(&self.endpoints[index]).is_zero())
+               .collect();
+           if ready.is_empty() {
+               let wait = group.iter().map(|&index| states[index].wait(&self.endpoints[index])).min();
+               return Err(wait.unwrap_or(Duration::from_millis(10)));
+           }
+
+           // Smooth weighted round-robin: even spread, proportional to scores
+           let mut total = 0.0;
+           let mut chosen = ready[0];
+           for &index in &ready {
+               let score = states[index].score(&self.endpoints[index]);
+               total += score;
+               states[index].current_weight += score;
+               if states[index].current_weight > states[chosen].current_weight {
+                   chosen = index;
+               }
+           }
+           let state = &mut states[chosen];
+           state.current_weight -= total;
+           if self.endpoints[chosen].max_rps.is_some() {
+               state.budget -= 1.0;
+           }
+
+           Ok(chosen)
+       }
+
+       fn record(&self, index: usize, latency: Duration, failed: bool) {
+           let mut states = self.states.lock().unwrap();
+           let state = &mut states[index];
+           state.requests += 1;
+           state.error_rate += ERROR_SMOOTHING * (if failed { 1.0 } else { 0.0 } - state.error_rate);
+
+           if failed {
+               state.failures += 1;
+               state.consecutive_failures += 1;
+               let cooldown = BASE_COOLDOWN * 2u32.pow(state.consecutive_failures.min(7) - 1);
+               state.cooldown_until = Some(Instant::now() + cooldown.min(MAX_COOLDOWN));
+           } else {
+               let latency_ms = latency.as_secs_f64() * 1000.0;
+               state.latency_ms = Some(match state.latency_ms {
+                   Some(smoothed) => smoothed + LATENCY_SMOOTHING * (latency_ms - smoothed),
+                   None => latency_ms,
+               });
+               state.consecutive_failures = 0;
+               state.cooldown_until = None;
+           }
+       }
+   }
+
+   #[async_trait]
+   impl JsonRpcClient for RpcPool {
+       type Error = HttpClientError;
+
+       async fn request<T, R>(&self, method: &str, params: T) -> std::result::Result<R, HttpClientError>
+       where
+           T: Debug + Serialize + Send + Sync,
+           R: DeserializeOwned + Send,
+       {
+           let params = serde_json::to_value(params).map_err(|err| HttpClientError::SerdeJson {
+               err,
+               text: String::new(),
+           })?;
+           let attempts = if is_idempotent(method) { self.endpoints.len() } else { 1 };
+
+           let mut tried = Vec::new();
+           loop {
+               let index = self.acquire(&tried).await;
+               tried.push(index);
+
+               let started = Instant::now();
+               let result: std::result::Result<Value, HttpClientError> =
+                   self.endpoints[index].http.request(method, &params).await;
+               let failed = result.as_ref().err().is_some_and(is_endpoint_failure);
+               self.record(index, started.elapsed(), failed);
+
+               match result {
+                   Ok(value) => {
+                       return serde_json::from_value(value.clone()).map_err(|err| HttpClientError::SerdeJson {
+                           err,
+                           text: value.to_string(),
+                       });
+                   }
+                   Err(_) if failed && tried.len() < attempts => continue,
+                   Err(e) => return Err(e),
+               }
+           }
+       }
+   }
+
+   pub fn is_idempotent(method: &str) -> bool {
+       IDEMPOTENT_METHODS.contains(&method)
+   }
+
+   // Whether an error says the endpoint, not the request, is at fault. Bodies
+   // that aren't JSON-RPC are usually gateway or rate-limit pages.
+   fn is_endpoint_failure(error: &HttpClientError) -> bool {
+       match error {
+           HttpClientError::ReqwestError(_) | HttpClientError::SerdeJson { .. } => true,
+           HttpClientError::JsonRpcError(error) => is_throttled(error),
+       }
+   }
+
+   fn is_throttled(error: &JsonRpcError) -> bool {
+       let message = error.message.to_ascii_lowercase();
+       error.code == 429
+           || error.code == -32005
+           || message.contains("rate limit")
+           || message.contains("too many requests")
+           || message.contains("limit exceeded")
+   }
\ No newline at end of file
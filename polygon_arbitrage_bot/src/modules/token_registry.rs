@@ -0,0 +1,220 @@
+use anyhow::{Result, anyhow};
+   use ethers::abi::AbiEncode;
+   use ethers::prelude::*;
+   use serde::{Deserialize, Serialize};
+   use std::collections::HashMap;
+   use std::sync::{Arc, Mutex};
+   use crate::config::TokenPair;
+   use crate::modules::multicall::{BatchResults, CallBatch};
+   use crate::modules::rpc_pool::RpcClient;
+
+   abigen!(
+       IERC20Metadata,
+       r#"[
+           function decimals() external view returns (uint8)
+           function symbol() external view returns (string)
+           function name() external view returns (string)
+       ]"#
+   );
+
+   /// ERC-20 metadata, read once per token.
+   #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+   pub struct TokenInfo {
+       pub address: Address,
+       pub symbol: String,
+       pub name: String,
+       pub decimals: u8,
+   }
+
+   /// Parses an address, rejecting mixed-case input whose EIP-55 checksum
+   /// doesn't match. All-lowercase or all-uppercase input carries no checksum.
+   pub fn parse_checksummed(address: &str) -> Result<Address> {
+       let parsed = address
+           .parse::<Address>()
+           .map_err(|e| anyhow!("Invalid address {}: {}", address, e))?;
+
+       let hex = address.trim_start_matches("0x");
+       let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+       if mixed_case {
+           let checksummed = ethers::utils::to_checksum(&parsed, None);
+           if checksummed != address {
+               return Err(anyhow!("Address {} has a bad checksum; expected {}", address, checksummed));
+           }
+       }
+
+       Ok(parsed)
+   }
+
+   /// Token metadata shared by every price source. Tokens are read from the
+   /// chain on first use and cached; `register_pairs` loads and checks the
+   /// configured ones up front.
+   pub struct TokenRegistry {
+       client: Arc<RpcClient>,
+       tokens: Mutex<HashMap<Address, TokenInfo>>,
+   }
+
+   impl TokenRegistry {
+       pub fn new(client: Arc<RpcClient>) -> Self {
+           Self {
+               client,
+               tokens: Mutex::new(HashMap::new()),
+           }
+       }
+
+       pub fn get(&self, token: Address) -> Option<TokenInfo> {
+           self.tokens.lock().unwrap().get(&token).cloned()
+       }
+
+       pub fn find(&self, symbol: &str) -> Option<TokenInfo> {
+           self.tokens
+               .lock()
+               .unwrap()
+               .values()
+               .find(|info| info.symbol == symbol)
+               .cloned()
+       }
+
+       /// Registers metadata without reading it, e.g. for tokens known offline.
+       pub fn insert(&self, info: TokenInfo) {
+           self.tokens.lock().unwrap().insert(info.address, info);
+       }
+
+       pub fn len(&self) -> usize {
+           self.tokens.lock().unwrap().len()
+       }
+
+       pub fn is_empty(&self) -> bool {
+           self.tokens.lock().unwrap().is_empty()
+       }
+
+       pub async fn load(&self, token: Address) -> Result<TokenInfo> {
+           if let Some(info) = self.get(token) {
+               return Ok(info);
+           }
+
+           let contract = IERC20Metadata::new(token, self.client.clone());
+           let decimals = contract.decimals();
+           let symbol = contract.symbol();
+           let name = contract.name();
+           let (decimals, symbol, name) = tokio::try_join!(decimals.call(), symbol.call(), name.call())
+               .map_err(|e| anyhow!("{:?} doesn't answer as an ERC-20 token: {}", token, e))?;
+
+           let info = TokenInfo {
+               address: token,
+               symbol,
+               name,
+               decimals,
+           };
+           self.insert(info.clone());
+
+           Ok(info)
+       }
+
+       pub async fn decimals(&self, token: Address) -> Result<u8> {
+           Ok(self.load(token).await?.decimals)
+       }
+
+       /// Returns the cached decimals, or queues the metadata reads for a
+       /// token seen for the first time.
+       pub fn queue(&self, token: Address, batch: &mut CallBatch) -> QueuedToken {
+           match self.get(token) {
+               Some(info) => QueuedToken::Known(info.decimals),
+               None => QueuedToken::Pending {
+                   token,
+                   decimals: batch.push(token, DecimalsCall.encode()),
+                   symbol: batch.push(token, SymbolCall.encode()),
+                   name: batch.push(token, NameCall.encode()),
+               },
+           }
+       }
+
+       /// Loads every token of `pairs`, checking each address's checksum and
+       /// that the token's on-chain symbol matches the configured one. All
+       /// problems are reported together.
+       pub async fn register_pairs(&self, pairs: &[TokenPair]) -> Result<()> {
+           let mut configured: Vec<(&str, &str)> = Vec::new();
+           for pair in pairs {
+               for token in [(pair.base.as_str(), pair.base_address.as_str()), (pair.quote.as_str(), pair.quote_address.as_str())] {
+                   if !configured.contains(&token) {
+                       configured.push(token);
+                   }
+               }
+           }
+
+           let mut errors = Vec::new();
+           let mut symbols: HashMap<&str, &str> = HashMap::new();
+           let mut addresses: HashMap<Address, &str> = HashMap::new();
+           for (symbol, address) in &configured {
+               if let Some(other) = symbols.insert(symbol, address) {
+                   errors.push(format!("{} is configured at both {} and {}", symbol, other, address));
+                   continue;
+               }
+
+               let token = match parse_checksummed(address) {
+                   Ok(token) => token,
+                   Err(e) => {
+                       errors.push(format!("{}: {}", symbol, e));
+                       continue;
+                   }
+               };
+               if let Some(other) = addresses.insert(token, symbol) {
+                   errors.push(format!("{:?} is configured as both {} and {}", token, other, symbol));
+                   continue;
+               }
+
+               match self.load(token).await {
+                   Ok(info) if !symbol_matches(&info.symbol, symbol) => errors.push(format!(
+                       "{}: {:?} is {} ({}) on chain",
+                       symbol, token, info.symbol, info.name
+                   )),
+                   Ok(_) => {}
+                   Err(e) => errors.push(format!("{}: {}", symbol, e)),
+               }
+           }
+
+           if errors.is_empty() {
+               Ok(())
+           } else {
+               Err(anyhow!("Invalid token configuration:\n  - {}", errors.join("\n  - ")))
+           }
+       }
+   }
+
+   // Bridged tokens may carry a suffix, like USDC.e for USDC
+   fn symbol_matches(on_chain: &str, configured: &str) -> bool {
+       on_chain.eq_ignore_ascii_case(configured)
+           || on_chain
+               .to_ascii_uppercase()
+               .starts_with(&format!("{}.", configured.to_ascii_uppercase()))
+   }
+
+   pub enum QueuedToken {
+       Known(u8),
+       Pending {
+           token: Address,
+           decimals: usize,
+           symbol: usize,
+           name: usize,
+       },
+   }
+
+   impl QueuedToken {
+       /// Decimals of the queued token, caching its metadata once read.
+       pub fn resolve(self, results: &BatchResults, registry: &TokenRegistry) -> Result<u8> {
+           match self {
+               QueuedToken::Known(value) => Ok(value),
+               QueuedToken::Pending { token, decimals, symbol, name } => {
+                   let DecimalsReturn(decimals) = results.decode(decimals)?;
+                   let SymbolReturn(symbol) = results.decode(symbol)?;
+                   let NameReturn(name) = results.decode(name)?;
+                   registry.insert(TokenInfo {
+                       address: token,
+                       symbol,
+                       name,
+                       decimals,
+                   });
+                   Ok(decimals)
+               }
+           }
+       }
+   }
\ No newline at end of file
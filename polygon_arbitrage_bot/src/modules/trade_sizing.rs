@@ -0,0 +1,258 @@
+use crate::modules::price_fetcher::{PriceData, Reserves};
+   use crate::modules::amounts::u256_to_f64;
+   use crate::modules::v3_swap::V3Liquidity;
+   use ethers::types::U256;
+   use serde::{Deserialize, Serialize};
+
+   // Points on the profit curve, as multiples of the optimal input
+   const CURVE_POINTS: [f64; 8] = [0.1, 0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+
+   const SEARCH_ITERATIONS: usize = 100;
+
+   /// Best size for buying base on one pool and selling it on another.
+   /// Amounts are in quote tokens.
+   #[derive(Debug, Clone, Serialize, Deserialize)]
+   pub struct TradeSizing {
+       pub input_amount: f64,
+       pub expected_output: f64,
+       pub profit: f64,
+       pub profit_curve: Vec<ProfitPoint>,
+   }
+
+   #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+   pub struct ProfitPoint {
+       pub input_amount: f64,
+       pub profit: f64,
+       // d(profit)/d(input): positive below the optimum, zero at it, negative above
+       pub marginal_profit: f64,
+   }
+
+   /// One x*y=k pool with its swap fee, oriented for a swap in one direction.
+   #[derive(Debug, Clone, Copy)]
+   struct Leg {
+       reserve_in: f64,
+       reserve_out: f64,
+       fee: f64,
+   }
+
+   /// Output of swapping `amount_in` through a constant-product pool.
+   pub fn constant_product_out(amount_in: f64, reserve_in: f64, reserve_out: f64, fee: f64) -> f64 {
+       let amount_in_with_fee = amount_in * (1.0 - fee);
+       amount_in_with_fee * reserve_out / (reserve_in + amount_in_with_fee)
+   }
+
+   /// Sizes a quote -> base -> quote round trip: buy base on `buy`, sell it on `sell`.
+   /// None when no input size makes a profit.
+   pub fn size_two_pool_trade(buy: &Reserves, buy_fee: f64, sell: &Reserves, sell_fee: f64) -> Option<TradeSizing> {
+       let buy = Leg { reserve_in: buy.quote.to_f64(), reserve_out: buy.base.to_f64(), fee: buy_fee };
+       let sell = Leg { reserve_in: sell.base.to_f64(), reserve_out: sell.quote.to_f64(), fee: sell_fee };
+       if [buy.reserve_in, buy.reserve_out, sell.reserve_in, sell.reserve_out]
+           .iter()
+           .any(|reserve| !reserve.is_finite() || *reserve <= 0.0)
+       {
+           return None;
+       }
+
+       let input_amount = optimal_input(&buy, &sell);
+       if input_amount <= 0.0 {
+           return None;
+       }
+
+       let expected_output = round_trip(&buy, &sell, input_amount);
+       let profit = expected_output - input_amount;
+       if profit <= 0.0 {
+           return None;
+       }
+
+       Some(TradeSizing {
+           input_amount,
+           expected_output,
+           profit,
+           profit_curve: profit_curve(
+               input_amount,
+               |input| round_trip(&buy, &sell, input) - input,
+               |input| marginal_output(&buy, &sell, input) - 1.0,
+           ),
+       })
+   }
+
+   /// Whether `price` carries enough pool state to size a trade against it.
+   pub fn has_pool_state(price: &PriceData) -> bool {
+       price.reserves.is_some() || price.v3_liquidity.as_ref().is_some_and(V3Liquidity::ticks_loaded)
+   }
+
+   /// Sizes buying base on `buy` and selling it on `sell`. V3 legs with loaded
+   /// ticks are walked tick by tick; pure x*y=k pairs use the closed form.
+   pub fn size_trade(buy: &PriceData, sell: &PriceData) -> Option<TradeSizing> {
+       // Virtual reserves match the spot price and in-range depth of either kind of pool
+       let estimate = size_two_pool_trade(&buy.reserves?, buy.fee, &sell.reserves?, sell.fee);
+       let walks_ticks = [buy, sell]
+           .iter()
+           .any(|price| price.v3_liquidity.as_ref().is_some_and(V3Liquidity::ticks_loaded));
+       if !walks_ticks {
+           return estimate;
+       }
+       // Profit is concave in size, so no profit at the margin means none at all
+       let estimate = estimate?;
+
+       let profit = |input: f64| {
+           swap_output(buy, false, input)
+               .and_then(|base| swap_output(sell, true, base))
+               .map_or(f64::NEG_INFINITY, |output| output - input)
+       };
+
+       // Bracket the optimum, then narrow it with a golden-section search
+       let mut high = estimate.input_amount * 2.0;
+       for _ in 0..32 {
+           if profit(high) <= profit(high / 2.0) {
+               break;
+           }
+           high *= 2.0;
+       }
+       let ratio = (5f64.sqrt() - 1.0) / 2.0;
+       let (mut low, mut high) = (0.0, high);
+       for _ in 0..SEARCH_ITERATIONS {
+           let left = high - ratio * (high - low);
+           let right = low + ratio * (high - low);
+           if profit(left) < profit(right) {
+               low = left;
+           } else {
+               high = right;
+           }
+       }
+
+       let input_amount = (low + high) / 2.0;
+       let best = profit(input_amount);
+       if best <= 0.0 || !best.is_finite() {
+           return None;
+       }
+
+       Some(TradeSizing {
+           input_amount,
+           expected_output: input_amount + best,
+           profit: best,
+           profit_curve: profit_curve(input_amount, profit, |input| {
+               let step = input * 1e-6;
+               (profit(input + step) - profit(input - step)) / (2.0 * step)
+           }),
+       })
+   }
+
+   /// Limits a sized trade to `max_input` quote tokens, repricing it at the
+   /// capped size. None when the capped trade no longer makes a profit.
+   pub fn cap_trade(buy: &PriceData, sell: &PriceData, sizing: TradeSizing, max_input: f64) -> Option<TradeSizing> {
+       if sizing.input_amount <= max_input {
+           return Some(sizing);
+       }
+
+       let base_out = swap_output(buy, false, max_input)?;
+       let expected_output = swap_output(sell, true, base_out)?;
+       let profit = expected_output - max_input;
+       if profit <= 0.0 || !profit.is_finite() {
+           return None;
+       }
+
+       Some(TradeSizing {
+           input_amount: max_input,
+           expected_output,
+           profit,
+           // The curve still shows where the uncapped optimum lies
+           profit_curve: sizing.profit_curve,
+       })
+   }
+
+   /// Output of swapping `amount_in` whole tokens through the pool behind
+   /// `price`: base for quote when `sell_base`, quote for base otherwise.
+   /// None when the quote carries no pool state.
+   pub fn swap_output(price: &PriceData, sell_base: bool, amount_in: f64) -> Option<f64> {
+       if let Some(pool) = price.v3_liquidity.as_ref().filter(|pool| pool.ticks_loaded()) {
+           return v3_swap_output(pool, sell_base, amount_in);
+       }
+
+       let reserves = price.reserves?;
+       let (reserve_in, reserve_out) = if sell_base {
+           (reserves.base.to_f64(), reserves.quote.to_f64())
+       } else {
+           (reserves.quote.to_f64(), reserves.base.to_f64())
+       };
+       Some(constant_product_out(amount_in, reserve_in, reserve_out, price.fee))
+   }
+
+   /// Fraction of the output lost to pool depth when swapping `amount_in`
+   /// through `price`, compared with filling it all at the quoted price after
+   /// fees. None when the quote carries no pool state.
+   pub fn price_impact(price: &PriceData, sell_base: bool, amount_in: f64) -> Option<f64> {
+       let output = swap_output(price, sell_base, amount_in)?;
+       let quoted = if sell_base {
+           amount_in * price.price_f64()
+       } else {
+           amount_in / price.price_f64()
+       } * (1.0 - price.fee);
+       if quoted <= 0.0 || !quoted.is_finite() || !output.is_finite() {
+           return None;
+       }
+       Some((1.0 - output / quoted).max(0.0))
+   }
+
+   fn v3_swap_output(pool: &V3Liquidity, sell_base: bool, amount_in: f64) -> Option<f64> {
+       let (decimals_in, decimals_out) = if sell_base {
+           (pool.base_decimals, pool.quote_decimals)
+       } else {
+           (pool.quote_decimals, pool.base_decimals)
+       };
+       let raw_in = amount_in * 10f64.powi(decimals_in as i32);
+       if !(0.0..u128::MAX as f64).contains(&raw_in) {
+           return None;
+       }
+
+       // Input beyond the loaded ticks is left unfilled and earns nothing
+       let result = pool
+           .simulate_swap(sell_base == pool.base_is_token0, U256::from(raw_in as u128))
+           .ok()?;
+       Some(u256_to_f64(result.amount_out) / 10f64.powi(decimals_out as i32))
+   }
+
+   fn profit_curve(input_amount: f64, profit: impl Fn(f64) -> f64, marginal_profit: impl Fn(f64) -> f64) -> Vec<ProfitPoint> {
+       CURVE_POINTS
+           .iter()
+           .map(|multiple| {
+               let input = input_amount * multiple;
+               ProfitPoint {
+                   input_amount: input,
+                   profit: profit(input),
+                   marginal_profit: marginal_profit(input),
+               }
+           })
+           .collect()
+   }
+
+   // Two chained constant-product swaps collapse into one virtual pool:
+   //   out = g * Eo * x / (Ei + g * x)
+   // with g = 1 - buy fee and
+   //   Ei = Ri_buy * Ri_sell / (Ri_sell + g_sell * Ro_buy)
+   //   Eo = g_sell * Ro_buy * Ro_sell / (Ri_sell + g_sell * Ro_buy)
+   fn virtual_pool(buy: &Leg, sell: &Leg) -> (f64, f64, f64) {
+       let gamma_buy = 1.0 - buy.fee;
+       let gamma_sell = 1.0 - sell.fee;
+       let denominator = sell.reserve_in + gamma_sell * buy.reserve_out;
+       let virtual_in = buy.reserve_in * sell.reserve_in / denominator;
+       let virtual_out = gamma_sell * buy.reserve_out * sell.reserve_out / denominator;
+       (gamma_buy, virtual_in, virtual_out)
+   }
+
+   // Setting d(out - x)/dx = 0 gives x* = (sqrt(g * Ei * Eo) - Ei) / g
+   fn optimal_input(buy: &Leg, sell: &Leg) -> f64 {
+       let (gamma, virtual_in, virtual_out) = virtual_pool(buy, sell);
+       ((gamma * virtual_in * virtual_out).sqrt() - virtual_in) / gamma
+   }
+
+   fn round_trip(buy: &Leg, sell: &Leg, amount_in: f64) -> f64 {
+       let base_out = constant_product_out(amount_in, buy.reserve_in, buy.reserve_out, buy.fee);
+       constant_product_out(base_out, sell.reserve_in, sell.reserve_out, sell.fee)
+   }
+
+   fn marginal_output(buy: &Leg, sell: &Leg, amount_in: f64) -> f64 {
+       let (gamma, virtual_in, virtual_out) = virtual_pool(buy, sell);
+       let denominator = virtual_in + gamma * amount_in;
+       gamma * virtual_out * virtual_in / (denominator * denominator)
+   }
@@ -0,0 +1,81 @@
+use crate::config::TokenPair;
+   use crate::modules::amounts::Decimal;
+   use crate::modules::price_fetcher::PriceData;
+   use crate::modules::route::Route;
+   use std::collections::BTreeSet;
+
+   /// A USDC -> X -> Y -> USDC style cycle across one or more DEXes.
+   pub type TriangularOpportunity = Route;
+
+   /// Every set of three tokens that is fully connected by the configured pairs.
+   pub fn find_triangles(token_pairs: &[TokenPair]) -> Vec<[String; 3]> {
+       let edges: BTreeSet<(String, String)> = token_pairs
+           .iter()
+           .flat_map(|pair| {
+               [
+                   (pair.base.clone(), pair.quote.clone()),
+                   (pair.quote.clone(), pair.base.clone()),
+               ]
+           })
+           .collect();
+       let tokens: Vec<String> = edges
+           .iter()
+           .map(|(token, _)| token.clone())
+           .collect::<BTreeSet<_>>()
+           .into_iter()
+           .collect();
+
+       let connected = |a: &String, b: &String| edges.contains(&(a.clone(), b.clone()));
+       let mut triangles = Vec::new();
+       for (i, a) in tokens.iter().enumerate() {
+           for (j, b) in tokens.iter().enumerate().skip(i + 1) {
+               for c in tokens.iter().skip(j + 1) {
+                   if connected(a, b) && connected(b, c) && connected(c, a) {
+                       triangles.push([a.clone(), b.clone(), c.clone()]);
+                   }
+               }
+           }
+       }
+
+       triangles
+   }
+
+   /// All quotes that can swap `from` into `to`, with their post-fee rate.
+   pub fn edge_quotes<'a>(prices: &'a [PriceData], from: &str, to: &str) -> Vec<(&'a PriceData, f64)> {
+       prices
+           .iter()
+           .filter(|price| price.price > Decimal::ZERO)
+           .filter_map(|price| {
+               let (base, quote) = price.token_pair.split_once('/')?;
+               if base == from && quote == to {
+                   Some((price, price.price_f64() * (1.0 - price.fee)))
+               } else if base == to && quote == from {
+                   Some((price, (1.0 / price.price_f64()) * (1.0 - price.fee)))
+               } else {
+                   None
+               }
+           })
+           .collect()
+   }
+
+   pub fn is_stablecoin(symbol: &str) -> bool {
+       matches!(symbol, "USDC" | "USDC.e" | "USDT" | "DAI")
+   }
+
+   /// USD value of one `token`, from a direct quote against a stablecoin.
+   pub fn usd_price(token: &str, prices: &[PriceData]) -> Option<f64> {
+       if is_stablecoin(token) {
+           return Some(1.0);
+       }
+
+       prices.iter().filter(|price| price.price > Decimal::ZERO).find_map(|price| {
+           let (base, quote) = price.token_pair.split_once('/')?;
+           if base == token && is_stablecoin(quote) {
+               Some(price.price_f64())
+           } else if quote == token && is_stablecoin(base) {
+               Some(1.0 / price.price_f64())
+           } else {
+               None
+           }
+       })
+   }
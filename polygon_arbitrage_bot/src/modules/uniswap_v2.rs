@@ -0,0 +1,294 @@
+use anyhow::{Result, anyhow};
+   use async_trait::async_trait;
+   use ethers::abi::AbiEncode;
+   use ethers::prelude::*;
+   use std::collections::HashMap;
+   use std::sync::{Arc, Mutex};
+   use crate::config::TokenPair;
+   use crate::modules::amounts::{self, Decimal, TokenAmount};
+   use crate::modules::multicall::{CallBatch, PendingQuote};
+   use crate::modules::pool_cache::{CachedState, PoolStateCache};
+   use crate::modules::price_fetcher::{self, BlockRef, PriceData, Reserves};
+   use crate::modules::price_source::{DexKind, PriceSource};
+   use crate::modules::rpc_pool::RpcClient;
+   use crate::modules::token_registry::{self, TokenRegistry};
+
+   abigen!(
+       IUniswapV2Factory,
+       r#"[
+           function getPair(address tokenA, address tokenB) external view returns (address pair)
+       ]"#
+   );
+
+   abigen!(
+       IUniswapV2Pair,
+       r#"[
+           function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)
+           function token0() external view returns (address)
+       ]"#
+   );
+
+   // Uniswap V2 and its forks charge 0.3% on the input amount
+   pub const SWAP_FEE: f64 = 0.003;
+
+   /// Reserves of a V2 pair, already ordered as base/quote for a `TokenPair`.
+   #[derive(Debug, Clone, Copy)]
+   pub struct PairReserves {
+       pub pair_address: Address,
+       pub reserve_base: U256,
+       pub reserve_quote: U256,
+   }
+
+   /// Pair address and token ordering; both are fixed once the pair exists.
+   #[derive(Debug, Clone, Copy)]
+   pub struct PairInfo {
+       pub pair_address: Address,
+       pub base_is_token0: bool,
+   }
+
+   impl PairInfo {
+       pub fn order_reserves(&self, reserve0: u128, reserve1: u128) -> PairReserves {
+           let (reserve_base, reserve_quote) = if self.base_is_token0 {
+               (U256::from(reserve0), U256::from(reserve1))
+           } else {
+               (U256::from(reserve1), U256::from(reserve0))
+           };
+
+           PairReserves {
+               pair_address: self.pair_address,
+               reserve_base,
+               reserve_quote,
+           }
+       }
+   }
+
+   /// Reads spot prices from Uniswap V2-style AMMs (SushiSwap, QuickSwap, ...).
+   pub struct UniswapV2Pricer {
+       client: Arc<RpcClient>,
+       factory: Address,
+       pairs: Mutex<HashMap<(Address, Address), PairInfo>>,
+   }
+
+   impl UniswapV2Pricer {
+       pub fn new(client: Arc<RpcClient>, factory_address: &str) -> Result<Self> {
+           let factory = factory_address
+               .parse::<Address>()
+               .map_err(|e| anyhow!("Invalid factory address {}: {}", factory_address, e))?;
+
+           Ok(Self {
+               client,
+               factory,
+               pairs: Mutex::new(HashMap::new()),
+           })
+       }
+
+       /// The pair if it was already looked up.
+       pub fn cached_pair_info(&self, base: Address, quote: Address) -> Option<PairInfo> {
+           self.pairs.lock().unwrap().get(&(base, quote)).copied()
+       }
+
+       /// Looks up the pair through the factory on first use, then from cache.
+       pub async fn pair_info(&self, base: Address, quote: Address) -> Result<PairInfo> {
+           if let Some(info) = self.cached_pair_info(base, quote) {
+               return Ok(info);
+           }
+
+           let factory = IUniswapV2Factory::new(self.factory, self.client.clone());
+           let pair_address = factory.get_pair(base, quote).call().await?;
+           if pair_address == Address::zero() {
+               return Err(anyhow!("No pair found for {:?}/{:?}", base, quote));
+           }
+
+           let token0 = IUniswapV2Pair::new(pair_address, self.client.clone())
+               .token_0()
+               .call()
+               .await?;
+           let info = PairInfo {
+               pair_address,
+               base_is_token0: token0 == base,
+           };
+           self.pairs.lock().unwrap().insert((base, quote), info);
+
+           Ok(info)
+       }
+
+       /// Reserves as of `block` (latest when None).
+       pub async fn fetch_reserves(&self, base: Address, quote: Address, block: Option<u64>) -> Result<PairReserves> {
+           let info = self.pair_info(base, quote).await?;
+           let pair = IUniswapV2Pair::new(info.pair_address, self.client.clone());
+           let mut call = pair.get_reserves();
+           if let Some(block) = block {
+               call = call.block(BlockId::Number(block.into()));
+           }
+           let (reserve0, reserve1, _) = call.call().await?;
+
+           Ok(info.order_reserves(reserve0, reserve1))
+       }
+   }
+
+   pub struct UniswapV2Source {
+       name: String,
+       pricer: UniswapV2Pricer,
+       tokens: Arc<TokenRegistry>,
+       fee: f64,
+       cache: Option<Arc<PoolStateCache>>,
+   }
+
+   impl UniswapV2Source {
+       pub fn new(
+           name: &str,
+           client: Arc<RpcClient>,
+           factory_address: &str,
+           tokens: Arc<TokenRegistry>,
+       ) -> Result<Self> {
+           Ok(Self {
+               name: name.to_string(),
+               pricer: UniswapV2Pricer::new(client, factory_address)?,
+               tokens,
+               fee: SWAP_FEE,
+               cache: None,
+           })
+       }
+
+       /// Overrides the 0.3% swap fee for forks that charge something else.
+       pub fn with_fee(mut self, fee: f64) -> Self {
+           self.fee = fee;
+           self
+       }
+
+       /// Seeds `cache` with every reserves read and quotes from it once synced.
+       pub fn with_pool_cache(mut self, cache: Arc<PoolStateCache>) -> Self {
+           self.cache = Some(cache);
+           self
+       }
+   }
+
+   #[async_trait]
+   impl PriceSource for UniswapV2Source {
+       fn name(&self) -> &str {
+           &self.name
+       }
+
+       fn kind(&self) -> DexKind {
+           DexKind::UniswapV2
+       }
+
+       fn swap_fee(&self) -> Option<f64> {
+           Some(self.fee)
+       }
+
+       async fn quote(&self, pair: &TokenPair) -> Result<Vec<PriceData>> {
+           let base = token_registry::parse_checksummed(&pair.base_address)?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address)?;
+
+           let reserves = self.pricer.fetch_reserves(base, quote, None).await?;
+           let base_decimals = self.tokens.decimals(base).await?;
+           let quote_decimals = self.tokens.decimals(quote).await?;
+
+           Ok(vec![reserves_to_price_data(&self.name, pair, &reserves, base_decimals, quote_decimals, self.fee, None)?])
+       }
+
+       async fn queue_reads(&self, pair: &TokenPair, batch: &mut CallBatch) -> Result<Option<PendingQuote>> {
+           let base = token_registry::parse_checksummed(&pair.base_address)?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address)?;
+           let info = self.pricer.pair_info(base, quote).await?;
+
+           let reserves_call = batch.push(info.pair_address, GetReservesCall.encode());
+           let base_decimals = self.tokens.queue(base, batch);
+           let quote_decimals = self.tokens.queue(quote, batch);
+
+           let name = self.name.clone();
+           let pair = pair.clone();
+           let tokens = self.tokens.clone();
+           let fee = self.fee;
+           let cache = self.cache.clone();
+
+           Ok(Some(Box::new(move |results| {
+               let GetReservesReturn { reserve_0, reserve_1, .. } = results.decode(reserves_call)?;
+               if let Some(cache) = cache {
+                   cache.seed(info.pair_address, CachedState::V2 { reserve0: reserve_0, reserve1: reserve_1 }, results.block);
+               }
+               let reserves = info.order_reserves(reserve_0, reserve_1);
+               let base_decimals = base_decimals.resolve(results, &tokens)?;
+               let quote_decimals = quote_decimals.resolve(results, &tokens)?;
+
+               Ok(vec![reserves_to_price_data(&name, &pair, &reserves, base_decimals, quote_decimals, fee, Some(results.block))?])
+           })))
+       }
+
+       fn cached_quote(&self, pair: &TokenPair, block: Option<BlockRef>) -> Option<Result<Vec<PriceData>>> {
+           let cache = self.cache.as_ref()?;
+           let base = token_registry::parse_checksummed(&pair.base_address).ok()?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address).ok()?;
+           let info = self.pricer.cached_pair_info(base, quote)?;
+           let (pool, head) = cache.snapshot(info.pair_address, block)?;
+           let CachedState::V2 { reserve0, reserve1 } = pool.state else {
+               return None;
+           };
+           let base_decimals = self.tokens.get(base)?.decimals;
+           let quote_decimals = self.tokens.get(quote)?.decimals;
+
+           let reserves = info.order_reserves(reserve0, reserve1);
+           Some(reserves_to_price_data(&self.name, pair, &reserves, base_decimals, quote_decimals, self.fee, Some(head)).map(|price| vec![price]))
+       }
+   }
+
+   fn reserves_to_price_data(
+       name: &str,
+       pair: &TokenPair,
+       reserves: &PairReserves,
+       base_decimals: u8,
+       quote_decimals: u8,
+       fee: f64,
+       block: Option<BlockRef>,
+   ) -> Result<PriceData> {
+       Ok(PriceData {
+           dex_name: name.to_string(),
+           token_pair: format!("{}/{}", pair.base, pair.quote),
+           price: reserves_to_price(reserves, base_decimals, quote_decimals)
+               .ok_or_else(|| anyhow!("Pool {:?} is empty", reserves.pair_address))?,
+           timestamp: price_fetcher::unix_timestamp()?,
+           liquidity: reserves_to_liquidity(reserves, quote_decimals)
+               .ok_or_else(|| anyhow!("Pool {:?} is too deep to price", reserves.pair_address))?,
+           fee,
+           block,
+           reserves: Some(reserves_to_amounts(reserves, base_decimals, quote_decimals)),
+           v3_liquidity: None,
+       })
+   }
+
+   /// Price of one base token in quote tokens, adjusted for token decimals.
+   /// None when either side of the pool is empty or the price is beyond a
+   /// Decimal's range.
+   pub fn reserves_to_price(reserves: &PairReserves, base_decimals: u8, quote_decimals: u8) -> Option<Decimal> {
+       amounts::ratio_u512(
+           U512::from(reserves.reserve_quote) * U512::exp10(base_decimals as usize),
+           U512::from(reserves.reserve_base) * U512::exp10(quote_decimals as usize),
+       )
+       .filter(|price| !price.is_zero())
+   }
+
+   pub fn reserves_to_amounts(reserves: &PairReserves, base_decimals: u8, quote_decimals: u8) -> Reserves {
+       Reserves {
+           base: TokenAmount::new(reserves.reserve_base, base_decimals),
+           quote: TokenAmount::new(reserves.reserve_quote, quote_decimals),
+       }
+   }
+
+   /// Pool depth expressed in quote tokens (both sides of the pool), None
+   /// when it is beyond a Decimal's range.
+   pub fn reserves_to_liquidity(reserves: &PairReserves, quote_decimals: u8) -> Option<Decimal> {
+       TokenAmount::new(reserves.reserve_quote, quote_decimals)
+           .to_decimal()?
+           .checked_mul(Decimal::TWO)
+   }
+
+   /// UniswapV2Library.getAmountOut, with the swap fee as a fraction of the
+   /// input. None when the pool is empty or the amounts overflow.
+   pub fn amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee: f64) -> Option<U256> {
+       let fee_denominator = U256::from(1_000_000u64);
+       let amount_in_with_fee = amount_in.checked_mul(fee_denominator - U256::from((fee * 1_000_000.0).round() as u64))?;
+       let numerator = amount_in_with_fee.checked_mul(reserve_out)?;
+       let denominator = reserve_in.checked_mul(fee_denominator)?.checked_add(amount_in_with_fee)?;
+       (!denominator.is_zero()).then(|| numerator / denominator)
+   }
\ No newline at end of file
@@ -0,0 +1,430 @@
+use anyhow::{Result, anyhow};
+   use async_trait::async_trait;
+   use ethers::abi::AbiEncode;
+   use ethers::prelude::*;
+   use std::collections::{BTreeMap, HashMap};
+   use std::sync::{Arc, Mutex};
+   use crate::config::TokenPair;
+   use crate::modules::amounts::{self, Decimal, TokenAmount};
+   use crate::modules::multicall::{CallBatch, PendingQuote};
+   use crate::modules::pool_cache::{CachedState, PoolStateCache};
+   use crate::modules::price_fetcher::{self, BlockRef, PriceData, Reserves};
+   use crate::modules::price_source::{DexKind, PriceSource};
+   use crate::modules::rpc_pool::RpcClient;
+   use crate::modules::token_registry::{self, TokenRegistry};
+   use crate::modules::v3_math;
+   use crate::modules::v3_swap::{self, V3Liquidity};
+
+   abigen!(
+       IUniswapV3Factory,
+       r#"[
+           function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool)
+       ]"#
+   );
+
+   abigen!(
+       IUniswapV3Pool,
+       r#"[
+           function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked)
+           function liquidity() external view returns (uint128)
+           function token0() external view returns (address)
+           function tickBitmap(int16 wordPosition) external view returns (uint256)
+           function ticks(int24 tick) external view returns (uint128 liquidityGross, int128 liquidityNet, uint256 feeGrowthOutside0X128, uint256 feeGrowthOutside1X128, int56 tickCumulativeOutside, uint160 secondsPerLiquidityOutsideX128, uint32 secondsOutside, bool initialized)
+       ]"#
+   );
+
+   // Fee tiers enabled on the Uniswap V3 factory, in hundredths of a basis point
+   pub const FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+   // tickBitmap words loaded on each side of the current one for swap simulation
+   const TICK_WORDS: i16 = 1;
+
+   /// A deployed pool for one fee tier; fixed once the pool exists.
+   #[derive(Debug, Clone, Copy)]
+   pub struct PoolInfo {
+       pub pool_address: Address,
+       pub fee: u32,
+       pub base_is_token0: bool,
+   }
+
+   impl PoolInfo {
+       pub fn with_state(&self, sqrt_price_x96: U256, tick: i32, liquidity: u128) -> PoolState {
+           PoolState {
+               pool_address: self.pool_address,
+               fee: self.fee,
+               sqrt_price_x96,
+               tick,
+               liquidity,
+               base_is_token0: self.base_is_token0,
+           }
+       }
+   }
+
+   #[derive(Debug, Clone, Copy)]
+   pub struct PoolState {
+       pub pool_address: Address,
+       pub fee: u32,
+       pub sqrt_price_x96: U256,
+       pub tick: i32,
+       pub liquidity: u128,
+       pub base_is_token0: bool,
+   }
+
+   /// Reads slot0/liquidity from every Uniswap V3 fee tier of a pair.
+   pub struct UniswapV3Pricer {
+       client: Arc<RpcClient>,
+       factory: Address,
+       pools: Mutex<HashMap<(Address, Address), Vec<PoolInfo>>>,
+   }
+
+   impl UniswapV3Pricer {
+       pub fn new(client: Arc<RpcClient>, factory_address: &str) -> Result<Self> {
+           let factory = factory_address
+               .parse::<Address>()
+               .map_err(|e| anyhow!("Invalid factory address {}: {}", factory_address, e))?;
+
+           Ok(Self {
+               client,
+               factory,
+               pools: Mutex::new(HashMap::new()),
+           })
+       }
+
+       /// The pools if they were already looked up.
+       pub fn cached_pool_infos(&self, base: Address, quote: Address) -> Option<Vec<PoolInfo>> {
+           self.pools.lock().unwrap().get(&(base, quote)).cloned()
+       }
+
+       /// Finds the deployed pool of every fee tier on first use, then from cache.
+       pub async fn pool_infos(&self, base: Address, quote: Address) -> Result<Vec<PoolInfo>> {
+           if let Some(infos) = self.cached_pool_infos(base, quote) {
+               return Ok(infos);
+           }
+
+           let factory = IUniswapV3Factory::new(self.factory, self.client.clone());
+           let mut infos = Vec::new();
+
+           for fee in FEE_TIERS {
+               let pool_address = factory.get_pool(base, quote, fee).call().await?;
+               if pool_address == Address::zero() {
+                   continue;
+               }
+
+               let token0 = IUniswapV3Pool::new(pool_address, self.client.clone())
+                   .token_0()
+                   .call()
+                   .await?;
+               infos.push(PoolInfo {
+                   pool_address,
+                   fee,
+                   base_is_token0: token0 == base,
+               });
+           }
+
+           if infos.is_empty() {
+               return Err(anyhow!("No V3 pools for {:?}/{:?}", base, quote));
+           }
+           self.pools.lock().unwrap().insert((base, quote), infos.clone());
+
+           Ok(infos)
+       }
+
+       pub async fn fetch_pools(&self, base: Address, quote: Address) -> Result<Vec<PoolState>> {
+           let mut pools = Vec::new();
+
+           for info in self.pool_infos(base, quote).await? {
+               let pool = IUniswapV3Pool::new(info.pool_address, self.client.clone());
+               let liquidity = pool.liquidity().call().await?;
+               let (sqrt_price_x96, tick, ..) = pool.slot_0().call().await?;
+               pools.push(info.with_state(sqrt_price_x96, tick, liquidity));
+           }
+
+           initialized_pools(pools, base, quote)
+       }
+   }
+
+   // Pools without in-range liquidity have no meaningful spot price
+   fn initialized_pools(pools: Vec<PoolState>, base: Address, quote: Address) -> Result<Vec<PoolState>> {
+       let pools: Vec<PoolState> = pools.into_iter().filter(|pool| pool.liquidity > 0).collect();
+       if pools.is_empty() {
+           return Err(anyhow!("No initialized V3 pools for {:?}/{:?}", base, quote));
+       }
+
+       Ok(pools)
+   }
+
+   pub struct UniswapV3Source {
+       name: String,
+       pricer: UniswapV3Pricer,
+       tokens: Arc<TokenRegistry>,
+       cache: Option<Arc<PoolStateCache>>,
+   }
+
+   impl UniswapV3Source {
+       pub fn new(
+           name: &str,
+           client: Arc<RpcClient>,
+           factory_address: &str,
+           tokens: Arc<TokenRegistry>,
+       ) -> Result<Self> {
+           Ok(Self {
+               name: name.to_string(),
+               pricer: UniswapV3Pricer::new(client, factory_address)?,
+               tokens,
+               cache: None,
+           })
+       }
+
+       /// Seeds `cache` with every pool and tick read and quotes from it once
+       /// synced.
+       pub fn with_pool_cache(mut self, cache: Arc<PoolStateCache>) -> Self {
+           self.cache = Some(cache);
+           self
+       }
+   }
+
+   #[async_trait]
+   impl PriceSource for UniswapV3Source {
+       fn name(&self) -> &str {
+           &self.name
+       }
+
+       fn kind(&self) -> DexKind {
+           DexKind::UniswapV3
+       }
+
+       async fn quote(&self, pair: &TokenPair) -> Result<Vec<PriceData>> {
+           let base = token_registry::parse_checksummed(&pair.base_address)?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address)?;
+
+           let pools = self.pricer.fetch_pools(base, quote).await?;
+           let base_decimals = self.tokens.decimals(base).await?;
+           let quote_decimals = self.tokens.decimals(quote).await?;
+
+           pools_to_prices(&self.name, pair, &pools, base_decimals, quote_decimals, None)
+       }
+
+       async fn queue_reads(&self, pair: &TokenPair, batch: &mut CallBatch) -> Result<Option<PendingQuote>> {
+           let base = token_registry::parse_checksummed(&pair.base_address)?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address)?;
+           let infos = self.pricer.pool_infos(base, quote).await?;
+
+           let state_calls: Vec<(PoolInfo, usize, usize)> = infos
+               .into_iter()
+               .map(|info| {
+                   let slot0_call = batch.push(info.pool_address, Slot0Call.encode());
+                   let liquidity_call = batch.push(info.pool_address, LiquidityCall.encode());
+                   (info, slot0_call, liquidity_call)
+               })
+               .collect();
+           let base_decimals = self.tokens.queue(base, batch);
+           let quote_decimals = self.tokens.queue(quote, batch);
+
+           let name = self.name.clone();
+           let pair = pair.clone();
+           let tokens = self.tokens.clone();
+           let cache = self.cache.clone();
+
+           Ok(Some(Box::new(move |results| {
+               let mut pools = Vec::new();
+               for (info, slot0_call, liquidity_call) in state_calls {
+                   let slot0: Slot0Return = results.decode(slot0_call)?;
+                   let LiquidityReturn(liquidity) = results.decode(liquidity_call)?;
+                   let pool = info.with_state(slot0.sqrt_price_x96, slot0.tick, liquidity);
+                   if let Some(cache) = &cache {
+                       cache.seed(pool.pool_address, cached_state(&pool), results.block);
+                   }
+                   pools.push(pool);
+               }
+               let pools = initialized_pools(pools, base, quote)?;
+               let base_decimals = base_decimals.resolve(results, &tokens)?;
+               let quote_decimals = quote_decimals.resolve(results, &tokens)?;
+
+               pools_to_prices(&name, &pair, &pools, base_decimals, quote_decimals, Some(results.block))
+           })))
+       }
+
+       async fn load_liquidity(&self, prices: &mut [&mut PriceData], block: BlockRef) -> Result<()> {
+           let pools: Vec<&mut V3Liquidity> = prices
+               .iter_mut()
+               .filter_map(|price| price.v3_liquidity.as_mut())
+               .filter(|pool| !pool.ticks_loaded())
+               .collect();
+           if pools.is_empty() {
+               return Ok(());
+           }
+
+           // Bitmap words around each pool's current tick...
+           let mut batch = CallBatch::new();
+           let word_calls: Vec<Vec<(i16, usize)>> = pools
+               .iter()
+               .map(|pool| {
+                   let center = v3_swap::word_position(pool.tick, pool.tick_spacing);
+                   (center - TICK_WORDS..=center + TICK_WORDS)
+                       .map(|word| (word, batch.push(pool.pool, TickBitmapCall { word_position: word }.encode())))
+                       .collect()
+               })
+               .collect();
+           let results = batch.execute(&self.pricer.client, block).await?;
+
+           // ...then liquidityNet of every initialized tick in them
+           let mut batch = CallBatch::new();
+           let mut tick_calls = Vec::new();
+           for (pool, words) in pools.iter().zip(&word_calls) {
+               let mut calls = Vec::new();
+               for &(word, index) in words {
+                   let TickBitmapReturn(bitmap) = results.decode(index)?;
+                   for bit in (0..256).filter(|bit| bitmap.bit(*bit)) {
+                       let tick = (word as i32 * 256 + bit as i32) * pool.tick_spacing;
+                       calls.push((tick, batch.push(pool.pool, TicksCall { tick }.encode())));
+                   }
+               }
+               tick_calls.push(calls);
+           }
+           let results = if batch.is_empty() {
+               None
+           } else {
+               Some(batch.execute(&self.pricer.client, block).await?)
+           };
+
+           for ((pool, words), calls) in pools.into_iter().zip(word_calls).zip(tick_calls) {
+               for (tick, index) in calls {
+                   let results = results.as_ref().ok_or_else(|| anyhow!("Tick reads were not executed"))?;
+                   let info: TicksReturn = results.decode(index)?;
+                   pool.ticks.insert(tick, info.liquidity_net);
+               }
+               let loaded_words = (words[0].0, words[words.len() - 1].0);
+               pool.loaded_words = Some(loaded_words);
+               if let Some(cache) = &self.cache {
+                   cache.seed_ticks(pool.pool, &pool.ticks, loaded_words, block);
+               }
+           }
+
+           Ok(())
+       }
+
+       fn cached_quote(&self, pair: &TokenPair, block: Option<BlockRef>) -> Option<Result<Vec<PriceData>>> {
+           let cache = self.cache.as_ref()?;
+           let base = token_registry::parse_checksummed(&pair.base_address).ok()?;
+           let quote = token_registry::parse_checksummed(&pair.quote_address).ok()?;
+           let base_decimals = self.tokens.get(base)?.decimals;
+           let quote_decimals = self.tokens.get(quote)?.decimals;
+
+           let mut head = None;
+           let mut pools = Vec::new();
+           let mut tick_liquidity = HashMap::new();
+           for info in self.pricer.cached_pool_infos(base, quote)? {
+               let (cached, at) = cache.snapshot(info.pool_address, block)?;
+               let CachedState::V3 { sqrt_price_x96, tick, liquidity, ticks, loaded_words, .. } = &cached.state else {
+                   return None;
+               };
+               // Pools that moved off their loaded ticks are read again
+               if *liquidity > 0 && !cached.ticks_current() {
+                   return None;
+               }
+               head = Some(at);
+               tick_liquidity.insert(info.pool_address, (ticks.clone(), *loaded_words));
+               pools.push(info.with_state(*sqrt_price_x96, *tick, *liquidity));
+           }
+
+           let prices = initialized_pools(pools, base, quote)
+               .and_then(|pools| pools_to_prices(&self.name, pair, &pools, base_decimals, quote_decimals, head))
+               .map(|mut prices| {
+                   for pool in prices.iter_mut().filter_map(|price| price.v3_liquidity.as_mut()) {
+                       if let Some((ticks, loaded_words)) = tick_liquidity.remove(&pool.pool) {
+                           pool.ticks = ticks;
+                           pool.loaded_words = loaded_words;
+                       }
+                   }
+                   prices
+               });
+           Some(prices)
+       }
+   }
+
+   fn cached_state(pool: &PoolState) -> CachedState {
+       CachedState::V3 {
+           sqrt_price_x96: pool.sqrt_price_x96,
+           tick: pool.tick,
+           liquidity: pool.liquidity,
+           tick_spacing: v3_swap::tick_spacing(pool.fee).unwrap_or(1),
+           ticks: BTreeMap::new(),
+           loaded_words: None,
+       }
+   }
+
+   fn pools_to_prices(
+       name: &str,
+       pair: &TokenPair,
+       pools: &[PoolState],
+       base_decimals: u8,
+       quote_decimals: u8,
+       block: Option<BlockRef>,
+   ) -> Result<Vec<PriceData>> {
+       let timestamp = price_fetcher::unix_timestamp()?;
+
+       // Each fee tier is its own market, so report it under its own name;
+       // pools too deep to express are skipped
+       Ok(pools
+           .iter()
+           .filter_map(|pool| Some(PriceData {
+               dex_name: format!("{}_{}", name, pool.fee),
+               token_pair: format!("{}/{}", pair.base, pair.quote),
+               price: pool_price(pool, base_decimals, quote_decimals),
+               timestamp,
+               liquidity: pool_liquidity(pool, quote_decimals)?,
+               fee: pool.fee as f64 / 1_000_000.0,
+               block,
+               reserves: Some(pool_virtual_reserves(pool, base_decimals, quote_decimals)),
+               v3_liquidity: v3_swap::tick_spacing(pool.fee).map(|tick_spacing| V3Liquidity {
+                   pool: pool.pool_address,
+                   fee: pool.fee,
+                   tick_spacing,
+                   sqrt_price_x96: pool.sqrt_price_x96,
+                   tick: pool.tick,
+                   liquidity: pool.liquidity,
+                   base_is_token0: pool.base_is_token0,
+                   base_decimals,
+                   quote_decimals,
+                   ticks: BTreeMap::new(),
+                   loaded_words: None,
+               }),
+           }))
+           .collect())
+   }
+
+   /// Price of one base token in quote tokens, adjusted for token decimals.
+   pub fn pool_price(pool: &PoolState, base_decimals: u8, quote_decimals: u8) -> Decimal {
+       // token1 per token0 in raw units is sqrtP^2 / 2^192
+       let sqrt_price = U512::from(pool.sqrt_price_x96);
+       let (token1, token0) = (sqrt_price * sqrt_price, U512::one() << 192);
+       let (quote, base) = if pool.base_is_token0 { (token1, token0) } else { (token0, token1) };
+
+       amounts::ratio_u512(
+           quote * U512::exp10(base_decimals as usize),
+           base * U512::exp10(quote_decimals as usize),
+       )
+       .unwrap_or_default()
+   }
+
+   /// Virtual depth at the current price, expressed in quote tokens (both
+   /// sides), None when it is beyond a Decimal's range.
+   pub fn pool_liquidity(pool: &PoolState, quote_decimals: u8) -> Option<Decimal> {
+       let reserves = pool_virtual_reserves(pool, 0, quote_decimals);
+       reserves.quote.to_decimal()?.checked_mul(Decimal::TWO)
+   }
+
+   /// Balances of the x*y=k pool that matches this pool at its current price:
+   /// x = L / sqrtP and y = L * sqrtP in raw token0/token1 units.
+   pub fn pool_virtual_reserves(pool: &PoolState, base_decimals: u8, quote_decimals: u8) -> Reserves {
+       let q96 = U256::one() << 96;
+       let liquidity = U256::from(pool.liquidity);
+       let token0 = v3_math::mul_div(liquidity, q96, pool.sqrt_price_x96).unwrap_or_default();
+       let token1 = v3_math::mul_div(liquidity, pool.sqrt_price_x96, q96).unwrap_or_default();
+
+       let (base, quote) = if pool.base_is_token0 { (token0, token1) } else { (token1, token0) };
+       Reserves {
+           base: TokenAmount::new(base, base_decimals),
+           quote: TokenAmount::new(quote, quote_decimals),
+       }
+   }
@@ -0,0 +1,244 @@
+use anyhow::{Result, anyhow};
+   use ethers::types::{U256, U512};
+
+   // Exact ports of Uniswap V3's TickMath, SqrtPriceMath and SwapMath libraries,
+   // so simulated swaps round the same way the pool contract does.
+
+   pub const MIN_TICK: i32 = -887272;
+   pub const MAX_TICK: i32 = 887272;
+
+   pub fn min_sqrt_ratio() -> U256 {
+       U256::from(4295128739u64)
+   }
+
+   pub fn max_sqrt_ratio() -> U256 {
+       U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap()
+   }
+
+   fn q96() -> U256 {
+       U256::one() << 96
+   }
+
+   // Multipliers for each set bit of |tick|, as 128.128 fixed point
+   const TICK_RATIOS: [(u32, u128); 19] = [
+       (0x2, 0xfff97272373d413259a46990580e213a),
+       (0x4, 0xfff2e50f5f656932ef12357cf3c7fdcc),
+       (0x8, 0xffe5caca7e10e4e61c3624eaa0941cd0),
+       (0x10, 0xffcb9843d60f6159c9db58835c926644),
+       (0x20, 0xff973b41fa98c081472e6896dfb254c0),
+       (0x40, 0xff2ea16466c96a3843ec78b326b52861),
+       (0x80, 0xfe5dee046a99a2a811c461f1969c3053),
+       (0x100, 0xfcbe86c7900a88aedcffc83b479aa3a4),
+       (0x200, 0xf987a7253ac413176f2b074cf7815e54),
+       (0x400, 0xf3392b0822b70005940c7a398e4b70f3),
+       (0x800, 0xe7159475a2c29b7443b29c7fa6e889d9),
+       (0x1000, 0xd097f3bdfd2022b8845ad8f792aa5825),
+       (0x2000, 0xa9f746462d870fdf8a65dc1f90e061e5),
+       (0x4000, 0x70d869a156d2a1b890bb3df62baf32f7),
+       (0x8000, 0x31be135f97d08fd981231505542fcfa6),
+       (0x10000, 0x9aa508b5b7a84e1c677de54f3e99bc9),
+       (0x20000, 0x5d6af8dedb81196699c329225ee604),
+       (0x40000, 0x2216e584f5fa1ea926041bedfe98),
+       (0x80000, 0x48a170391f7dc42444e8fa2),
+   ];
+
+   /// sqrt(1.0001^tick) as a Q64.96.
+   pub fn sqrt_ratio_at_tick(tick: i32) -> Result<U256> {
+       let abs_tick = tick.unsigned_abs();
+       if abs_tick > MAX_TICK as u32 {
+           return Err(anyhow!("Tick {} is out of range", tick));
+       }
+
+       let mut ratio = if abs_tick & 0x1 != 0 {
+           U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+       } else {
+           U256::one() << 128
+       };
+       for (bit, multiplier) in TICK_RATIOS {
+           if abs_tick & bit != 0 {
+               ratio = (ratio * U256::from(multiplier)) >> 128;
+           }
+       }
+       if tick > 0 {
+           ratio = U256::MAX / ratio;
+       }
+
+       // Round up so the result is always >= the true price
+       let remainder = ratio & U256::from(u32::MAX);
+       Ok((ratio >> 32) + if remainder.is_zero() { U256::zero() } else { U256::one() })
+   }
+
+   /// Greatest tick whose sqrt ratio is at or below `sqrt_price_x96`, the
+   /// inverse TickMath.getTickAtSqrtRatio computes.
+   pub fn tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32> {
+       if sqrt_price_x96 < min_sqrt_ratio() || sqrt_price_x96 >= max_sqrt_ratio() {
+           return Err(anyhow!("Sqrt price {} is out of range", sqrt_price_x96));
+       }
+
+       // sqrt_ratio_at_tick is monotonic, so bisect on it
+       let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+       while low < high {
+           let middle = low + (high - low + 1) / 2;
+           if sqrt_ratio_at_tick(middle)? <= sqrt_price_x96 {
+               low = middle;
+           } else {
+               high = middle - 1;
+           }
+       }
+       Ok(low)
+   }
+
+   pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256> {
+       if denominator.is_zero() {
+           return Err(anyhow!("mul_div by zero"));
+       }
+       let product = U512::from(a) * U512::from(b);
+       U256::try_from(product / U512::from(denominator)).map_err(|_| anyhow!("mul_div overflow"))
+   }
+
+   pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256> {
+       let result = mul_div(a, b, denominator)?;
+       let product = U512::from(a) * U512::from(b);
+       if (product % U512::from(denominator)).is_zero() {
+           Ok(result)
+       } else {
+           result.checked_add(U256::one()).ok_or_else(|| anyhow!("mul_div overflow"))
+       }
+   }
+
+   fn div_rounding_up(a: U256, b: U256) -> U256 {
+       let (quotient, remainder) = a.div_mod(b);
+       if remainder.is_zero() { quotient } else { quotient + U256::one() }
+   }
+
+   /// Price after adding `amount` of token0, rounded up.
+   fn next_sqrt_price_from_amount0(sqrt_price: U256, liquidity: u128, amount: U256) -> Result<U256> {
+       if amount.is_zero() {
+           return Ok(sqrt_price);
+       }
+       let numerator = U256::from(liquidity) << 96;
+
+       if let Some(product) = amount.checked_mul(sqrt_price) {
+           if let Some(denominator) = numerator.checked_add(product) {
+               return mul_div_rounding_up(numerator, sqrt_price, denominator);
+           }
+       }
+       Ok(div_rounding_up(numerator, numerator / sqrt_price + amount))
+   }
+
+   /// Price after adding `amount` of token1, rounded down.
+   fn next_sqrt_price_from_amount1(sqrt_price: U256, liquidity: u128, amount: U256) -> Result<U256> {
+       let quotient = if amount < (U256::one() << 160) {
+           (amount << 96) / U256::from(liquidity)
+       } else {
+           mul_div(amount, q96(), U256::from(liquidity))?
+       };
+       sqrt_price.checked_add(quotient).ok_or_else(|| anyhow!("sqrt price overflow"))
+   }
+
+   pub fn next_sqrt_price_from_input(sqrt_price: U256, liquidity: u128, amount_in: U256, zero_for_one: bool) -> Result<U256> {
+       if sqrt_price.is_zero() || liquidity == 0 {
+           return Err(anyhow!("Pool has no price or liquidity"));
+       }
+       if zero_for_one {
+           next_sqrt_price_from_amount0(sqrt_price, liquidity, amount_in)
+       } else {
+           next_sqrt_price_from_amount1(sqrt_price, liquidity, amount_in)
+       }
+   }
+
+   /// token0 needed to move between two prices with `liquidity` in range.
+   pub fn amount0_delta(a: U256, b: U256, liquidity: u128, round_up: bool) -> Result<U256> {
+       let (lower, upper) = if a < b { (a, b) } else { (b, a) };
+       if lower.is_zero() {
+           return Err(anyhow!("sqrt price is zero"));
+       }
+       let numerator1 = U256::from(liquidity) << 96;
+       let numerator2 = upper - lower;
+
+       if round_up {
+           Ok(div_rounding_up(mul_div_rounding_up(numerator1, numerator2, upper)?, lower))
+       } else {
+           Ok(mul_div(numerator1, numerator2, upper)? / lower)
+       }
+   }
+
+   /// token1 needed to move between two prices with `liquidity` in range.
+   pub fn amount1_delta(a: U256, b: U256, liquidity: u128, round_up: bool) -> Result<U256> {
+       let (lower, upper) = if a < b { (a, b) } else { (b, a) };
+       if round_up {
+           mul_div_rounding_up(U256::from(liquidity), upper - lower, q96())
+       } else {
+           mul_div(U256::from(liquidity), upper - lower, q96())
+       }
+   }
+
+   /// Result of swapping within a single initialized-tick range.
+   #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+   pub struct SwapStep {
+       pub sqrt_price_next: U256,
+       pub amount_in: U256,
+       pub amount_out: U256,
+       pub fee_amount: U256,
+   }
+
+   /// One exact-input swap step towards `sqrt_price_target`. `fee_pips` is in
+   /// hundredths of a basis point, like the pool's `fee()`.
+   pub fn compute_swap_step(
+       sqrt_price_current: U256,
+       sqrt_price_target: U256,
+       liquidity: u128,
+       amount_remaining: U256,
+       fee_pips: u32,
+   ) -> Result<SwapStep> {
+       let zero_for_one = sqrt_price_current >= sqrt_price_target;
+       let fee_complement = U256::from(1_000_000 - fee_pips);
+
+       let amount_remaining_less_fee = mul_div(amount_remaining, fee_complement, U256::from(1_000_000))?;
+       let amount_to_target = if zero_for_one {
+           amount0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+       } else {
+           amount1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)?
+       };
+
+       let sqrt_price_next = if amount_remaining_less_fee >= amount_to_target {
+           sqrt_price_target
+       } else {
+           next_sqrt_price_from_input(sqrt_price_current, liquidity, amount_remaining_less_fee, zero_for_one)?
+       };
+       let reached_target = sqrt_price_next == sqrt_price_target;
+
+       let (amount_in, amount_out) = if zero_for_one {
+           (
+               if reached_target {
+                   amount_to_target
+               } else {
+                   amount0_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?
+               },
+               amount1_delta(sqrt_price_next, sqrt_price_current, liquidity, false)?,
+           )
+       } else {
+           (
+               if reached_target {
+                   amount_to_target
+               } else {
+                   amount1_delta(sqrt_price_current, sqrt_price_next, liquidity, true)?
+               },
+               amount0_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?,
+           )
+       };
+
+       // Whatever input is left when the target isn't reached goes to the fee
+       let fee_amount = if !reached_target {
+           amount_remaining - amount_in
+       } else {
+           mul_div_rounding_up(amount_in, U256::from(fee_pips), fee_complement)?
+       };
+
+       Ok(SwapStep {
+           sqrt_price_next,
+           amount_in,
+           amount_out,
+           fee_amount,
+       })
+   }
@@ -0,0 +1,145 @@
+use anyhow::{Result, anyhow};
+   use ethers::types::{Address, U256};
+   use serde::{Deserialize, Serialize};
+   use std::collections::BTreeMap;
+   use crate::modules::v3_math::{self, MAX_TICK, MIN_TICK};
+
+   /// Everything needed to replay a swap against a V3 pool offline: the
+   /// current price and in-range liquidity plus the initialized ticks of the
+   /// tick-bitmap words that were loaded around the current tick.
+   #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+   pub struct V3Liquidity {
+       pub pool: Address,
+       pub fee: u32,
+       pub tick_spacing: i32,
+       pub sqrt_price_x96: U256,
+       pub tick: i32,
+       pub liquidity: u128,
+       pub base_is_token0: bool,
+       pub base_decimals: u8,
+       pub quote_decimals: u8,
+       // Initialized tick -> liquidityNet
+       pub ticks: BTreeMap<i32, i128>,
+       // Inclusive range of tickBitmap words in `ticks`; None until loaded
+       pub loaded_words: Option<(i16, i16)>,
+   }
+
+   #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+   pub struct SwapResult {
+       pub amount_in: U256, // Input actually consumed, fee included
+       pub amount_out: U256,
+       pub sqrt_price_x96: U256,
+       // Pool state after the swap
+       pub tick: i32,
+       pub liquidity: u128,
+       pub ticks_crossed: u32,
+       // False when the swap ran past the loaded ticks before using all input
+       pub filled: bool,
+   }
+
+   /// Tick spacing the Uniswap V3 factory enables for each fee tier.
+   pub fn tick_spacing(fee: u32) -> Option<i32> {
+       match fee {
+           100 => Some(1),
+           500 => Some(10),
+           3000 => Some(60),
+           10000 => Some(200),
+           _ => None,
+       }
+   }
+
+   /// tickBitmap word holding `tick`, for a given spacing.
+   pub fn word_position(tick: i32, tick_spacing: i32) -> i16 {
+       (tick.div_euclid(tick_spacing) >> 8) as i16
+   }
+
+   impl V3Liquidity {
+       pub fn ticks_loaded(&self) -> bool {
+           self.loaded_words.is_some()
+       }
+
+       /// Replays an exact-input swap tick by tick, as `UniswapV3Pool.swap` would.
+       pub fn simulate_swap(&self, zero_for_one: bool, amount_in: U256) -> Result<SwapResult> {
+           let Some((first_word, last_word)) = self.loaded_words else {
+               return Err(anyhow!("Ticks for pool {:?} are not loaded", self.pool));
+           };
+           let sqrt_price_limit = if zero_for_one {
+               v3_math::min_sqrt_ratio() + U256::one()
+           } else {
+               v3_math::max_sqrt_ratio() - U256::one()
+           };
+
+           let mut remaining = amount_in;
+           let mut amount_out = U256::zero();
+           let mut sqrt_price = self.sqrt_price_x96;
+           let mut tick = self.tick;
+           let mut liquidity = self.liquidity;
+           let mut ticks_crossed = 0;
+
+           while !remaining.is_zero() && sqrt_price != sqrt_price_limit {
+               let (tick_next, initialized, word) = self.next_tick_within_word(tick, zero_for_one);
+               if word < first_word || word > last_word {
+                   break;
+               }
+               let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+               let sqrt_price_next = v3_math::sqrt_ratio_at_tick(tick_next)?;
+               let target = if zero_for_one {
+                   sqrt_price_next.max(sqrt_price_limit)
+               } else {
+                   sqrt_price_next.min(sqrt_price_limit)
+               };
+
+               let step_start = sqrt_price;
+               let step = v3_math::compute_swap_step(sqrt_price, target, liquidity, remaining, self.fee)?;
+               sqrt_price = step.sqrt_price_next;
+               remaining -= step.amount_in + step.fee_amount;
+               amount_out += step.amount_out;
+
+               if sqrt_price == sqrt_price_next {
+                   if initialized {
+                       let net = self.ticks[&tick_next];
+                       let net = if zero_for_one { -net } else { net };
+                       liquidity = liquidity
+                           .checked_add_signed(net)
+                           .ok_or_else(|| anyhow!("Liquidity underflow crossing tick {}", tick_next))?;
+                       ticks_crossed += 1;
+                   }
+                   tick = if zero_for_one { tick_next - 1 } else { tick_next };
+               } else if sqrt_price != step_start {
+                   tick = v3_math::tick_at_sqrt_ratio(sqrt_price)?;
+               }
+           }
+
+           Ok(SwapResult {
+               amount_in: amount_in - remaining,
+               amount_out,
+               sqrt_price_x96: sqrt_price,
+               tick,
+               liquidity,
+               ticks_crossed,
+               filled: remaining.is_zero(),
+           })
+       }
+
+       // TickBitmap.nextInitializedTickWithinOneWord over the loaded ticks
+       fn next_tick_within_word(&self, tick: i32, lte: bool) -> (i32, bool, i16) {
+           let spacing = self.tick_spacing;
+           if lte {
+               let compressed = tick.div_euclid(spacing);
+               let word_start = (compressed - compressed.rem_euclid(256)) * spacing;
+               let word = (compressed >> 8) as i16;
+               match self.ticks.range(word_start..=compressed * spacing).next_back() {
+                   Some((&next, _)) => (next, true, word),
+                   None => (word_start, false, word),
+               }
+           } else {
+               let compressed = tick.div_euclid(spacing) + 1;
+               let word_end = (compressed + 255 - compressed.rem_euclid(256)) * spacing;
+               let word = (compressed >> 8) as i16;
+               match self.ticks.range(compressed * spacing..=word_end).next() {
+                   Some((&next, _)) => (next, true, word),
+                   None => (word_end, false, word),
+               }
+           }
+       }
+   }
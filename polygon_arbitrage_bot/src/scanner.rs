@@ -0,0 +1,339 @@
+use anyhow::{Result, anyhow};
+   use ethers::prelude::*;
+   use serde::Serialize;
+   use crate::config::{Config, TokenPair};
+   use crate::database::Database;
+   use crate::modules::arbitrage_detector::{ArbitrageDetector, ArbitrageOpportunity};
+   use crate::modules::gas_estimator::GasEstimator;
+   use crate::modules::gas_oracle::GasOracle;
+   use crate::modules::native_price::NativePriceOracle;
+   use crate::modules::pool_discovery::{DiscoveredMarkets, DiscoveredPool, PoolDiscovery};
+   use crate::modules::price_fetcher::{BlockRef, PriceData, PriceFetcher};
+   use crate::modules::price_source::PriceSourceRegistry;
+   use crate::modules::profit_calculator::{ProfitAnalysis, ProfitCalculator};
+   use crate::modules::reorg::{ChainLink, ReorgDetector, ReorgEvent};
+   use crate::modules::route::Route;
+   use tokio::sync::{broadcast, Mutex};
+
+   /// An opportunity with its cost breakdown and, once stored, its row id.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct AnalyzedOpportunity {
+       pub opportunity: ArbitrageOpportunity,
+       pub analysis: ProfitAnalysis,
+       pub stored_id: Option<i32>,
+   }
+
+   /// Everything one detection cycle found.
+   #[derive(Debug, Clone, Serialize)]
+   pub struct ScanReport {
+       pub block: Option<BlockRef>, // Newest block any price was read at
+       pub prices: usize,
+       pub opportunities: Vec<AnalyzedOpportunity>,
+       pub triangular: Vec<Route>,
+       pub multi_hop: Vec<Route>,
+       pub reorg: Option<ReorgEvent>, // Found when this cycle's head replaced earlier ones
+       pub discovered: Vec<DiscoveredPool>, // Pools discovery found this cycle
+   }
+
+   /// The price fetcher, detector and profit calculator wired up from a config.
+   pub struct Scanner {
+       config: Config,
+       price_fetcher: PriceFetcher,
+       detector: ArbitrageDetector,
+       profit_calculator: ProfitCalculator,
+       reorg_detector: Option<Mutex<ReorgDetector>>,
+       discovery: Option<Mutex<PoolDiscovery>>,
+       markets: std::sync::Mutex<DiscoveredMarkets>,
+   }
+
+   impl Scanner {
+       /// Builds every component and loads the configured tokens' metadata.
+       /// `verbose` prints each quote as it is read.
+       pub async fn from_config(config: &Config, verbose: bool) -> Result<Self> {
+           let price_fetcher = PriceFetcher::new(PriceSourceRegistry::from_config(config)?).with_verbose(verbose);
+           if let (Some(_), Some(tokens)) = (price_fetcher.registry().client(), price_fetcher.registry().tokens()) {
+               // Fail fast on a wrong address rather than on the first quote
+               tokens.register_pairs(&config.token_pairs).await?;
+           }
+
+           let detector = ArbitrageDetector::from_config(config);
+           let mut profit_calculator = ProfitCalculator::new().with_reserve_tolerance(config.reserve_tolerance);
+           let reorg_detector = price_fetcher
+               .registry()
+               .client()
+               .map(|client| Mutex::new(ReorgDetector::new(client).with_depth(config.reorg_depth)));
+           let discovery = match (config.discovery, price_fetcher.registry().client(), price_fetcher.registry().tokens()) {
+               (true, Some(client), Some(tokens)) => Some(Mutex::new(PoolDiscovery::from_config(client, tokens, config)?)),
+               _ => None,
+           };
+           if let (Some(client), Some(tokens)) = (price_fetcher.registry().client(), price_fetcher.registry().tokens()) {
+               profit_calculator = profit_calculator
+                   .with_gas_oracle(GasOracle::new(client.clone()).with_reward_percentile(config.priority_fee_percentile))
+                   .with_native_price_oracle(NativePriceOracle::from_config(client.clone(), config)?.with_token_registry(tokens.clone()))
+                   .with_gas_estimator(GasEstimator::from_config(client, config)?.with_token_registry(tokens));
+           }
+
+           Ok(Self {
+               config: config.clone(),
+               price_fetcher,
+               detector,
+               profit_calculator,
+               reorg_detector,
+               discovery,
+               markets: std::sync::Mutex::new(DiscoveredMarkets::new(&config.token_pairs)),
+           })
+       }
+
+       pub fn config(&self) -> &Config {
+           &self.config
+       }
+
+       pub fn price_fetcher(&self) -> &PriceFetcher {
+           &self.price_fetcher
+       }
+
+       /// Reorgs found by later cycles; None without an RPC client.
+       pub async fn subscribe_reorgs(&self) -> Option<broadcast::Receiver<ReorgEvent>> {
+           Some(self.reorg_detector.as_ref()?.lock().await.subscribe())
+       }
+
+       /// Runs one cycle: reads every price, detects opportunities and prices
+       /// their costs, storing them when a database is given.
+       pub async fn scan(&self, database: Option<&Database>) -> Result<ScanReport> {
+           self.scan_at(database, None).await
+       }
+
+       /// Like `scan`, with every pool read pinned to `block`.
+       pub async fn scan_at(&self, database: Option<&Database>, block: Option<BlockRef>) -> Result<ScanReport> {
+           let head = self.resolve_head(block).await?;
+           let reorg = match head {
+               Some((_, link)) => self.check_reorg(link, database).await,
+               None => None,
+           };
+           let block = head.map(|(block, _)| block).or(block);
+           let discovered = match head {
+               Some((head, _)) => self.discover_pools(head, database).await?,
+               None => Vec::new(),
+           };
+           if let Some(block) = block {
+               self.sync_pool_cache(block).await;
+           }
+
+           let token_pairs = self.token_pairs();
+           let prices = self.fetch_monitored(&token_pairs, block).await?;
+           if let Some(database) = database {
+               database.store_price_snapshots(&prices).await?;
+           }
+
+           let mut opportunities = Vec::new();
+           // Gas and MATIC are priced once per cycle, on its first opportunity
+           let mut costs = None;
+           for opportunity in self.detector.detect_opportunities(&prices)? {
+               let (gas_price, native_price) = match costs {
+                   Some(costs) => costs,
+                   None => *costs.insert((
+                       self.profit_calculator.gas_price(block.map(|b| b.number)).await?,
+                       self.profit_calculator.native_price(block).await?,
+                   )),
+               };
+               let analysis = match self.profit_calculator.calculate_profit_with(&opportunity, gas_price, native_price).await {
+                   Ok(analysis) => analysis,
+                   Err(e) => {
+                       eprintln!("⚠️  Skipping {} ({} → {}): {}", opportunity.token_pair, opportunity.buy_dex, opportunity.sell_dex, e);
+                       continue;
+                   }
+               };
+               let stored_id = match database {
+                   Some(database) => Some(database.store_opportunity(&opportunity, Some(&analysis)).await?),
+                   None => None,
+               };
+               opportunities.push(AnalyzedOpportunity {
+                   opportunity,
+                   analysis,
+                   stored_id,
+               });
+           }
+
+           let triangular = self.detector.detect_triangular_opportunities(&token_pairs, &prices)?;
+           let mut multi_hop = self.detector.detect_cycles(&prices, self.config.max_cycle_hops)?;
+           // 2- and 3-hop cycles are already reported above
+           multi_hop.retain(|route| route.hops.len() > 3);
+
+           Ok(ScanReport {
+               block: prices.iter().filter_map(|price| price.block).max_by_key(|block| block.number),
+               prices: prices.len(),
+               opportunities,
+               triangular,
+               multi_hop,
+               reorg,
+               discovered,
+           })
+       }
+
+       /// Every monitored quote at `block` (the latest when None), brought
+       /// forward through the pool cache when one is enabled.
+       pub async fn current_prices(&self, block: Option<BlockRef>) -> Result<Vec<PriceData>> {
+           if let Some(block) = block {
+               self.sync_pool_cache(block).await;
+           }
+           self.fetch_monitored(&self.token_pairs(), block).await
+       }
+
+       /// The configured pairs followed by those discovery added.
+       pub fn token_pairs(&self) -> Vec<TokenPair> {
+           let markets = self.markets.lock().unwrap();
+           self.config.token_pairs.iter().chain(markets.pairs()).cloned().collect()
+       }
+
+       // Quotes of `token_pairs` minus the markets of disabled or undiscovered pools
+       async fn fetch_monitored(&self, token_pairs: &[TokenPair], block: Option<BlockRef>) -> Result<Vec<PriceData>> {
+           let mut prices = self.price_fetcher.fetch_all_prices_at(token_pairs, block).await?;
+           if self.discovery.is_some() {
+               let markets = self.markets.lock().unwrap();
+               prices.retain(|price| markets.allows(price));
+           }
+           Ok(prices)
+       }
+
+       // Searches the factories up to `head` and adds what qualifies to the
+       // monitored markets. With a database the stored pools are reloaded, so
+       // pools toggled from the CLI take effect on the next cycle.
+       async fn discover_pools(&self, head: BlockRef, database: Option<&Database>) -> Result<Vec<DiscoveredPool>> {
+           let Some(discovery) = &self.discovery else {
+               return Ok(Vec::new());
+           };
+
+           let pools = match discovery.lock().await.scan_to(head).await {
+               Ok(report) => report.pools,
+               Err(e) => {
+                   eprintln!("⚠️  Pool discovery failed, retrying next cycle: {}", e);
+                   Vec::new()
+               }
+           };
+
+           match database {
+               Some(database) => {
+                   database.store_discovered_pools(&pools).await?;
+                   let stored = database.get_discovered_pools().await?;
+                   *self.markets.lock().unwrap() = DiscoveredMarkets::from_pools(&self.config.token_pairs, &stored);
+               }
+               None => {
+                   let mut markets = self.markets.lock().unwrap();
+                   for pool in &pools {
+                       markets.insert(pool);
+                   }
+               }
+           }
+
+           Ok(pools)
+       }
+
+       // The cycle's head (the latest block when None) with its parent link
+       async fn resolve_head(&self, block: Option<BlockRef>) -> Result<Option<(BlockRef, ChainLink)>> {
+           let Some(client) = self.price_fetcher.registry().client() else {
+               return Ok(None);
+           };
+           let header = match block {
+               Some(block) => client.get_block(block.hash).await?,
+               None => client.get_block(BlockNumber::Latest).await?,
+           }
+           .ok_or_else(|| anyhow!("RPC returned no head block"))?;
+
+           let block = match block {
+               Some(block) => block,
+               None => BlockRef::from_block(&header)?,
+           };
+           Ok(Some((block, ChainLink::from_block(&header)?)))
+       }
+
+       // Marks what was stored from orphaned blocks and tells subscribers
+       async fn check_reorg(&self, head: ChainLink, database: Option<&Database>) -> Option<ReorgEvent> {
+           let mut detector = self.reorg_detector.as_ref()?.lock().await;
+           let mut event = match detector.observe(head).await {
+               Ok(event) => event?,
+               Err(e) => {
+                   eprintln!("⚠️  Could not check block #{} for a reorg: {}", head.number, e);
+                   return None;
+               }
+           };
+
+           if let Some(database) = database {
+               match database.mark_reorged(&event.orphaned).await {
+                   Ok(rows) => {
+                       event.invalidated_opportunities = rows.opportunities;
+                       event.invalidated_snapshots = rows.price_snapshots;
+                   }
+                   Err(e) => eprintln!("⚠️  Could not mark reorged rows: {}", e),
+               }
+           }
+           detector.publish(&event);
+
+           Some(event)
+       }
+
+       // Brings the pool cache up to the cycle's head so quotes need no reads;
+       // the head is pinned so cached and freshly read quotes agree
+       async fn sync_pool_cache(&self, head: BlockRef) {
+           let registry = self.price_fetcher.registry();
+           let (Some(cache), Some(client)) = (registry.pool_cache(), registry.client()) else {
+               return;
+           };
+
+           match cache.sync_to(&client, head).await {
+               Ok(report) if report.reset => eprintln!("↩️  Reorg deeper than the pool cache history; re-reading pools"),
+               Ok(report) => {
+                   if let Some(rolled_back_to) = report.rolled_back_to {
+                       eprintln!("↩️  Reorg: pool cache rolled back to block #{}", rolled_back_to);
+                   }
+               }
+               Err(e) => {
+                   eprintln!("⚠️  Pool cache sync failed, re-reading pools: {}", e);
+                   cache.clear();
+               }
+           }
+       }
+
+       /// Human-readable summary of a cycle.
+       pub fn print_report(&self, report: &ScanReport) {
+           if let Some(reorg) = &report.reorg {
+               reorg.print();
+           }
+           for pool in &report.discovered {
+               println!(
+                   "🆕 Discovered pool {:?}: {} on {} ({} {} / {} {})",
+                   pool.pool,
+                   pool.token_pair(),
+                   pool.market(),
+                   pool.base_balance.round_dp(4),
+                   pool.pair.base,
+                   pool.quote_balance.round_dp(4),
+                   pool.pair.quote
+               );
+           }
+           if report.opportunities.is_empty() {
+               println!("❌ No opportunities found this cycle");
+           } else {
+               let opportunities: Vec<ArbitrageOpportunity> =
+                   report.opportunities.iter().map(|analyzed| analyzed.opportunity.clone()).collect();
+               self.detector.print_opportunities(&opportunities);
+               for id in report.opportunities.iter().filter_map(|analyzed| analyzed.stored_id) {
+                   println!("💾 Stored opportunity #{} in database", id);
+               }
+
+               // Printed as analysed and stored, without pricing it again
+               if let Some(best) = report.opportunities.first() {
+                   println!("\n🎯 Detailed Analysis of Best Opportunity:");
+                   self.profit_calculator.print_detailed_analysis(&best.opportunity, &best.analysis);
+               }
+           }
+
+           if !report.triangular.is_empty() {
+               println!();
+               self.detector.print_routes("Triangular", &report.triangular);
+           }
+           if !report.multi_hop.is_empty() {
+               println!();
+               self.detector.print_routes("Multi-hop", &report.multi_hop);
+           }
+       }
+   }
\ No newline at end of file
@@ -0,0 +1,88 @@
+use ethers::types::{Address, U256};
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal, TokenAmount};
+use polygon_arbitrage_bot::modules::uniswap_v2::{self, PairReserves};
+use polygon_arbitrage_bot::modules::uniswap_v3::{self, PoolState};
+use std::str::FromStr;
+
+#[test]
+fn ratio_is_exact_within_decimal_precision() {
+    assert_eq!(amounts::ratio(U256::from(1), U256::from(8)), Some(Decimal::new(125, 3)));
+    assert_eq!(amounts::ratio(U256::from(5), U256::zero()), None);
+
+    // 1/3 keeps all 28 digits Decimal can hold
+    let third = amounts::ratio(U256::from(1), U256::from(3)).unwrap();
+    assert_eq!(third, Decimal::from_str("0.3333333333333333333333333333").unwrap());
+
+    // Too large for a 96-bit mantissa
+    assert_eq!(amounts::ratio(U256::MAX, U256::one()), None);
+}
+
+#[test]
+fn token_amount_round_trips_through_its_raw_string() {
+    // More digits than an f64 can hold
+    let amount = TokenAmount::new(U256::from_dec_str("123456789012345678901234567").unwrap(), 18);
+
+    let stored = amount.raw.to_string();
+    assert_eq!(TokenAmount::from_raw_str(&stored, 18).unwrap(), amount);
+    assert_eq!(amount.to_decimal(), Some(Decimal::from_str("123456789.012345678901234567").unwrap()));
+    assert_eq!(amount.to_string(), "123456789.012345678901234567");
+
+    assert!(TokenAmount::from_raw_str("12.5", 18).is_err());
+    assert_eq!(TokenAmount::from_f64(1.5, 6), Some(TokenAmount::new(U256::from(1_500_000), 6)));
+    assert_eq!(TokenAmount::from_f64(-1.0, 6), None);
+}
+
+#[test]
+fn v2_price_is_exact_across_decimals() {
+    // 400 WETH (18 decimals) against 1,000,000.123456 USDC (6 decimals)
+    let reserves = PairReserves {
+        pair_address: Address::zero(),
+        reserve_base: U256::from(400u64) * U256::exp10(18),
+        reserve_quote: U256::from(1_000_000_123_456u64),
+    };
+
+    let price = uniswap_v2::reserves_to_price(&reserves, 18, 6);
+    assert_eq!(price, Some(Decimal::from_str("2500.00030864").unwrap()));
+
+    let amounts = uniswap_v2::reserves_to_amounts(&reserves, 18, 6);
+    assert_eq!(amounts.quote.to_decimal(), Some(Decimal::from_str("1000000.123456").unwrap()));
+    assert_eq!(amounts.base.decimals, 18);
+
+    // An empty pair has no price rather than a price of zero
+    let empty = PairReserves { reserve_base: U256::zero(), ..reserves };
+    assert_eq!(uniswap_v2::reserves_to_price(&empty, 18, 6), None);
+    let drained = PairReserves { reserve_quote: U256::zero(), ..reserves };
+    assert_eq!(uniswap_v2::reserves_to_price(&drained, 18, 6), None);
+}
+
+#[test]
+fn v3_price_is_exact_for_representable_prices() {
+    // sqrtP = 2^96 * 2 is a raw price of exactly 4 token1 per token0
+    let pool = PoolState {
+        pool_address: Address::zero(),
+        fee: 500,
+        sqrt_price_x96: U256::one() << 97,
+        tick: 13_863,
+        liquidity: 1_000_000,
+        base_is_token0: true,
+    };
+
+    assert_eq!(uniswap_v3::pool_price(&pool, 6, 6), Decimal::from(4));
+    // An 18-decimal base priced in a 6-decimal quote
+    assert_eq!(uniswap_v3::pool_price(&pool, 18, 6), Decimal::from(4_000_000_000_000u64));
+
+    let inverted = PoolState { base_is_token0: false, ..pool };
+    assert_eq!(uniswap_v3::pool_price(&inverted, 6, 6), Decimal::new(25, 2));
+}
+
+#[test]
+fn amounts_beyond_decimal_range_are_not_saturated() {
+    let reserves = PairReserves {
+        pair_address: Address::zero(),
+        reserve_base: U256::exp10(18),
+        reserve_quote: U256::MAX,
+    };
+
+    assert_eq!(TokenAmount::new(U256::MAX, 6).to_decimal(), None);
+    assert_eq!(uniswap_v2::reserves_to_liquidity(&reserves, 6), None);
+}
@@ -0,0 +1,118 @@
+use ethers::types::H256;
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageDetector;
+use polygon_arbitrage_bot::modules::price_fetcher::{BlockRef, PriceData};
+
+fn block(number: u64, hash_byte: u8) -> Option<BlockRef> {
+    Some(BlockRef {
+        number,
+        hash: H256::repeat_byte(hash_byte),
+        timestamp: 1_700_000_000 + number * 2,
+    })
+}
+
+fn quote(dex: &str, price: f64, block: Option<BlockRef>) -> PriceData {
+    quote_with_fee(dex, price, 0.003, block)
+}
+
+fn quote_with_fee(dex: &str, price: f64, fee: f64, block: Option<BlockRef>) -> PriceData {
+    PriceData {
+        dex_name: dex.to_string(),
+        token_pair: "WETH/USDC".to_string(),
+        price: amounts::decimal(price),
+        timestamp: 0,
+        liquidity: Decimal::from(1_000_000),
+        fee,
+        block,
+        reserves: None,
+        v3_liquidity: None,
+    }
+}
+
+#[test]
+fn pairs_prices_from_the_same_block() {
+    let detector = ArbitrageDetector::new(0.001);
+    let prices = vec![
+        quote("sushiswap", 2500.0, block(100, 1)),
+        quote("quickswap", 2550.0, block(100, 1)),
+    ];
+
+    let opportunities = detector.detect_opportunities(&prices).unwrap();
+
+    assert_eq!(opportunities.len(), 1);
+    let opp = &opportunities[0];
+    assert_eq!((opp.buy_dex.as_str(), opp.sell_dex.as_str()), ("sushiswap", "quickswap"));
+    assert_eq!(opp.block, block(100, 1));
+    assert_eq!(opp.block_span, 0);
+}
+
+#[test]
+fn refuses_prices_from_different_blocks_by_default() {
+    let detector = ArbitrageDetector::new(0.001);
+    let prices = vec![
+        quote("sushiswap", 2500.0, block(100, 1)),
+        quote("quickswap", 2550.0, block(101, 2)),
+    ];
+
+    assert!(detector.detect_opportunities(&prices).unwrap().is_empty());
+}
+
+#[test]
+fn refuses_same_height_blocks_with_different_hashes() {
+    let detector = ArbitrageDetector::new(0.001).with_max_block_lag(5);
+    let prices = vec![
+        quote("sushiswap", 2500.0, block(100, 1)),
+        quote("quickswap", 2550.0, block(100, 9)),
+    ];
+
+    assert!(detector.detect_opportunities(&prices).unwrap().is_empty());
+}
+
+#[test]
+fn block_lag_tolerance_allows_nearby_blocks() {
+    let detector = ArbitrageDetector::new(0.001).with_max_block_lag(2);
+    let prices = vec![
+        quote("sushiswap", 2500.0, block(100, 1)),
+        quote("quickswap", 2550.0, block(102, 2)),
+        quote("uniswap_v3_500", 2700.0, block(110, 3)),
+    ];
+
+    let opportunities = detector.detect_opportunities(&prices).unwrap();
+
+    // The 2700 quote is too far from the others to pair with them
+    assert_eq!(opportunities.len(), 1);
+    assert_eq!(opportunities[0].sell_dex, "quickswap");
+    assert_eq!(opportunities[0].block, block(102, 2));
+    assert_eq!(opportunities[0].block_span, 2);
+}
+
+#[test]
+fn fees_eat_a_thin_spread() {
+    let detector = ArbitrageDetector::new(0.001);
+    // 0.4% apart, but two 0.3% fees cost about 0.6%
+    let prices = vec![
+        quote("sushiswap", 2500.0, None),
+        quote("quickswap", 2510.0, None),
+    ];
+
+    assert!(detector.detect_opportunities(&prices).unwrap().is_empty());
+}
+
+#[test]
+fn reports_raw_and_after_fee_spreads() {
+    let detector = ArbitrageDetector::new(0.001);
+    let prices = vec![
+        quote_with_fee("sushiswap", 2500.0, 0.003, None),
+        quote_with_fee("uniswap_v3_500", 2550.0, 0.0005, None),
+    ];
+
+    let opportunities = detector.detect_opportunities(&prices).unwrap();
+
+    assert_eq!(opportunities.len(), 1);
+    let opp = &opportunities[0];
+    assert_eq!(opp.raw_spread, Decimal::new(2, 2));
+    assert_eq!((opp.buy_fee, opp.sell_fee), (0.003, 0.0005));
+    let expected = (2550.0 * 0.9995 - 2500.0 / 0.997) / (2500.0 / 0.997);
+    assert!((amounts::float(opp.profit_percentage) - expected).abs() < 1e-12);
+    assert!(opp.profit_percentage < opp.raw_spread);
+}
@@ -0,0 +1,97 @@
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use ethers::types::U256;
+use polygon_arbitrage_bot::cli::{self, Cli, Command, OutputFormat};
+use polygon_arbitrage_bot::database::StoredOpportunity;
+use polygon_arbitrage_bot::modules::amounts::{Decimal, TokenAmount};
+use std::path::Path;
+
+fn stored(id: i32, token_pair: &str) -> StoredOpportunity {
+    StoredOpportunity {
+        id,
+        token_pair: token_pair.to_string(),
+        buy_dex: "quickswap".to_string(),
+        sell_dex: "sushiswap".to_string(),
+        buy_price: Decimal::new(250012, 2),
+        sell_price: Decimal::new(251500, 2),
+        profit_percentage: Decimal::new(4, 3),
+        raw_spread: Some(Decimal::new(6, 3)),
+        profit_usd: Decimal::new(400, 2),
+        trade_size: Decimal::from(1000),
+        amount_in: Some(TokenAmount::new(U256::from(1_000_000_000u64), 6)),
+        net_profit: None,
+        gas_costs: Some(Decimal::new(2, 2)),
+        block_number: Some(50_000_000),
+        block_hash: None,
+        block_timestamp: None,
+        native_price_usd: None,
+        native_price_source: None,
+        reorged: false,
+        created_at: Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap(),
+    }
+}
+
+#[test]
+fn parses_global_flags_after_the_subcommand() {
+    let cli = Cli::try_parse_from([
+        "bot", "history", "--pair", "WETH/USDC", "--min-profit", "0.5", "--format", "json", "--config", "ci.toml",
+    ])
+    .unwrap();
+
+    assert_eq!(cli.format, OutputFormat::Json);
+    assert_eq!(cli.config.as_deref(), Some(Path::new("ci.toml")));
+    let Command::History { filter } = cli.command else {
+        panic!("expected history");
+    };
+    let filter = filter.to_filter(Some(20));
+    assert_eq!(filter.token_pair.as_deref(), Some("WETH/USDC"));
+    // Percent on the command line, a fraction in the database
+    assert_eq!(filter.min_profit, Some(Decimal::new(5, 3)));
+    assert_eq!(filter.limit, Some(20));
+    assert!(filter.since.is_none());
+}
+
+#[test]
+fn watch_defaults_and_rejects_unknown_formats() {
+    let cli = Cli::try_parse_from(["bot", "--database-url", "mysql://localhost/bot", "watch"]).unwrap();
+    assert_eq!(cli.format, OutputFormat::Text);
+    assert_eq!(cli.database_url.as_deref(), Some("mysql://localhost/bot"));
+    assert!(matches!(cli.command, Command::Watch { interval: None, on_block: false, cycles: None }));
+
+    let cli = Cli::try_parse_from(["bot", "daemon", "--on-block", "--cycles", "3"]).unwrap();
+    assert!(matches!(cli.command, Command::Watch { interval: None, on_block: true, cycles: Some(3) }));
+    assert!(Cli::try_parse_from(["bot", "watch", "--on-block", "--interval", "10"]).is_err());
+    assert!(Cli::try_parse_from(["bot", "watch", "--interval", "0"]).is_err());
+
+    assert!(Cli::try_parse_from(["bot", "--format", "xml", "scan"]).is_err());
+    assert!(Cli::try_parse_from(["bot"]).is_err());
+}
+
+#[test]
+fn exports_opportunities_as_csv() {
+    let mut out = Vec::new();
+    cli::write_opportunities(&mut out, OutputFormat::Csv, &[stored(7, "WETH/USDC"), stored(8, "A,B")]).unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,created_at,token_pair,"), "{}", lines[0]);
+    assert_eq!(
+        lines[1],
+        "7,2026-01-02T03:04:05+00:00,WETH/USDC,quickswap,sushiswap,2500.12,2515.00,0.006,0.004,4.00,1000,1000.000000,,0.02,50000000,,,false"
+    );
+    // Separators inside a field are quoted
+    assert!(lines[2].starts_with("8,2026-01-02T03:04:05+00:00,\"A,B\",quickswap"), "{}", lines[2]);
+}
+
+#[test]
+fn exports_opportunities_as_json() {
+    let mut out = Vec::new();
+    cli::write_opportunities(&mut out, OutputFormat::Json, &[stored(7, "WETH/USDC")]).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(json[0]["id"], 7);
+    assert_eq!(json[0]["token_pair"], "WETH/USDC");
+    assert_eq!(json[0]["profit_percentage"], "0.004");
+    assert_eq!(json[0]["amount_in"]["decimals"], 6);
+}
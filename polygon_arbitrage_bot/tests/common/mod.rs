@@ -0,0 +1,316 @@
+#![allow(dead_code)]
+
+use ethers::abi::{self, ParamType, Token};
+use ethers::types::{Address, Bytes, H256, U256};
+use polygon_arbitrage_bot::config::TokenPair;
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::price_fetcher::PriceData;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub mod ws;
+
+pub const WETH: &str = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619";
+pub const USDC: &str = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174";
+
+type Handler = Arc<dyn Fn(&str, &Value) -> Option<Value> + Send + Sync>;
+type CallTable = HashMap<(Address, [u8; 4]), Bytes>;
+type ExactCallTable = HashMap<(Address, Bytes), Bytes>;
+
+/// Minimal JSON-RPC server answering canned responses over HTTP.
+///
+/// `eth_call` requests are matched on `(to, calldata)` first, then on
+/// `(to, 4-byte selector)`. Multicall3 `aggregate3` calls are unpacked and
+/// each inner call is answered the same way. Other methods
+/// are answered from `on_method` results or the fallback handler.
+#[derive(Clone, Default)]
+pub struct MockRpc {
+    calls: Arc<Mutex<CallTable>>,
+    exact_calls: Arc<Mutex<ExactCallTable>>,
+    methods: Arc<Mutex<HashMap<String, Value>>>,
+    fallback: Arc<Mutex<Option<Handler>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    call_blocks: Arc<Mutex<Vec<Value>>>,
+}
+
+impl MockRpc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_call(&self, to: Address, signature: &str, output: Vec<Token>) -> &Self {
+        let selector = ethers::utils::id(signature);
+        self.calls
+            .lock()
+            .unwrap()
+            .insert((to, selector), Bytes::from(abi::encode(&output)));
+        self
+    }
+
+    /// Like `on_call`, but only matches when the call arguments are equal too.
+    pub fn on_call_with(
+        &self,
+        to: Address,
+        signature: &str,
+        args: Vec<Token>,
+        output: Vec<Token>,
+    ) -> &Self {
+        let mut calldata = ethers::utils::id(signature).to_vec();
+        calldata.extend(abi::encode(&args));
+        self.exact_calls.lock().unwrap().insert(
+            (to, Bytes::from(calldata)),
+            Bytes::from(abi::encode(&output)),
+        );
+        self
+    }
+
+    /// Answers an ERC-20's metadata reads; the name is derived from the symbol.
+    pub fn on_token(&self, to: Address, symbol: &str, decimals: u8) -> &Self {
+        self.on_call(to, "decimals()", vec![uint(decimals as u128)])
+            .on_call(to, "symbol()", vec![Token::String(symbol.to_string())])
+            .on_call(to, "name()", vec![Token::String(format!("{} Token", symbol))])
+    }
+
+    pub fn on_method(&self, method: &str, result: Value) -> &Self {
+        self.methods
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), result);
+        self
+    }
+
+    pub fn fallback<F>(&self, handler: F) -> &Self
+    where
+        F: Fn(&str, &Value) -> Option<Value> + Send + Sync + 'static,
+    {
+        *self.fallback.lock().unwrap() = Some(Arc::new(handler));
+        self
+    }
+
+    /// Methods received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Block tag of every `eth_call` received so far, in order.
+    pub fn call_blocks(&self) -> Vec<Value> {
+        self.call_blocks.lock().unwrap().clone()
+    }
+
+    pub fn count(&self, method: &str) -> usize {
+        self.requests().iter().filter(|m| m.as_str() == method).count()
+    }
+
+    pub async fn serve(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let mock = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    break;
+                };
+                let mock = mock.clone();
+                tokio::spawn(async move {
+                    let _ = mock.handle_connection(stream).await;
+                });
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    async fn handle_connection(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            let header_end = loop {
+                if let Some(pos) = find(&buffer, b"\r\n\r\n") {
+                    break pos + 4;
+                }
+                let n = stream.read(&mut chunk).await?;
+                if n == 0 {
+                    return Ok(());
+                }
+                buffer.extend_from_slice(&chunk[..n]);
+            };
+
+            let headers = String::from_utf8_lossy(&buffer[..header_end]).to_lowercase();
+            let content_length = headers
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length:"))
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+
+            while buffer.len() < header_end + content_length {
+                let n = stream.read(&mut chunk).await?;
+                if n == 0 {
+                    return Ok(());
+                }
+                buffer.extend_from_slice(&chunk[..n]);
+            }
+
+            let body: Value =
+                serde_json::from_slice(&buffer[header_end..header_end + content_length])
+                    .unwrap_or(Value::Null);
+            buffer.drain(..header_end + content_length);
+
+            let response = match body {
+                Value::Array(requests) => {
+                    Value::Array(requests.iter().map(|r| self.respond(r)).collect())
+                }
+                request => self.respond(&request),
+            };
+
+            let payload = response.to_string();
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                payload.len(),
+                payload
+            );
+            stream.write_all(reply.as_bytes()).await?;
+        }
+    }
+
+    fn respond(&self, request: &Value) -> Value {
+        let id = request["id"].clone();
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let params = request["params"].clone();
+        self.requests.lock().unwrap().push(method.clone());
+
+        match self.dispatch(&method, &params) {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32000, "message": format!("no mock for {}", method) }
+            }),
+        }
+    }
+
+    fn dispatch(&self, method: &str, params: &Value) -> Option<Value> {
+        if method == "eth_call" {
+            let tx = &params[0];
+            self.call_blocks.lock().unwrap().push(params[1].clone());
+            let to: Address = tx["to"].as_str()?.parse().ok()?;
+            let data = tx["input"].as_str().or_else(|| tx["data"].as_str())?;
+            let data: Bytes = data.parse().ok()?;
+            if let Some(output) = self.eth_call(to, &data) {
+                return Some(json!(output));
+            }
+        }
+
+        if let Some(result) = self.methods.lock().unwrap().get(method) {
+            return Some(result.clone());
+        }
+
+        let fallback = self.fallback.lock().unwrap().clone();
+        fallback.and_then(|handler| handler(method, params))
+    }
+
+    fn eth_call(&self, to: Address, data: &Bytes) -> Option<Bytes> {
+        if data.len() < 4 {
+            return None;
+        }
+        if to == address(MULTICALL3) && data[..4] == ethers::utils::id(AGGREGATE3) {
+            return self.aggregate3(&data[4..]);
+        }
+        if let Some(output) = self.exact_calls.lock().unwrap().get(&(to, data.clone())) {
+            return Some(output.clone());
+        }
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(&data[..4]);
+        self.calls.lock().unwrap().get(&(to, selector)).cloned()
+    }
+}
+
+const MULTICALL3: &str = "0xcA11bde05977b3631167028662bEcA7e4e4D6A6e";
+const AGGREGATE3: &str = "aggregate3((address,bool,bytes)[])";
+
+impl MockRpc {
+    fn aggregate3(&self, data: &[u8]) -> Option<Bytes> {
+        let call_type = ParamType::Array(Box::new(ParamType::Tuple(vec![
+            ParamType::Address,
+            ParamType::Bool,
+            ParamType::Bytes,
+        ])));
+        let Token::Array(calls) = abi::decode(&[call_type], data).ok()?.remove(0) else {
+            return None;
+        };
+
+        let results = calls
+            .into_iter()
+            .map(|call| {
+                let fields = call.into_tuple().unwrap();
+                let target = fields[0].clone().into_address().unwrap();
+                let calldata = Bytes::from(fields[2].clone().into_bytes().unwrap());
+                let output = self.eth_call(target, &calldata);
+                Token::Tuple(vec![
+                    Token::Bool(output.is_some()),
+                    Token::Bytes(output.unwrap_or_default().to_vec()),
+                ])
+            })
+            .collect();
+
+        Some(Bytes::from(abi::encode(&[Token::Array(results)])))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+pub fn address(s: &str) -> Address {
+    s.parse().unwrap()
+}
+
+pub fn uint(value: u128) -> Token {
+    Token::Uint(U256::from(value))
+}
+
+/// JSON for a block header, as returned by `eth_getBlockByNumber`.
+pub fn block_json(number: u64, hash: H256, parent_hash: H256, timestamp: u64) -> Value {
+    json!({
+        "number": format!("{:#x}", number),
+        "hash": hash,
+        "parentHash": parent_hash,
+        "timestamp": format!("{:#x}", timestamp),
+        "logsBloom": null,
+        "transactions": [],
+    })
+}
+
+/// `value * 10^decimals` as a token amount.
+pub fn units(value: u64, decimals: u32) -> U256 {
+    U256::from(value) * U256::exp10(decimals as usize)
+}
+
+pub fn weth_usdc() -> TokenPair {
+    TokenPair {
+        base: "WETH".to_string(),
+        quote: "USDC".to_string(),
+        base_address: WETH.to_string(),
+        quote_address: USDC.to_string(),
+    }
+}
+
+/// A quote without pool state, deep enough to never limit a trade.
+pub fn quote(dex: &str, token_pair: &str, price: f64, fee: f64) -> PriceData {
+    PriceData {
+        dex_name: dex.to_string(),
+        token_pair: token_pair.to_string(),
+        price: amounts::decimal(price),
+        timestamp: 0,
+        liquidity: Decimal::from(1_000_000),
+        fee,
+        block: None,
+        reserves: None,
+        v3_liquidity: None,
+    }
+}
@@ -0,0 +1,87 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+/// One WebSocket connection's script: the headers pushed after
+/// `eth_subscribe`, then whether the server drops the socket.
+pub struct Session {
+    pub heads: Vec<Value>,
+    pub close: bool,
+}
+
+/// Minimal `newHeads` WebSocket server. Each connection plays the next
+/// scripted session; once they run out, connections subscribe and idle.
+#[derive(Clone, Default)]
+pub struct MockWs {
+    sessions: Arc<Mutex<VecDeque<Session>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl MockWs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn session(&self, heads: Vec<Value>, close: bool) -> &Self {
+        self.sessions.lock().unwrap().push_back(Session { heads, close });
+        self
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    pub async fn serve(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mock = self.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                mock.connections.fetch_add(1, Ordering::SeqCst);
+                let session = mock.sessions.lock().unwrap().pop_front();
+                tokio::spawn(async move {
+                    let Ok(mut socket) = tokio_tungstenite::accept_async(stream).await else {
+                        return;
+                    };
+                    let session = session.unwrap_or(Session { heads: Vec::new(), close: false });
+
+                    while let Some(Ok(message)) = socket.next().await {
+                        let Message::Text(text) = message else {
+                            continue;
+                        };
+                        let request: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                        let reply = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x1" });
+                        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                            return;
+                        }
+                        if request["method"] != "eth_subscribe" {
+                            continue;
+                        }
+
+                        for head in &session.heads {
+                            let notification = json!({
+                                "jsonrpc": "2.0",
+                                "method": "eth_subscription",
+                                "params": { "subscription": "0x1", "result": head },
+                            });
+                            let _ = socket.send(Message::Text(notification.to_string())).await;
+                            tokio::time::sleep(Duration::from_millis(10)).await;
+                        }
+                        if session.close {
+                            let _ = socket.close(None).await;
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        format!("ws://{}", addr)
+    }
+}
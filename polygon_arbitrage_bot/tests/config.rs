@@ -0,0 +1,168 @@
+use polygon_arbitrage_bot::config::Config;
+use std::collections::HashMap;
+
+const FILE: &str = r#"
+profile = "mainnet"
+rpc_urls = ["https://polygon-rpc.com"]
+min_profit_threshold = 0.001
+trade_size = 500.0
+
+[profiles.mainnet]
+
+[profiles.local]
+rpc_urls = ["http://127.0.0.1:8545"]
+database_url = "mysql://root@localhost/arbitrage"
+max_trade_size = 10000.0
+
+[[profiles.local.dexes]]
+name = "quickswap"
+kind = "uniswap_v2"
+router = "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff"
+factory = "0x5757371414417b8C6CAad45bAeF941aBc7d3Ab32"
+fee = 0.003
+"#;
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |name| vars.get(name).cloned()
+}
+
+#[test]
+fn profile_overrides_top_level_values() {
+    let mainnet = Config::from_toml(FILE, None, &env(&[])).unwrap();
+    assert_eq!(mainnet.profile, "mainnet");
+    assert_eq!(mainnet.rpc_url(), "https://polygon-rpc.com");
+    assert_eq!(mainnet.min_profit_threshold, 0.001);
+    assert_eq!(mainnet.trade_size, 500.0);
+    // Unset keys keep the built-in defaults
+    assert_eq!(mainnet.dexes.len(), 3);
+    assert_eq!(mainnet.max_cycle_hops, 4);
+    assert!(mainnet.database_url.is_none());
+
+    let local = Config::from_toml(FILE, Some("local"), &env(&[])).unwrap();
+    assert_eq!(local.profile, "local");
+    assert_eq!(local.rpc_urls, vec!["http://127.0.0.1:8545"]);
+    assert_eq!(local.database_url.as_deref(), Some("mysql://root@localhost/arbitrage"));
+    assert_eq!(local.max_trade_size, Some(10000.0));
+    assert_eq!(local.trade_size, 500.0);
+    assert_eq!(local.dexes.len(), 1);
+    assert_eq!(local.dexes[0].fee, Some(0.003));
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let vars = env(&[
+        ("CONFIG_PROFILE", "local"),
+        ("POLYGON_RPC_URL", "https://a.example, https://b.example"),
+        ("MIN_PROFIT_THRESHOLD", "0.005"),
+        ("MAX_TRADE_SIZE", ""),
+        ("NATIVE_PRICE_FEED", ""),
+    ]);
+
+    let config = Config::from_toml(FILE, None, &vars).unwrap();
+
+    assert_eq!(config.profile, "local");
+    assert_eq!(config.rpc_urls, vec!["https://a.example", "https://b.example"]);
+    assert_eq!(config.min_profit_threshold, 0.005);
+    assert_eq!(config.max_trade_size, None);
+    assert_eq!(config.native_price_feed, None);
+    // Untouched by the environment
+    assert_eq!(config.database_url.as_deref(), Some("mysql://root@localhost/arbitrage"));
+}
+
+#[test]
+fn reports_every_error_at_once() {
+    let file = r#"
+rpc_urls = ["not a url"]
+min_profit_threshold = 1.5
+max_cycle_hops = 1
+
+[[token_pairs]]
+base = "WETH"
+quote = "WETH"
+base_address = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619"
+quote_address = "0x7CEB23fD6bC0adD59E62ac25578270cFf1b9f619"
+
+[[dexes]]
+name = "uniswap_v3"
+kind = "uniswap_v3"
+router = "0xE592427A0AEce92De3Edee1F18E0157C05861564"
+fee = 0.003
+"#;
+
+    let error = Config::from_toml(file, None, &env(&[("TRADE_SIZE", "lots")])).unwrap_err().to_string();
+
+    assert!(error.starts_with("Invalid configuration (8 errors)"), "{}", error);
+    for expected in [
+        "TRADE_SIZE=lots",
+        "rpc_urls[0]",
+        "min_profit_threshold: 1.5",
+        "max_cycle_hops: 1",
+        "token_pairs[0]: base and quote are both WETH",
+        "token_pairs[0].quote_address: Address",
+        "dexes[0].fee",
+        "dexes[0].factory: required",
+    ] {
+        assert!(error.contains(expected), "missing {:?} in {}", expected, error);
+    }
+    assert_eq!(error.lines().count(), 9, "{}", error);
+}
+
+#[test]
+fn rejects_unknown_profiles_and_keys() {
+    let error = Config::from_toml(FILE, Some("amoy"), &env(&[])).unwrap_err().to_string();
+    assert!(error.contains("Unknown profile amoy"), "{}", error);
+    assert!(error.contains("mainnet, local") || error.contains("local, mainnet"), "{}", error);
+
+    let error = Config::from_toml("min_profit_treshold = 0.01", None, &env(&[])).unwrap_err().to_string();
+    assert!(error.contains("min_profit_treshold"), "{}", error);
+}
+
+#[test]
+fn shipped_config_file_is_valid_for_every_profile() {
+    let file = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml")).unwrap();
+
+    let mainnet = Config::from_toml(&file, None, &env(&[])).unwrap();
+    assert_eq!(mainnet.token_pairs.len(), 5);
+    assert_eq!(mainnet.min_profit_threshold, 0.001);
+
+    let local = Config::from_toml(&file, Some("local"), &env(&[])).unwrap();
+    assert_eq!(local.rpc_url(), "http://127.0.0.1:8545");
+}
+
+#[test]
+fn rpc_urls_must_be_http() {
+    let error = Config::from_toml(r#"rpc_urls = ["wss://polygon.example"]"#, None, &env(&[])).unwrap_err().to_string();
+    assert!(error.contains("rpc_urls[0]: expected http:// or https://, found wss://"), "{}", error);
+}
+
+#[test]
+fn rpc_endpoints_replace_rpc_urls() {
+    let file = r#"
+rpc_urls = ["https://polygon-rpc.com"]
+rpc_endpoints = [
+    { url = "https://a.example/v2/key", weight = 3.0, max_rps = 25.0 },
+    { url = "https://b.example" },
+]
+"#;
+
+    let config = Config::from_toml(file, None, &env(&[])).unwrap();
+    let endpoints = config.endpoints();
+    assert_eq!(endpoints.len(), 2);
+    assert_eq!(config.rpc_url(), "https://a.example/v2/key");
+    assert_eq!((endpoints[0].weight, endpoints[0].max_rps), (3.0, Some(25.0)));
+    assert_eq!((endpoints[1].weight, endpoints[1].max_rps), (1.0, None));
+
+    // Plain URLs share the load equally
+    let config = Config::from_toml(FILE, None, &env(&[("POLYGON_RPC_URL", "https://a.example,https://b.example")])).unwrap();
+    let endpoints = config.endpoints();
+    assert_eq!(endpoints.iter().map(|endpoint| endpoint.weight).collect::<Vec<_>>(), vec![1.0, 1.0]);
+
+    let invalid = r#"
+rpc_endpoints = [{ url = "wss://a.example", weight = 0.0, max_rps = -1.0 }]
+"#;
+    let error = Config::from_toml(invalid, None, &env(&[])).unwrap_err().to_string();
+    for expected in ["rpc_endpoints[0].url", "rpc_endpoints[0].weight", "rpc_endpoints[0].max_rps"] {
+        assert!(error.contains(expected), "missing {:?} in {}", expected, error);
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::quote;
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageDetector;
+use polygon_arbitrage_bot::modules::cycle_search::TokenGraph;
+use polygon_arbitrage_bot::modules::price_fetcher::PriceData;
+use polygon_arbitrage_bot::modules::profit_calculator::ProfitCalculator;
+
+// USDC -> A -> B -> C -> USDC, consistent except for a 3% rich C/USDC quote
+fn four_token_ring(edge: f64, fee: f64) -> Vec<PriceData> {
+    vec![
+        quote("dex_a", "A/USDC", 2.0, fee),
+        quote("dex_b", "B/A", 0.5, fee),
+        quote("dex_c", "C/B", 0.25, fee),
+        quote("dex_d", "C/USDC", 0.25 * edge, fee),
+    ]
+}
+
+#[test]
+fn finds_a_four_hop_cycle() {
+    let prices = four_token_ring(1.03, 0.0);
+    let graph = TokenGraph::from_prices(&prices);
+
+    assert_eq!(graph.token_count(), 4);
+    assert_eq!(graph.edge_count(), 8);
+
+    let cycles = graph.find_negative_cycles(4);
+    assert_eq!(cycles.len(), 1, "the cycle is reported once, not once per token");
+    assert_eq!(cycles[0].len(), 4);
+
+    let rate: f64 = cycles[0].iter().map(|&i| graph.edge(i).rate).product();
+    assert!((rate - 1.03).abs() < 1e-9);
+}
+
+#[test]
+fn hop_limit_excludes_longer_cycles() {
+    let prices = four_token_ring(1.03, 0.0);
+    let graph = TokenGraph::from_prices(&prices);
+
+    assert!(graph.find_negative_cycles(3).is_empty());
+}
+
+#[test]
+fn fees_can_remove_a_cycle() {
+    // 1% mispricing against four 0.3% fees
+    let prices = four_token_ring(1.01, 0.003);
+    let graph = TokenGraph::from_prices(&prices);
+
+    assert!(graph.find_negative_cycles(4).is_empty());
+}
+
+#[test]
+fn finds_two_hop_cross_dex_cycle() {
+    let prices = vec![
+        quote("sushiswap", "WETH/USDC", 2500.0, 0.0),
+        quote("quickswap", "WETH/USDC", 2550.0, 0.0),
+    ];
+    let graph = TokenGraph::from_prices(&prices);
+
+    let cycles = graph.find_negative_cycles(4);
+    assert_eq!(cycles.len(), 1);
+    let dexes: Vec<&str> = cycles[0].iter().map(|&i| graph.edge(i).quote.dex_name.as_str()).collect();
+    assert!(dexes.contains(&"sushiswap") && dexes.contains(&"quickswap"));
+}
+
+#[test]
+fn consistent_prices_have_no_cycles() {
+    let prices = four_token_ring(1.0, 0.0);
+    let graph = TokenGraph::from_prices(&prices);
+
+    assert!(graph.find_negative_cycles(6).is_empty());
+}
+
+#[tokio::test]
+async fn detector_starts_routes_at_a_stablecoin_and_prices_each_hop() {
+    let detector = ArbitrageDetector::new(0.001);
+    let prices = four_token_ring(1.03, 0.0);
+
+    let routes = detector.detect_cycles(&prices, 4).unwrap();
+
+    assert_eq!(routes.len(), 1);
+    let route = &routes[0];
+    assert_eq!(route.start_token, "USDC");
+    assert_eq!(route.hops.len(), 4);
+    assert!((route.profit_percentage - 0.03).abs() < 1e-9);
+    assert!((route.profit_usd - 30.0).abs() < 1e-6);
+    for window in route.hops.windows(2) {
+        assert_eq!(window[0].amount_out, window[1].amount_in);
+    }
+
+    let analysis = ProfitCalculator::new().calculate_route_profit(route).await.unwrap();
+    assert_eq!(analysis.legs.len(), 4);
+    let gas: Decimal = analysis.legs.iter().map(|leg| leg.gas_cost).sum();
+    assert_eq!(analysis.gas_costs, gas);
+    let costs = amounts::float(analysis.gas_costs + analysis.slippage_cost);
+    assert!((amounts::float(analysis.net_profit) - (30.0 - costs)).abs() < 1e-6);
+}
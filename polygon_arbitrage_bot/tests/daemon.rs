@@ -0,0 +1,87 @@
+mod common;
+
+use common::MockRpc;
+use ethers::providers::Provider;
+use polygon_arbitrage_bot::config::Config;
+use polygon_arbitrage_bot::daemon::{CycleTrigger, DaemonSummary, ScanTrigger, Tick};
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+#[tokio::test]
+async fn block_trigger_fires_once_per_new_block_and_coalesces_missed_ones() {
+    let rpc = MockRpc::new();
+    let blocks = [100u64, 100, 100, 101, 105];
+    let polls = Arc::new(AtomicUsize::new(0));
+    let counter = polls.clone();
+    rpc.fallback(move |method, _| {
+        (method == "eth_blockNumber").then(|| {
+            let poll = counter.fetch_add(1, Ordering::SeqCst).min(blocks.len() - 1);
+            json!(format!("{:#x}", blocks[poll]))
+        })
+    });
+    let url = rpc.serve().await;
+    let client = Arc::new(Provider::new(RpcPool::single(&url).unwrap()));
+    let mut trigger = CycleTrigger::per_block(client).with_poll_interval(Duration::from_millis(5));
+    let (_sender, mut shutdown) = watch::channel(false);
+
+    let tick = |skipped| Some(Tick { skipped, block: None });
+
+    // The first block seen starts a cycle
+    assert_eq!(trigger.wait(&mut shutdown).await.unwrap(), tick(0));
+    // Polls through the repeats of block 100 until 101
+    assert_eq!(trigger.wait(&mut shutdown).await.unwrap(), tick(0));
+    assert_eq!(polls.load(Ordering::SeqCst), 4);
+    // 102 to 104 were mined during the cycle
+    assert_eq!(trigger.wait(&mut shutdown).await.unwrap(), tick(3));
+}
+
+#[tokio::test]
+async fn shutdown_interrupts_the_wait_between_cycles() {
+    let mut trigger = CycleTrigger::interval(Duration::from_secs(60));
+    let (sender, mut shutdown) = watch::channel(false);
+
+    let started = Instant::now();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        sender.send(true).unwrap();
+    });
+
+    assert_eq!(trigger.wait(&mut shutdown).await.unwrap(), None);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // Once requested, no further cycle is started
+    assert_eq!(trigger.wait(&mut shutdown).await.unwrap(), None);
+}
+
+#[test]
+fn summary_counts_failures_and_skipped_blocks() {
+    let mut summary = DaemonSummary::new();
+    summary.record_failure();
+    summary.record_skipped_blocks(2);
+    summary.record_skipped_blocks(1);
+
+    assert_eq!((summary.cycles, summary.failed_cycles, summary.skipped_blocks), (1, 1, 3));
+    assert!(summary.best_profit_percentage.is_none());
+}
+
+#[test]
+fn trigger_and_interval_are_configurable() {
+    let config = Config::from_toml("scan_trigger = \"block\"", None, &|_| None).unwrap();
+    assert_eq!(config.scan_trigger, ScanTrigger::Block);
+    assert_eq!(config.scan_interval, 5);
+
+    let env = |name: &str| match name {
+        "SCAN_TRIGGER" => Some("interval".to_string()),
+        "SCAN_INTERVAL" => Some("12".to_string()),
+        _ => None,
+    };
+    let config = Config::from_toml("scan_trigger = \"block\"", None, &env).unwrap();
+    assert_eq!((config.scan_trigger, config.scan_interval), (ScanTrigger::Interval, 12));
+
+    let error = Config::from_toml("scan_interval = 0", None, &|_| None).unwrap_err().to_string();
+    assert!(error.contains("scan_interval"), "{}", error);
+    assert!(Config::from_toml("scan_trigger = \"mempool\"", None, &|_| None).is_err());
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::{address, uint, MockRpc, USDC, WETH};
+use ethers::abi::AbiDecode;
+use ethers::providers::Provider;
+use ethers::types::{Bytes, U256};
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageOpportunity;
+use polygon_arbitrage_bot::modules::gas_estimator::{GasEstimateSource, GasEstimator, SwapLeg};
+use polygon_arbitrage_bot::modules::price_source::DexKind;
+use polygon_arbitrage_bot::modules::profit_calculator::ProfitCalculator;
+use polygon_arbitrage_bot::modules::routers::{ExactInputSingleCall, SwapExactTokensForTokensCall};
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const SENDER: &str = "0x00000000000000000000000000000000000000A1";
+const SUSHI_ROUTER: &str = "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506";
+const QUICK_ROUTER: &str = "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff";
+const V3_ROUTER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
+
+fn mock_decimals(rpc: &MockRpc) {
+    rpc.on_token(address(WETH), "WETH", 18)
+        .on_token(address(USDC), "USDC", 6);
+}
+
+/// Answers eth_estimateGas per router and records every simulated transaction.
+fn mock_estimates(rpc: &MockRpc, gas: &[(&str, u64)]) -> Arc<Mutex<Vec<Value>>> {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let gas: Vec<(ethers::types::Address, u64)> = gas.iter().map(|(router, units)| (address(router), *units)).collect();
+    let recorded = seen.clone();
+    rpc.fallback(move |method, params| {
+        if method != "eth_estimateGas" {
+            return None;
+        }
+        recorded.lock().unwrap().push(params[0].clone());
+        let to: ethers::types::Address = params[0]["to"].as_str()?.parse().ok()?;
+        let units = gas.iter().find(|(router, _)| *router == to)?.1;
+        Some(json!(format!("{:#x}", units)))
+    });
+    seen
+}
+
+async fn estimator(rpc: &MockRpc) -> GasEstimator {
+    let url = rpc.serve().await;
+    GasEstimator::new(Arc::new(Provider::new(RpcPool::single(&url).unwrap())))
+        .with_sender(SENDER)
+        .unwrap()
+        .with_dex("sushiswap", DexKind::UniswapV2, SUSHI_ROUTER)
+        .unwrap()
+        .with_dex("quickswap", DexKind::UniswapV2, QUICK_ROUTER)
+        .unwrap()
+        .with_dex("uniswap_v3", DexKind::UniswapV3, V3_ROUTER)
+        .unwrap()
+        .with_token("WETH", WETH)
+        .unwrap()
+        .with_token("USDC", USDC)
+        .unwrap()
+}
+
+fn leg(dex: &str, token_in: &str, token_out: &str, amount_in: f64, fee: f64) -> SwapLeg {
+    SwapLeg {
+        dex_name: dex.to_string(),
+        token_in: token_in.to_string(),
+        token_out: token_out.to_string(),
+        amount_in,
+        fee,
+        price_impact: None,
+    }
+}
+
+fn calldata(tx: &Value) -> Bytes {
+    tx["input"].as_str().or_else(|| tx["data"].as_str()).unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn simulates_v2_swap_from_the_sender() {
+    let rpc = MockRpc::new();
+    mock_decimals(&rpc);
+    let seen = mock_estimates(&rpc, &[(SUSHI_ROUTER, 118_000)]);
+    let estimator = estimator(&rpc).await;
+
+    let gas = estimator.estimate(&leg("sushiswap", "USDC", "WETH", 1000.0, 0.003), None).await;
+
+    assert_eq!(gas.units, 118_000);
+    assert_eq!(gas.source, GasEstimateSource::Simulated);
+
+    let tx = seen.lock().unwrap()[0].clone();
+    assert_eq!(tx["from"].as_str().unwrap().parse::<ethers::types::Address>().unwrap(), address(SENDER));
+    let call = SwapExactTokensForTokensCall::decode(calldata(&tx)).unwrap();
+    assert_eq!(call.amount_in, U256::from(1_000_000_000u64));
+    assert_eq!(call.path, vec![address(USDC), address(WETH)]);
+    assert_eq!(call.to, address(SENDER));
+}
+
+#[tokio::test]
+async fn v3_calldata_uses_the_fee_tier() {
+    let rpc = MockRpc::new();
+    mock_decimals(&rpc);
+    let estimator = estimator(&rpc).await;
+
+    let (router, data) = estimator
+        .swap_calldata(&leg("uniswap_v3", "WETH", "USDC", 0.5, 0.0005), address(SENDER))
+        .await
+        .unwrap();
+
+    assert_eq!(router, address(V3_ROUTER));
+    let call = ExactInputSingleCall::decode(data).unwrap();
+    assert_eq!(call.params.fee, 500);
+    assert_eq!(call.params.token_in, address(WETH));
+    assert_eq!(call.params.amount_in, U256::exp10(17) * 5);
+}
+
+#[tokio::test]
+async fn fee_tier_quotes_use_their_dex_router_and_model() {
+    let rpc = MockRpc::new();
+    mock_decimals(&rpc);
+    let seen = mock_estimates(&rpc, &[(V3_ROUTER, 152_000)]);
+    let estimator = estimator(&rpc).await;
+
+    // V3 quotes are named per fee tier, e.g. uniswap_v3_500
+    let gas = estimator.estimate(&leg("uniswap_v3_500", "WETH", "USDC", 0.5, 0.0005), None).await;
+    assert_eq!(gas.units, 152_000);
+    assert_eq!(gas.source, GasEstimateSource::Simulated);
+    let call = ExactInputSingleCall::decode(calldata(&seen.lock().unwrap()[0])).unwrap();
+    assert_eq!(call.params.fee, 500);
+
+    // Other tiers fall back to what the DEX as a whole has been observed to use
+    let fallback = estimator.estimate(&leg("uniswap_v3_3000", "DAI", "USDC", 1.0, 0.003), None).await;
+    assert_eq!(fallback.source, GasEstimateSource::Model);
+    assert_eq!(fallback.units, 152_000);
+}
+
+#[tokio::test]
+async fn reverted_simulation_falls_back_to_calibrated_model() {
+    let rpc = MockRpc::new();
+    mock_decimals(&rpc);
+    let seen = mock_estimates(&rpc, &[(SUSHI_ROUTER, 120_000)]);
+    let estimator = estimator(&rpc).await;
+
+    estimator.estimate(&leg("sushiswap", "USDC", "WETH", 1000.0, 0.003), None).await;
+    estimator.estimate(&leg("sushiswap", "USDC", "WETH", 2000.0, 0.003), None).await;
+    assert_eq!(seen.lock().unwrap().len(), 2);
+
+    // Unknown tokens can't be encoded, so the model answers from what it has seen
+    let sushi = estimator.estimate(&leg("sushiswap", "DAI", "WETH", 1000.0, 0.003), None).await;
+    assert_eq!(sushi.source, GasEstimateSource::Model);
+    assert_eq!(sushi.units, 120_000);
+
+    // Nothing observed for QuickSwap yet: the V2 default applies
+    let quick = estimator.estimate(&leg("quickswap", "WETH", "USDC", 1.0, 0.003), None).await;
+    assert_eq!(quick.source, GasEstimateSource::Model);
+    assert_eq!(quick.units, 130_000);
+}
+
+#[tokio::test]
+async fn profit_analysis_reports_gas_units_per_leg() {
+    let rpc = MockRpc::new();
+    mock_decimals(&rpc);
+    mock_estimates(&rpc, &[(SUSHI_ROUTER, 110_000), (QUICK_ROUTER, 125_000)]);
+    let calculator = ProfitCalculator::new().with_gas_estimator(estimator(&rpc).await);
+    let opportunity = ArbitrageOpportunity {
+        token_pair: "WETH/USDC".to_string(),
+        buy_dex: "sushiswap".to_string(),
+        sell_dex: "quickswap".to_string(),
+        buy_price: amounts::decimal(2500.0),
+        sell_price: amounts::decimal(2550.0),
+        buy_fee: 0.003,
+        sell_fee: 0.003,
+        raw_spread: amounts::decimal(0.02),
+        profit_percentage: amounts::decimal(0.014),
+        profit_usd: amounts::decimal(14.0),
+        trade_size: amounts::decimal(1000.0),
+        amount_in: Some(amounts::TokenAmount::new(U256::from(1_000_000_000u64), 6)),
+        timestamp: 0,
+        block: None,
+        block_span: 0,
+        sizing: None,
+        buy_impact: None,
+        sell_impact: None,
+    };
+
+    let analysis = calculator.calculate_detailed_profit(&opportunity).await.unwrap();
+
+    let units: Vec<u64> = analysis.legs.iter().map(|leg| leg.gas_units).collect();
+    assert_eq!(units, vec![110_000, 125_000]);
+    assert_eq!(analysis.gas_units, 235_000);
+    // 235k gas at the default 30 gwei, MATIC at $0.80
+    assert_eq!(analysis.gas_costs, Decimal::new(564, 5));
+}
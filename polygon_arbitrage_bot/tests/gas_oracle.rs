@@ -0,0 +1,137 @@
+mod common;
+
+use common::MockRpc;
+use ethers::providers::Provider;
+use ethers::types::U256;
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageOpportunity;
+use polygon_arbitrage_bot::modules::gas_oracle::{GasOracle, GasPriceSource};
+use polygon_arbitrage_bot::modules::price_fetcher::BlockRef;
+use polygon_arbitrage_bot::modules::profit_calculator::ProfitCalculator;
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use serde_json::json;
+use std::sync::Arc;
+
+fn gwei(value: u64) -> String {
+    format!("{:#x}", value * 1_000_000_000)
+}
+
+fn mock_fee_history(rpc: &MockRpc) {
+    rpc.on_method(
+        "eth_feeHistory",
+        json!({
+            "oldestBlock": "0x60",
+            "baseFeePerGas": [gwei(90), gwei(95), gwei(98), gwei(99), gwei(100), gwei(100)],
+            "gasUsedRatio": [0.5, 0.6, 0.5, 0.5, 0.5],
+            "reward": [[gwei(30)], [gwei(40)], [gwei(31)], [gwei(35)], [gwei(32)]]
+        }),
+    );
+}
+
+async fn oracle(rpc: &MockRpc) -> GasOracle {
+    let url = rpc.serve().await;
+    GasOracle::new(Arc::new(Provider::new(RpcPool::single(&url).unwrap()))).with_reward_percentile(60.0)
+}
+
+fn opportunity(block: u64) -> ArbitrageOpportunity {
+    ArbitrageOpportunity {
+        token_pair: "WETH/USDC".to_string(),
+        buy_dex: "sushiswap".to_string(),
+        sell_dex: "quickswap".to_string(),
+        buy_price: amounts::decimal(2500.0),
+        sell_price: amounts::decimal(2550.0),
+        buy_fee: 0.003,
+        sell_fee: 0.003,
+        raw_spread: amounts::decimal(0.02),
+        profit_percentage: amounts::decimal(0.014),
+        profit_usd: amounts::decimal(14.0),
+        trade_size: amounts::decimal(1000.0),
+        amount_in: None,
+        timestamp: 0,
+        block: Some(BlockRef {
+            number: block,
+            hash: Default::default(),
+            timestamp: 0,
+        }),
+        block_span: 0,
+        sizing: None,
+        buy_impact: None,
+        sell_impact: None,
+    }
+}
+
+#[tokio::test]
+async fn base_fee_plus_median_percentile_reward() {
+    let rpc = MockRpc::new();
+    mock_fee_history(&rpc);
+    let oracle = oracle(&rpc).await;
+
+    let gas = oracle.gas_price(Some(100)).await.unwrap();
+
+    assert_eq!(gas.source, GasPriceSource::FeeHistory);
+    assert_eq!(gas.base_fee_wei, U256::from(100_000_000_000u64));
+    assert_eq!(gas.priority_fee_wei, U256::from(32_000_000_000u64));
+    assert_eq!(gas.base_fee_gwei(), 100.0);
+    assert_eq!(gas.gas_price_gwei(), 132.0);
+    assert_eq!(gas.reward_percentile, Some(60.0));
+    assert_eq!(gas.block_number, Some(100));
+}
+
+#[tokio::test]
+async fn caches_one_reading_per_block() {
+    let rpc = MockRpc::new();
+    mock_fee_history(&rpc);
+    let oracle = oracle(&rpc).await;
+
+    oracle.gas_price(Some(100)).await.unwrap();
+    oracle.gas_price(Some(100)).await.unwrap();
+    assert_eq!(rpc.count("eth_feeHistory"), 1);
+
+    oracle.gas_price(Some(101)).await.unwrap();
+    assert_eq!(rpc.count("eth_feeHistory"), 2);
+}
+
+#[tokio::test]
+async fn falls_back_to_eth_gas_price() {
+    let rpc = MockRpc::new();
+    rpc.on_method("eth_gasPrice", json!(gwei(80)))
+        .on_method("eth_blockNumber", json!("0x2a"));
+    let oracle = oracle(&rpc).await;
+
+    let gas = oracle.gas_price(None).await.unwrap();
+
+    assert_eq!(gas.source, GasPriceSource::GasPrice);
+    assert_eq!(gas.gas_price_gwei(), 80.0);
+    // eth_gasPrice answers for the latest block, whatever was asked for
+    assert_eq!(gas.block_number, None);
+
+    // Nor is it reused as the price at that block
+    oracle.gas_price(Some(42)).await.unwrap();
+    assert_eq!(rpc.count("eth_gasPrice"), 2);
+}
+
+#[tokio::test]
+async fn profit_analysis_uses_and_reports_the_oracle_price() {
+    let rpc = MockRpc::new();
+    mock_fee_history(&rpc);
+    let calculator = ProfitCalculator::new().with_gas_oracle(oracle(&rpc).await);
+
+    let analysis = calculator.calculate_detailed_profit(&opportunity(100)).await.unwrap();
+
+    assert_eq!(analysis.gas_price.gas_price_gwei(), 132.0);
+    assert_eq!(analysis.gas_price.block_number, Some(100));
+    // Two swaps of 200k gas at 132 gwei, MATIC at $0.80
+    assert_eq!(analysis.gas_costs, Decimal::new(4224, 5));
+}
+
+#[tokio::test]
+async fn out_of_range_gas_cost_is_an_error() {
+    let rpc = MockRpc::new();
+    rpc.on_method("eth_gasPrice", json!(format!("{:#x}", U256::MAX)))
+        .on_method("eth_blockNumber", json!("0x2a"));
+    let calculator = ProfitCalculator::new().with_gas_oracle(oracle(&rpc).await);
+
+    let error = calculator.calculate_detailed_profit(&opportunity(100)).await.unwrap_err();
+
+    assert!(error.to_string().contains("Gas cost out of range"), "{}", error);
+}
@@ -0,0 +1,308 @@
+mod common;
+
+use common::ws::MockWs;
+use common::{USDC, WETH};
+use ethers::abi::AbiEncode;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes, Eip1559TransactionRequest, H256, U256};
+use polygon_arbitrage_bot::config::{DexConfig, TokenPair};
+use polygon_arbitrage_bot::modules::amounts::{self, TokenAmount};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageDetector;
+use polygon_arbitrage_bot::modules::mempool::{self, MempoolWatcher, PendingTransactions, SwapHop};
+use polygon_arbitrage_bot::modules::new_heads::Backoff;
+use polygon_arbitrage_bot::modules::price_fetcher::{BlockRef, PriceData, Reserves};
+use polygon_arbitrage_bot::modules::price_source::DexKind;
+use polygon_arbitrage_bot::modules::routers::{
+    ExactInputCall, ExactInputParams, ExactInputSingleCall, ExactInputSingleParams, MulticallCall,
+    SwapExactTokensForTokensCall,
+};
+use polygon_arbitrage_bot::modules::uniswap_v2;
+use polygon_arbitrage_bot::modules::v3_math;
+use polygon_arbitrage_bot::modules::v3_swap::V3Liquidity;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const WBTC: &str = "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6";
+const SUSHI_ROUTER: &str = "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506";
+const V3_ROUTER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
+const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn addr(address: &str) -> Address {
+    address.parse().unwrap()
+}
+
+fn dex(name: &str, kind: DexKind, router: &str) -> DexConfig {
+    DexConfig {
+        name: name.to_string(),
+        kind,
+        router: router.to_string(),
+        factory: None,
+        fee: None,
+    }
+}
+
+fn watcher() -> MempoolWatcher {
+    let dexes = [
+        dex("sushiswap", DexKind::UniswapV2, SUSHI_ROUTER),
+        dex("quickswap", DexKind::UniswapV2, "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff"),
+        dex("uniswap_v3", DexKind::UniswapV3, V3_ROUTER),
+    ];
+    let pairs = [TokenPair {
+        base: "WETH".to_string(),
+        quote: "USDC".to_string(),
+        base_address: WETH.to_string(),
+        quote_address: USDC.to_string(),
+    }];
+    MempoolWatcher::new(&dexes, &pairs, ArbitrageDetector::new(0.001)).unwrap()
+}
+
+fn block() -> Option<BlockRef> {
+    Some(BlockRef {
+        number: 100,
+        hash: H256::repeat_byte(1),
+        timestamp: 1_700_000_000,
+    })
+}
+
+fn v2_quote(dex: &str, weth: f64, usdc: f64) -> PriceData {
+    PriceData {
+        dex_name: dex.to_string(),
+        token_pair: "WETH/USDC".to_string(),
+        price: amounts::decimal(usdc / weth),
+        timestamp: 0,
+        liquidity: amounts::decimal(usdc * 2.0),
+        fee: 0.003,
+        block: block(),
+        reserves: Some(Reserves {
+            base: TokenAmount::from_f64(weth, 18).unwrap(),
+            quote: TokenAmount::from_f64(usdc, 6).unwrap(),
+        }),
+        v3_liquidity: None,
+    }
+}
+
+// WETH/USDC at tick 198060 (about $2500) with no initialized ticks nearby
+fn v3_quote(loaded_words: Option<(i16, i16)>) -> PriceData {
+    let pool = V3Liquidity {
+        pool: Address::repeat_byte(0x33),
+        fee: 3000,
+        tick_spacing: 60,
+        sqrt_price_x96: v3_math::sqrt_ratio_at_tick(198060).unwrap(),
+        tick: 198060,
+        liquidity: 2_000_000_000_000_000_000,
+        base_is_token0: false,
+        base_decimals: 18,
+        quote_decimals: 6,
+        ticks: BTreeMap::new(),
+        loaded_words,
+    };
+    PriceData {
+        dex_name: "uniswap_v3_3000".to_string(),
+        v3_liquidity: Some(pool),
+        ..v2_quote("uniswap_v3_3000", 1000.0, 2_500_000.0)
+    }
+}
+
+fn signed(to: &str, calldata: Vec<u8>) -> Vec<u8> {
+    let wallet = KEY.parse::<LocalWallet>().unwrap().with_chain_id(137u64);
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(addr(to))
+        .data(calldata)
+        .nonce(7)
+        .gas(300_000)
+        .max_fee_per_gas(100_000_000_000u64)
+        .max_priority_fee_per_gas(30_000_000_000u64)
+        .chain_id(137)
+        .into();
+    let signature = wallet.sign_transaction_sync(&tx).unwrap();
+    tx.rlp_signed(&signature).to_vec()
+}
+
+fn v2_swap(amount_in: U256, path: &[&str]) -> Vec<u8> {
+    SwapExactTokensForTokensCall {
+        amount_in,
+        amount_out_min: U256::zero(),
+        path: path.iter().map(|token| addr(token)).collect(),
+        to: Address::repeat_byte(0x11),
+        deadline: U256::from(1_700_000_600u64),
+    }
+    .encode()
+}
+
+fn exact_input_single(token_in: &str, token_out: &str, fee: u32, amount_in: U256) -> Vec<u8> {
+    ExactInputSingleCall {
+        params: ExactInputSingleParams {
+            token_in: addr(token_in),
+            token_out: addr(token_out),
+            fee,
+            recipient: Address::repeat_byte(0x11),
+            deadline: U256::from(1_700_000_600u64),
+            amount_in,
+            amount_out_minimum: U256::zero(),
+            sqrt_price_limit_x96: U256::zero(),
+        },
+    }
+    .encode()
+}
+
+fn packed_path(tokens: &[&str], fees: &[u32]) -> Bytes {
+    let mut path = addr(tokens[0]).as_bytes().to_vec();
+    for (token, fee) in tokens[1..].iter().zip(fees) {
+        path.extend_from_slice(&fee.to_be_bytes()[1..]);
+        path.extend_from_slice(addr(token).as_bytes());
+    }
+    Bytes::from(path)
+}
+
+fn e18(value: u64) -> U256 {
+    U256::from(value) * U256::exp10(18)
+}
+
+#[test]
+fn decodes_recorded_raw_transactions() {
+    let raw = signed(SUSHI_ROUTER, v2_swap(e18(5), &[WETH, USDC]));
+
+    let tx = mempool::decode_raw_transaction(&raw).unwrap();
+    let wallet = KEY.parse::<LocalWallet>().unwrap();
+    assert_eq!(tx.from, wallet.address());
+    assert_eq!(tx.hash, H256::from(ethers::utils::keccak256(&raw)));
+
+    let swaps = watcher().decode(&tx);
+    assert_eq!(swaps.len(), 1);
+    assert_eq!(swaps[0].dex, "sushiswap");
+    assert_eq!(swaps[0].amount_in, e18(5));
+    assert_eq!(
+        swaps[0].hops,
+        vec![SwapHop {
+            token_in: addr(WETH),
+            token_out: addr(USDC),
+            fee: None,
+        }]
+    );
+
+    assert!(mempool::decode_raw_transaction(&raw[..raw.len() - 3]).is_err());
+}
+
+#[test]
+fn unpacks_v3_multicalls_and_packed_paths() {
+    let exact_input = ExactInputCall {
+        params: ExactInputParams {
+            path: packed_path(&[WBTC, WETH, USDC], &[500, 3000]),
+            recipient: Address::repeat_byte(0x11),
+            deadline: U256::from(1_700_000_600u64),
+            amount_in: U256::from(10_000_000u64),
+            amount_out_minimum: U256::zero(),
+        },
+    }
+    .encode();
+    let multicall = MulticallCall {
+        data: vec![
+            Bytes::from(exact_input_single(USDC, WETH, 500, U256::from(1_000_000u64))),
+            Bytes::from(exact_input),
+            // Anything else in the bundle, like refundETH(), is skipped
+            Bytes::from(vec![0x12, 0x21, 0x0e, 0x8a]),
+        ],
+    }
+    .encode();
+
+    let tx = mempool::decode_raw_transaction(&signed(V3_ROUTER, multicall)).unwrap();
+    let swaps = watcher().decode(&tx);
+
+    assert_eq!(swaps.len(), 2);
+    assert_eq!(swaps[0].hops[0].fee, Some(500));
+    assert_eq!(swaps[0].amount_in, U256::from(1_000_000u64));
+    let hops: Vec<(Address, Address, Option<u32>)> =
+        swaps[1].hops.iter().map(|hop| (hop.token_in, hop.token_out, hop.fee)).collect();
+    assert_eq!(
+        hops,
+        vec![(addr(WBTC), addr(WETH), Some(500)), (addr(WETH), addr(USDC), Some(3000))]
+    );
+    assert!(mempool::decode_path(&packed_path(&[WBTC, WETH], &[500])[..30]).is_none());
+}
+
+#[test]
+fn ignores_transactions_that_are_not_router_swaps() {
+    let watcher = watcher();
+    let prices = vec![v2_quote("sushiswap", 1000.0, 2_500_000.0)];
+
+    let elsewhere = signed(&format!("{:?}", Address::repeat_byte(0x44)), v2_swap(e18(5), &[WETH, USDC]));
+    assert!(watcher.evaluate_raw(&prices, &elsewhere).unwrap().is_none());
+
+    // A V3 call sent to a V2 router isn't a swap that router understands
+    let wrong_router = signed(SUSHI_ROUTER, exact_input_single(WETH, USDC, 3000, e18(5)));
+    assert!(watcher.evaluate_raw(&prices, &wrong_router).unwrap().is_none());
+}
+
+#[test]
+fn pending_v2_swap_opens_arbitrage_against_an_untouched_pool() {
+    let watcher = watcher();
+    let prices = vec![
+        v2_quote("sushiswap", 1000.0, 2_500_000.0),
+        v2_quote("quickswap", 1000.0, 2_500_000.0),
+    ];
+    // Same prices on both pools: nothing to trade before the swap lands
+    assert!(ArbitrageDetector::new(0.001).detect_opportunities(&prices).unwrap().is_empty());
+
+    // Dumping 50 WETH on SushiSwap
+    let raw = signed(SUSHI_ROUTER, v2_swap(e18(50), &[WETH, USDC]));
+    let report = watcher.evaluate_raw(&prices, &raw).unwrap().unwrap();
+
+    let usdc_out = uniswap_v2::amount_out(e18(50), e18(1000), U256::from(2_500_000_000_000u64), 0.003).unwrap();
+    let expected = (2_500_000_000_000u64 - usdc_out.as_u64()) as f64 / 1e6 / 1050.0;
+    assert_eq!(report.moved.len(), 1);
+    assert_eq!(report.moved[0].dex_name, "sushiswap");
+    assert!((amounts::float(report.moved[0].price_after) - expected).abs() < 1e-6);
+    assert!(report.moved[0].change() < -0.09);
+
+    assert_eq!(report.opportunities.len(), 1);
+    let opportunity = &report.opportunities[0];
+    assert_eq!((opportunity.buy_dex.as_str(), opportunity.sell_dex.as_str()), ("sushiswap", "quickswap"));
+}
+
+#[test]
+fn projects_v3_swaps_through_the_simulated_pool() {
+    let watcher = watcher();
+    let before = v3_quote(Some((11, 13)));
+    let pool = before.v3_liquidity.clone().unwrap();
+    let tx = mempool::decode_raw_transaction(&signed(V3_ROUTER, exact_input_single(USDC, WETH, 3000, U256::from(100_000_000_000u64)))).unwrap();
+
+    let (projected, moved) = watcher.project(std::slice::from_ref(&before), &watcher.decode(&tx));
+
+    // Buying WETH with USDC: WETH is token1 here, so the swap is one for zero
+    let expected = pool.simulate_swap(true, U256::from(100_000_000_000u64)).unwrap();
+    let after = projected[0].v3_liquidity.as_ref().unwrap();
+    assert!(expected.filled);
+    assert_eq!(after.sqrt_price_x96, expected.sqrt_price_x96);
+    assert_eq!(after.tick, v3_math::tick_at_sqrt_ratio(expected.sqrt_price_x96).unwrap());
+    assert_eq!(moved.len(), 1);
+    assert!(moved[0].price_after > moved[0].price_before);
+
+    // Without loaded ticks the swap can't be replayed, so nothing moves
+    let unloaded = v3_quote(None);
+    let (_, moved) = watcher.project(&[unloaded], &watcher.decode(&tx));
+    assert!(moved.is_empty());
+}
+
+#[tokio::test]
+async fn streams_pending_transactions_across_reconnects() {
+    let first = mempool::decode_raw_transaction(&signed(SUSHI_ROUTER, v2_swap(e18(1), &[WETH, USDC]))).unwrap();
+    let second = mempool::decode_raw_transaction(&signed(V3_ROUTER, exact_input_single(WETH, USDC, 500, e18(1)))).unwrap();
+    let ws = MockWs::new();
+    ws.session(vec![serde_json::to_value(&first).unwrap()], true)
+        .session(vec![serde_json::to_value(&second).unwrap()], false);
+    let url = ws.serve().await;
+
+    let mut pending = PendingTransactions::with_backoff(&url, Backoff::new(Duration::from_millis(10), Duration::from_millis(50)));
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        vec![pending.next().await.unwrap(), pending.next().await.unwrap()]
+    })
+    .await
+    .expect("no pending transactions");
+
+    assert_eq!(received[0].hash, first.hash);
+    assert_eq!(received[1].hash, second.hash);
+    assert_eq!(watcher().decode(&received[1])[0].dex, "uniswap_v3");
+    assert!(pending.reconnects() >= 1);
+    assert_eq!(pending.dropped(), 0);
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use common::{address, block_json, uint, units, weth_usdc, MockRpc, USDC, WETH};
+use ethers::abi::Token;
+use ethers::types::{H256, U256};
+use polygon_arbitrage_bot::config::DexConfig;
+use polygon_arbitrage_bot::modules::price_fetcher::{BlockRef, PriceFetcher};
+use polygon_arbitrage_bot::modules::price_source::{DexKind, PriceSourceRegistry};
+use serde_json::json;
+
+const V2_FACTORY: &str = "0xc35DADB65012eC5796536bD9864eD8773aBc74C4";
+const V2_PAIR: &str = "0x34965ba0ac2451A34a0471F04CCa3F990b8dea27";
+const V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+const V3_POOL: &str = "0x45dDa9cb7c25131DF268515131f647d726f50608";
+
+fn dexes() -> Vec<DexConfig> {
+    vec![
+        DexConfig {
+            name: "sushiswap".to_string(),
+            kind: DexKind::UniswapV2,
+            router: String::new(),
+            factory: Some(V2_FACTORY.to_string()),
+            fee: None,
+        },
+        DexConfig {
+            name: "uniswap_v3".to_string(),
+            kind: DexKind::UniswapV3,
+            router: String::new(),
+            factory: Some(V3_FACTORY.to_string()),
+            fee: None,
+        },
+    ]
+}
+
+fn block_hash() -> H256 {
+    H256::repeat_byte(0x42)
+}
+
+fn mock_chain(rpc: &MockRpc) {
+    rpc.on_method("eth_getBlockByNumber", block_json(42, block_hash(), H256::zero(), 1_700_000_000))
+        .on_method("eth_chainId", json!("0x89"))
+        .on_token(address(WETH), "WETH", 18)
+        .on_token(address(USDC), "USDC", 6)
+        // V2 pair at 2500 USDC/WETH
+        .on_call(
+            address(V2_FACTORY),
+            "getPair(address,address)",
+            vec![Token::Address(address(V2_PAIR))],
+        )
+        .on_call(address(V2_PAIR), "token0()", vec![Token::Address(address(USDC))])
+        .on_call(
+            address(V2_PAIR),
+            "getReserves()",
+            vec![
+                Token::Uint(units(250_000, 6)),
+                Token::Uint(units(100, 18)),
+                uint(0),
+            ],
+        )
+        // A single V3 pool in the 0.05% tier, also at 2500
+        .on_call(
+            address(V3_FACTORY),
+            "getPool(address,address,uint24)",
+            vec![Token::Address(Default::default())],
+        )
+        .on_call_with(
+            address(V3_FACTORY),
+            "getPool(address,address,uint24)",
+            vec![
+                Token::Address(address(WETH)),
+                Token::Address(address(USDC)),
+                uint(500),
+            ],
+            vec![Token::Address(address(V3_POOL))],
+        )
+        .on_call(address(V3_POOL), "token0()", vec![Token::Address(address(USDC))])
+        .on_call(
+            address(V3_POOL),
+            "slot0()",
+            vec![
+                Token::Uint(U256::from(20_000u64) << 96),
+                Token::Int(U256::zero()),
+                uint(0),
+                uint(1),
+                uint(1),
+                uint(0),
+                Token::Bool(true),
+            ],
+        )
+        .on_call(address(V3_POOL), "liquidity()", vec![uint(20_000_000_000_000_000)])
+        // No initialized ticks near the current one
+        .on_call(address(V3_POOL), "tickBitmap(int16)", vec![uint(0)]);
+}
+
+#[tokio::test]
+async fn reads_all_sources_in_one_pinned_multicall() {
+    let rpc = MockRpc::new();
+    mock_chain(&rpc);
+    let url = rpc.serve().await;
+    let fetcher = PriceFetcher::new(PriceSourceRegistry::from_dexes(&url, &dexes()).unwrap());
+
+    // First cycle also discovers pair/pool addresses
+    fetcher.fetch_all_prices(&[weth_usdc()]).await.unwrap();
+    let calls_before = rpc.count("eth_call");
+
+    let prices = fetcher.fetch_all_prices(&[weth_usdc()]).await.unwrap();
+
+    // One batch for prices, one for the V3 tick bitmap around the current tick
+    assert_eq!(rpc.count("eth_call") - calls_before, 2);
+    assert_eq!(
+        rpc.call_blocks().last().unwrap(),
+        &json!({ "blockHash": format!("{:?}", block_hash()) })
+    );
+
+    let names: Vec<&str> = prices.iter().map(|p| p.dex_name.as_str()).collect();
+    assert_eq!(names, vec!["sushiswap", "uniswap_v3_500"]);
+    assert!(prices.iter().all(|p| (p.price_f64() - 2500.0).abs() < 1e-6));
+
+    // Every price in the cycle carries the block it was read at
+    for price in &prices {
+        let block = price.block.unwrap();
+        assert_eq!(block.number, 42);
+        assert_eq!(block.hash, block_hash());
+        assert_eq!(block.timestamp, 1_700_000_000);
+    }
+
+    // V3 quotes come with the tick bitmap words on either side of tick 0
+    let v3 = prices[1].v3_liquidity.as_ref().unwrap();
+    assert_eq!(v3.loaded_words, Some((-1, 1)));
+    assert_eq!(v3.tick_spacing, 10);
+    assert!(v3.ticks.is_empty());
+}
+
+#[tokio::test]
+async fn pins_reads_to_a_given_head() {
+    let rpc = MockRpc::new();
+    mock_chain(&rpc);
+    let url = rpc.serve().await;
+    let fetcher = PriceFetcher::new(PriceSourceRegistry::from_dexes(&url, &dexes()).unwrap());
+    let head = BlockRef {
+        number: 77,
+        hash: H256::repeat_byte(0x77),
+        timestamp: 1_700_000_100,
+    };
+
+    let prices = fetcher.fetch_all_prices_at(&[weth_usdc()], Some(head)).await.unwrap();
+
+    // The head came from the subscription, so the latest block is never asked for
+    assert_eq!(rpc.count("eth_getBlockByNumber"), 0);
+    assert_eq!(
+        rpc.call_blocks().last().unwrap(),
+        &json!({ "blockHash": format!("{:?}", head.hash) })
+    );
+    assert_eq!(prices.len(), 2);
+    assert!(prices.iter().all(|price| price.block == Some(head)));
+}
+
+#[tokio::test]
+async fn loads_initialized_ticks_at_the_same_block() {
+    let rpc = MockRpc::new();
+    mock_chain(&rpc);
+    // Bit 5 of every loaded word is initialized
+    rpc.on_call(address(V3_POOL), "tickBitmap(int16)", vec![uint(1 << 5)])
+        .on_call(
+            address(V3_POOL),
+            "ticks(int24)",
+            vec![
+                uint(1_000),
+                Token::Int(U256::from(1_000)),
+                uint(0),
+                uint(0),
+                Token::Int(U256::zero()),
+                uint(0),
+                uint(0),
+                Token::Bool(true),
+            ],
+        );
+    let url = rpc.serve().await;
+    let fetcher = PriceFetcher::new(PriceSourceRegistry::from_dexes(&url, &dexes()).unwrap());
+
+    fetcher.fetch_all_prices(&[weth_usdc()]).await.unwrap();
+    let calls_before = rpc.count("eth_call");
+    let prices = fetcher.fetch_all_prices(&[weth_usdc()]).await.unwrap();
+
+    assert_eq!(rpc.count("eth_call") - calls_before, 3);
+    assert!(rpc
+        .call_blocks()
+        .iter()
+        .rev()
+        .take(3)
+        .all(|block| block == &json!({ "blockHash": format!("{:?}", block_hash()) })));
+
+    let v3 = prices[1].v3_liquidity.as_ref().unwrap();
+    let ticks: Vec<(i32, i128)> = v3.ticks.iter().map(|(tick, net)| (*tick, *net)).collect();
+    assert_eq!(ticks, vec![(-2510, 1_000), (50, 1_000), (2610, 1_000)]);
+}
+
+#[tokio::test]
+async fn reverted_reads_only_drop_their_own_source() {
+    let rpc = MockRpc::new();
+    mock_chain(&rpc);
+    // slot0 returns no data for this pool, so decoding its result fails
+    rpc.on_call(address(V3_POOL), "slot0()", vec![]);
+    let url = rpc.serve().await;
+    let fetcher = PriceFetcher::new(PriceSourceRegistry::from_dexes(&url, &dexes()).unwrap());
+
+    let prices = fetcher.fetch_all_prices(&[weth_usdc()]).await.unwrap();
+
+    let names: Vec<&str> = prices.iter().map(|p| p.dex_name.as_str()).collect();
+    assert_eq!(names, vec!["sushiswap"]);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{address, uint, units, MockRpc, USDC};
+use ethers::abi::Token;
+use ethers::providers::Provider;
+use ethers::types::U256;
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageOpportunity;
+use polygon_arbitrage_bot::modules::native_price::{NativePriceOracle, NativePriceSource};
+use polygon_arbitrage_bot::modules::price_fetcher::BlockRef;
+use polygon_arbitrage_bot::modules::profit_calculator::ProfitCalculator;
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use std::sync::Arc;
+
+const FEED: &str = "0xAB594600376Ec9fD91F8e885dADF0CE036862dE0";
+const FACTORY: &str = "0x5757371414417b8C6CAad45bAeF941aBc7d3Ab32";
+const PAIR: &str = "0x6e7a5FAFcec6BB1e78bAE2A1F0B612012BF14827";
+const WMATIC: &str = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270";
+
+const NOW: u128 = 1_700_000_000;
+
+fn block(number: u64) -> BlockRef {
+    BlockRef {
+        number,
+        hash: Default::default(),
+        timestamp: NOW as u64,
+    }
+}
+
+fn mock_feed(rpc: &MockRpc, answer: u64, updated_at: u128) {
+    rpc.on_call(address(FEED), "decimals()", vec![uint(8)])
+        .on_call(
+            address(FEED),
+            "latestRoundData()",
+            vec![
+                uint(42),
+                Token::Int(U256::from(answer)),
+                uint(updated_at),
+                uint(updated_at),
+                uint(42),
+            ],
+        );
+}
+
+// 1,000,000 WMATIC against 550,000 USDC: $0.55
+fn mock_pool(rpc: &MockRpc) {
+    rpc.on_call(
+        address(FACTORY),
+        "getPair(address,address)",
+        vec![Token::Address(address(PAIR))],
+    )
+    .on_call(
+        address(PAIR),
+        "getReserves()",
+        vec![Token::Uint(units(1_000_000, 18)), Token::Uint(units(550_000, 6)), uint(NOW)],
+    )
+    .on_call(address(PAIR), "token0()", vec![Token::Address(address(WMATIC))])
+    .on_token(address(WMATIC), "WMATIC", 18)
+    .on_token(address(USDC), "USDC", 6);
+}
+
+async fn oracle(rpc: &MockRpc) -> NativePriceOracle {
+    let url = rpc.serve().await;
+    NativePriceOracle::new(Arc::new(Provider::new(RpcPool::single(&url).unwrap())))
+        .with_chainlink_feed(FEED)
+        .unwrap()
+        .with_pool(FACTORY, WMATIC, USDC)
+        .unwrap()
+        .with_max_age(3600)
+}
+
+#[tokio::test]
+async fn reads_fresh_chainlink_answer() {
+    let rpc = MockRpc::new();
+    mock_feed(&rpc, 52_000_000, NOW - 60);
+    mock_pool(&rpc);
+    let oracle = oracle(&rpc).await;
+
+    let price = oracle.price(Some(block(100))).await.unwrap();
+
+    assert_eq!(price.source, NativePriceSource::Chainlink);
+    assert_eq!(price.usd, Decimal::new(52, 2));
+    assert_eq!(price.updated_at, Some(NOW as u64 - 60));
+    assert_eq!(price.block_number, Some(100));
+}
+
+#[tokio::test]
+async fn stale_feed_falls_back_to_pool() {
+    let rpc = MockRpc::new();
+    mock_feed(&rpc, 52_000_000, NOW - 7200);
+    mock_pool(&rpc);
+    let oracle = oracle(&rpc).await;
+
+    let price = oracle.price(Some(block(100))).await.unwrap();
+
+    assert_eq!(price.source, NativePriceSource::Pool);
+    assert_eq!(price.usd, Decimal::new(55, 2));
+    assert_eq!(price.updated_at, Some(NOW as u64));
+    assert_eq!(price.block_number, Some(100));
+    // Both the feed round and the reserves were read at the block
+    let pinned = rpc.call_blocks().iter().filter(|tag| **tag == serde_json::json!("0x64")).count();
+    assert_eq!(pinned, 2);
+}
+
+#[tokio::test]
+async fn reuses_last_price_when_both_sources_fail() {
+    let rpc = MockRpc::new();
+    mock_feed(&rpc, 52_000_000, NOW - 60);
+    let oracle = oracle(&rpc).await;
+    let first = oracle.price(Some(block(100))).await.unwrap();
+    assert!(!first.cached);
+
+    // The feed stops answering and there is no pool to fall back on
+    mock_feed(&rpc, 0, NOW);
+    let mut later = block(101);
+    later.timestamp = NOW as u64 + 600;
+    let second = oracle.price(Some(later)).await.unwrap();
+
+    assert!(second.cached);
+    assert_eq!((second.usd, second.source), (first.usd, first.source));
+    assert_eq!(second.label(), "chainlink (cached)");
+
+    // Past the max age the old price is no longer used
+    let mut much_later = block(102);
+    much_later.timestamp = NOW as u64 + 7200;
+    assert!(oracle.price(Some(much_later)).await.is_err());
+}
+
+#[tokio::test]
+async fn errors_without_any_price() {
+    let rpc = MockRpc::new();
+    mock_feed(&rpc, 0, NOW);
+    let oracle = oracle(&rpc).await;
+
+    assert!(oracle.price(Some(block(100))).await.is_err());
+}
+
+#[tokio::test]
+async fn profit_analysis_prices_gas_at_the_oracle_price() {
+    let rpc = MockRpc::new();
+    mock_feed(&rpc, 52_000_000, NOW - 60);
+    let calculator = ProfitCalculator::new().with_native_price_oracle(oracle(&rpc).await);
+    let opportunity = ArbitrageOpportunity {
+        token_pair: "WETH/USDC".to_string(),
+        buy_dex: "sushiswap".to_string(),
+        sell_dex: "quickswap".to_string(),
+        buy_price: amounts::decimal(2500.0),
+        sell_price: amounts::decimal(2550.0),
+        buy_fee: 0.003,
+        sell_fee: 0.003,
+        raw_spread: amounts::decimal(0.02),
+        profit_percentage: amounts::decimal(0.014),
+        profit_usd: amounts::decimal(14.0),
+        trade_size: amounts::decimal(1000.0),
+        amount_in: None,
+        timestamp: 0,
+        block: Some(block(100)),
+        block_span: 0,
+        sizing: None,
+        buy_impact: None,
+        sell_impact: None,
+    };
+
+    let analysis = calculator.calculate_detailed_profit(&opportunity).await.unwrap();
+
+    assert_eq!(analysis.native_price.source, NativePriceSource::Chainlink);
+    // Two swaps of 200k gas at the default 30 gwei, MATIC at $0.52
+    assert_eq!(analysis.gas_costs, Decimal::new(624, 5));
+}
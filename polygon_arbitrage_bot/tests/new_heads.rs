@@ -0,0 +1,75 @@
+mod common;
+
+use common::block_json;
+use common::ws::MockWs;
+use ethers::types::H256;
+use polygon_arbitrage_bot::daemon::CycleTrigger;
+use polygon_arbitrage_bot::modules::new_heads::{Backoff, NewHeads};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::watch;
+
+fn head(number: u64) -> Value {
+    block_json(number, H256::from_low_u64_be(number), H256::from_low_u64_be(number - 1), 1_700_000_000 + number * 2)
+}
+
+fn fast_backoff() -> Backoff {
+    Backoff::new(Duration::from_millis(10), Duration::from_millis(50))
+}
+
+async fn wait_for_head(heads: &NewHeads, number: u64) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while heads.latest().is_none_or(|head| head.number < number) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("no head #{} (latest {:?})", number, heads.latest()));
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap_and_resets() {
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+    let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+    assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn resubscribes_after_the_socket_drops() {
+    let ws = MockWs::new();
+    ws.session(vec![head(100)], true).session(vec![head(101), head(102)], false);
+    let url = ws.serve().await;
+
+    let heads = NewHeads::with_backoff(&url, fast_backoff());
+    wait_for_head(&heads, 102).await;
+
+    let latest = heads.latest().unwrap();
+    assert_eq!(latest.hash, H256::from_low_u64_be(102));
+    assert_eq!(latest.timestamp, 1_700_000_204);
+    assert_eq!(heads.reconnects(), 1);
+    assert_eq!(ws.connections(), 2);
+}
+
+#[tokio::test]
+async fn coalesces_heads_that_arrive_during_a_cycle() {
+    let ws = MockWs::new();
+    ws.session((200..=205).map(head).collect(), false);
+    let url = ws.serve().await;
+    let mut trigger = CycleTrigger::new_heads(NewHeads::with_backoff(&url, fast_backoff()));
+    let (_sender, mut shutdown) = watch::channel(false);
+
+    let first = trigger.wait(&mut shutdown).await.unwrap().unwrap();
+    assert_eq!(first.skipped, 0);
+    let first = first.block.unwrap().number;
+
+    // A cycle long enough for the rest of the heads to arrive
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let next = trigger.wait(&mut shutdown).await.unwrap().unwrap();
+    assert_eq!(next.block.unwrap().number, 205);
+    assert_eq!(next.skipped, 205 - first - 1);
+    assert_eq!(trigger.reconnects(), 0);
+}
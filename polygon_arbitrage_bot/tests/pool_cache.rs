@@ -0,0 +1,374 @@
+mod common;
+
+use common::{address, block_json, uint, units, weth_usdc, MockRpc, USDC, WETH};
+use ethers::abi::{self, Token};
+use ethers::providers::Provider;
+use ethers::types::{Address, Log, H256, I256, U256};
+use polygon_arbitrage_bot::config::DexConfig;
+use polygon_arbitrage_bot::modules::pool_cache::{CachedState, PoolStateCache};
+use polygon_arbitrage_bot::modules::price_fetcher::{BlockRef, PriceFetcher};
+use polygon_arbitrage_bot::modules::price_source::{DexKind, PriceSourceRegistry};
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const V2_FACTORY: &str = "0xc35DADB65012eC5796536bD9864eD8773aBc74C4";
+const V2_PAIR: &str = "0x34965ba0ac2451A34a0471F04CCa3F990b8dea27";
+const V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+const V3_POOL: &str = "0x45dDa9cb7c25131DF268515131f647d726f50608";
+
+fn dexes() -> Vec<DexConfig> {
+    vec![
+        DexConfig {
+            name: "sushiswap".to_string(),
+            kind: DexKind::UniswapV2,
+            router: String::new(),
+            factory: Some(V2_FACTORY.to_string()),
+            fee: None,
+        },
+        DexConfig {
+            name: "uniswap_v3".to_string(),
+            kind: DexKind::UniswapV3,
+            router: String::new(),
+            factory: Some(V3_FACTORY.to_string()),
+            fee: None,
+        },
+    ]
+}
+
+// Both pools at 2500 USDC/WETH as of block 42
+fn mock_chain(rpc: &MockRpc) {
+    rpc.on_method("eth_getBlockByNumber", block_json(42, hash(42), H256::zero(), 1_700_000_000))
+        .on_method("eth_chainId", json!("0x89"))
+        .on_token(address(WETH), "WETH", 18)
+        .on_token(address(USDC), "USDC", 6)
+        .on_call(
+            address(V2_FACTORY),
+            "getPair(address,address)",
+            vec![Token::Address(address(V2_PAIR))],
+        )
+        .on_call(address(V2_PAIR), "token0()", vec![Token::Address(address(USDC))])
+        .on_call(
+            address(V2_PAIR),
+            "getReserves()",
+            vec![
+                Token::Uint(units(250_000, 6)),
+                Token::Uint(units(100, 18)),
+                uint(0),
+            ],
+        )
+        .on_call(
+            address(V3_FACTORY),
+            "getPool(address,address,uint24)",
+            vec![Token::Address(Default::default())],
+        )
+        .on_call_with(
+            address(V3_FACTORY),
+            "getPool(address,address,uint24)",
+            vec![
+                Token::Address(address(WETH)),
+                Token::Address(address(USDC)),
+                uint(500),
+            ],
+            vec![Token::Address(address(V3_POOL))],
+        )
+        .on_call(address(V3_POOL), "token0()", vec![Token::Address(address(USDC))])
+        .on_call(
+            address(V3_POOL),
+            "slot0()",
+            vec![
+                Token::Uint(U256::from(20_000u64) << 96),
+                Token::Int(U256::zero()),
+                uint(0),
+                uint(1),
+                uint(1),
+                uint(0),
+                Token::Bool(true),
+            ],
+        )
+        .on_call(address(V3_POOL), "liquidity()", vec![uint(20_000_000_000_000_000)])
+        .on_call(address(V3_POOL), "tickBitmap(int16)", vec![uint(0)]);
+}
+
+fn hash(number: u64) -> H256 {
+    H256::from_low_u64_be(number)
+}
+
+fn head(number: u64) -> BlockRef {
+    BlockRef {
+        number,
+        hash: hash(number),
+        timestamp: 1_700_000_000 + number,
+    }
+}
+
+fn int_topic(value: i32) -> H256 {
+    let mut bytes = [0u8; 32];
+    I256::from(value).into_raw().to_big_endian(&mut bytes);
+    H256(bytes)
+}
+
+fn int(value: i64) -> Token {
+    Token::Int(I256::from(value).into_raw())
+}
+
+fn log(pool: &str, signature: &str, mut topics: Vec<H256>, data: Vec<Token>, block: BlockRef) -> Log {
+    topics.insert(0, H256(ethers::utils::keccak256(signature)));
+    Log {
+        address: address(pool),
+        topics,
+        data: abi::encode(&data).into(),
+        block_number: Some(block.number.into()),
+        block_hash: Some(block.hash),
+        log_index: Some(U256::zero()),
+        removed: Some(false),
+        ..Default::default()
+    }
+}
+
+fn sync(pool: &str, reserve0: U256, reserve1: U256, block: BlockRef) -> Log {
+    log(pool, "Sync(uint112,uint112)", vec![], vec![Token::Uint(reserve0), Token::Uint(reserve1)], block)
+}
+
+fn swap(pool: &str, sqrt_price_x96: U256, liquidity: u128, tick: i32, block: BlockRef) -> Log {
+    log(
+        pool,
+        "Swap(address,address,int256,int256,uint160,uint128,int24)",
+        vec![H256::zero(), H256::zero()],
+        vec![int(1_000), int(-990), Token::Uint(sqrt_price_x96), uint(liquidity), int(tick as i64)],
+        block,
+    )
+}
+
+fn mint(pool: &str, tick_lower: i32, tick_upper: i32, amount: u128, block: BlockRef) -> Log {
+    log(
+        pool,
+        "Mint(address,address,int24,int24,uint128,uint256,uint256)",
+        vec![H256::zero(), int_topic(tick_lower), int_topic(tick_upper)],
+        vec![Token::Address(Address::zero()), uint(amount), uint(0), uint(0)],
+        block,
+    )
+}
+
+fn burn(pool: &str, tick_lower: i32, tick_upper: i32, amount: u128, block: BlockRef) -> Log {
+    log(
+        pool,
+        "Burn(address,int24,int24,uint128,uint256,uint256)",
+        vec![H256::zero(), int_topic(tick_lower), int_topic(tick_upper)],
+        vec![uint(amount), uint(0), uint(0)],
+        block,
+    )
+}
+
+fn reserves(cache: &PoolStateCache, pool: &str) -> (u128, u128) {
+    match cache.get(address(pool)).unwrap().state {
+        CachedState::V2 { reserve0, reserve1 } => (reserve0, reserve1),
+        state => panic!("not a V2 pool: {:?}", state),
+    }
+}
+
+#[test]
+fn applies_sync_logs_newer_than_the_seed() {
+    let cache = PoolStateCache::new();
+    assert!(cache.seed(address(V2_PAIR), CachedState::V2 { reserve0: 100, reserve1: 200 }, head(10)));
+    assert_eq!(cache.head(), Some(head(10)));
+
+    // Already part of the seeded read
+    assert!(!cache.apply_log(&sync(V2_PAIR, 1.into(), 2.into(), head(10))).unwrap());
+    assert!(cache.apply_log(&sync(V2_PAIR, 300.into(), 400.into(), head(11))).unwrap());
+    // Pools that were never seeded are ignored
+    assert!(!cache.apply_log(&sync(V3_POOL, 1.into(), 2.into(), head(11))).unwrap());
+
+    assert_eq!(reserves(&cache, V2_PAIR), (300, 400));
+    assert_eq!(cache.get(address(V2_PAIR)).unwrap().updated_at, 11);
+
+    // A read older than the head would miss the logs in between
+    assert!(!cache.seed(address(V3_POOL), CachedState::V2 { reserve0: 1, reserve1: 1 }, head(9)));
+}
+
+#[test]
+fn tracks_v3_swaps_mints_and_burns() {
+    let cache = PoolStateCache::new();
+    cache.seed(
+        address(V3_POOL),
+        CachedState::V3 {
+            sqrt_price_x96: U256::one() << 96,
+            tick: 0,
+            liquidity: 1_000,
+            tick_spacing: 60,
+            ticks: BTreeMap::from([(-600, 500), (600, -500)]),
+            loaded_words: Some((-1, 0)),
+        },
+        head(10),
+    );
+
+    cache.apply_log(&mint(V3_POOL, -120, 120, 300, head(11))).unwrap();
+    cache.apply_log(&burn(V3_POOL, -600, 600, 500, head(11))).unwrap();
+    // In range, but its ticks are outside the loaded words
+    cache.apply_log(&mint(V3_POOL, -60_000, 60_000, 7, head(11))).unwrap();
+    // Out of range: ticks only
+    cache.apply_log(&mint(V3_POOL, 60, 180, 50, head(11))).unwrap();
+
+    let pool = cache.get(address(V3_POOL)).unwrap();
+    let CachedState::V3 { liquidity, ticks, .. } = &pool.state else {
+        panic!("not a V3 pool");
+    };
+    assert_eq!(*liquidity, 1_000 + 300 - 500 + 7);
+    assert_eq!(ticks, &BTreeMap::from([(-120, 300), (60, 50), (120, -300), (180, -50)]));
+    assert!(pool.ticks_current());
+
+    let sqrt_price = U256::from(79_500_000_000_000_000_000_000_000_000u128);
+    cache.apply_log(&swap(V3_POOL, sqrt_price, 42, 61, head(12))).unwrap();
+    let pool = cache.get(address(V3_POOL)).unwrap();
+    let CachedState::V3 { sqrt_price_x96, tick, liquidity, .. } = pool.state else {
+        panic!("not a V3 pool");
+    };
+    assert_eq!((sqrt_price_x96, tick, liquidity), (sqrt_price, 61, 42));
+    assert!(pool.ticks_current());
+
+    // Past the loaded words the pool needs a fresh tick read
+    cache.apply_log(&swap(V3_POOL, sqrt_price, 42, 20_000, head(13))).unwrap();
+    assert!(!cache.get(address(V3_POOL)).unwrap().ticks_current());
+}
+
+#[test]
+fn removed_logs_roll_back_to_the_previous_head() {
+    let cache = PoolStateCache::new();
+    cache.seed(address(V2_PAIR), CachedState::V2 { reserve0: 100, reserve1: 100 }, head(10));
+    cache.apply_log(&sync(V2_PAIR, 110.into(), 110.into(), head(11))).unwrap();
+    cache.advance(head(11));
+    cache.apply_log(&sync(V2_PAIR, 120.into(), 120.into(), head(12))).unwrap();
+    cache.advance(head(12));
+    cache.seed(address(V3_POOL), CachedState::V2 { reserve0: 1, reserve1: 1 }, head(12));
+
+    let mut removed = sync(V2_PAIR, 120.into(), 120.into(), head(12));
+    removed.removed = Some(true);
+    assert!(cache.apply_log(&removed).unwrap());
+
+    assert_eq!(cache.head(), Some(head(11)));
+    assert_eq!(reserves(&cache, V2_PAIR), (110, 110));
+    // Seeded on the orphaned block, so it has to be read again
+    assert!(cache.get(address(V3_POOL)).is_none());
+
+    // Deeper than the undo history: nothing can be trusted
+    let cache = PoolStateCache::new().with_depth(2);
+    cache.seed(address(V2_PAIR), CachedState::V2 { reserve0: 100, reserve1: 100 }, head(10));
+    for number in 11..=14 {
+        cache.apply_log(&sync(V2_PAIR, number.into(), number.into(), head(number))).unwrap();
+        cache.advance(head(number));
+    }
+    assert_eq!(cache.rollback_to(12), Some(head(12)));
+    assert_eq!(reserves(&cache, V2_PAIR), (12, 12));
+    assert_eq!(cache.rollback_to(11), None);
+    assert!(cache.is_empty());
+    assert_eq!(cache.head(), None);
+}
+
+#[tokio::test]
+async fn serves_synced_quotes_without_rpc_calls() {
+    let rpc = MockRpc::new();
+    mock_chain(&rpc);
+    // USDC is token0: 240k USDC against 100 WETH
+    let update = sync(V2_PAIR, units(240_000, 6), units(100, 18), head(43));
+    rpc.on_method("eth_getLogs", json!([update]));
+    let url = rpc.serve().await;
+
+    let cache = Arc::new(PoolStateCache::new());
+    let registry = PriceSourceRegistry::from_dexes_cached(&url, &dexes(), cache.clone()).unwrap();
+    let client = registry.client().unwrap();
+    let fetcher = PriceFetcher::new(registry);
+
+    // The first cycle reads and seeds both pools
+    fetcher.fetch_all_prices(&[weth_usdc()]).await.unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.head().unwrap().number, 42);
+
+    let report = cache.sync_to(&client, head(43)).await.unwrap();
+    assert_eq!((report.logs, report.applied, report.rolled_back_to), (1, 1, None));
+
+    let calls_before = rpc.count("eth_call");
+    let prices = fetcher.fetch_all_prices_at(&[weth_usdc()], Some(head(43))).await.unwrap();
+    assert_eq!(rpc.count("eth_call"), calls_before);
+
+    let names: Vec<&str> = prices.iter().map(|p| p.dex_name.as_str()).collect();
+    assert_eq!(names, vec!["sushiswap", "uniswap_v3_500"]);
+    assert!((prices[0].price_f64() - 2400.0).abs() < 1e-6);
+    assert!((prices[1].price_f64() - 2500.0).abs() < 1e-6);
+    assert!(prices.iter().all(|price| price.block == Some(head(43))));
+    assert_eq!(prices[1].v3_liquidity.as_ref().unwrap().loaded_words, Some((-1, 1)));
+
+    // A head the cache was not synced to is read from the node
+    fetcher.fetch_all_prices_at(&[weth_usdc()], Some(head(44))).await.unwrap();
+    assert!(rpc.count("eth_call") > calls_before);
+}
+
+// Block hashes per chain version: 1 replaces blocks from 41 on, 2 replaces all
+fn versioned_hash(version: u64, number: u64) -> H256 {
+    match version {
+        1 if number >= 41 => H256::from_low_u64_be(0xaa00 + number),
+        2 => H256::from_low_u64_be(0xbb00 + number),
+        _ => hash(number),
+    }
+}
+
+fn versioned_head(version: u64, number: u64) -> BlockRef {
+    BlockRef {
+        hash: versioned_hash(version, number),
+        ..head(number)
+    }
+}
+
+fn block_number(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+#[tokio::test]
+async fn sync_rolls_back_to_the_last_canonical_head() {
+    let rpc = MockRpc::new();
+    let version = Arc::new(AtomicU64::new(0));
+    let chain = version.clone();
+    rpc.fallback(move |method, params| {
+        let version = chain.load(Ordering::SeqCst);
+        match method {
+            "eth_getBlockByNumber" => {
+                let number = block_number(&params[0]);
+                Some(block_json(number, versioned_hash(version, number), H256::zero(), 0))
+            }
+            "eth_getLogs" => {
+                let (from, to) = (block_number(&params[0]["fromBlock"]), block_number(&params[0]["toBlock"]));
+                let logs: Vec<Log> = (from..=to)
+                    .map(|number| {
+                        let reserve = U256::from(version * 100 + number);
+                        sync(V2_PAIR, reserve, reserve, versioned_head(version, number))
+                    })
+                    .collect();
+                Some(json!(logs))
+            }
+            _ => None,
+        }
+    });
+    let client = Provider::new(RpcPool::single(&rpc.serve().await).unwrap());
+
+    let cache = PoolStateCache::new();
+    cache.seed(address(V2_PAIR), CachedState::V2 { reserve0: 1, reserve1: 1 }, head(40));
+    cache.sync_to(&client, head(41)).await.unwrap();
+    assert_eq!(reserves(&cache, V2_PAIR), (41, 41));
+
+    // Block 41 is replaced; 40 is still canonical
+    version.store(1, Ordering::SeqCst);
+    let report = cache.sync_to(&client, versioned_head(1, 42)).await.unwrap();
+    assert_eq!(report.rolled_back_to, Some(40));
+    assert_eq!((report.logs, report.applied), (2, 2));
+    assert_eq!(reserves(&cache, V2_PAIR), (142, 142));
+    assert_eq!(cache.head(), Some(versioned_head(1, 42)));
+
+    // Nothing the cache saw survives, so the pools must be read again
+    version.store(2, Ordering::SeqCst);
+    let report = cache.sync_to(&client, versioned_head(2, 43)).await.unwrap();
+    assert!(report.reset);
+    assert!(cache.is_empty());
+    assert_eq!(cache.head(), Some(versioned_head(2, 43)));
+    assert_eq!(rpc.count("eth_getLogs"), 2);
+}
\ No newline at end of file
@@ -0,0 +1,303 @@
+mod common;
+
+use common::{address, units, MockRpc, USDC, WETH};
+use ethers::abi::{self, Token};
+use ethers::providers::Provider;
+use ethers::types::{Address, Log, H256, U256};
+use polygon_arbitrage_bot::config::{DexConfig, DiscoveryToken, TokenPair};
+use polygon_arbitrage_bot::modules::amounts::Decimal;
+use polygon_arbitrage_bot::modules::pool_discovery::{DiscoveredMarkets, DiscoveredPool, PoolDiscovery};
+use polygon_arbitrage_bot::modules::price_fetcher::{BlockRef, PriceData};
+use polygon_arbitrage_bot::modules::price_source::DexKind;
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use polygon_arbitrage_bot::modules::token_registry::TokenRegistry;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const V2_FACTORY: &str = "0xc35DADB65012eC5796536bD9864eD8773aBc74C4";
+const V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+const WMATIC: &str = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270";
+const DAI: &str = "0x8f3Cf7ad23Cd3CaDbD9735AFf958023239c6A063";
+
+fn pool(n: u64) -> Address {
+    Address::from_low_u64_be(0x9000 + n)
+}
+
+fn head(number: u64) -> BlockRef {
+    BlockRef {
+        number,
+        hash: H256::from_low_u64_be(number),
+        timestamp: 1_700_000_000 + number,
+    }
+}
+
+fn dexes() -> Vec<DexConfig> {
+    vec![
+        DexConfig {
+            name: "sushiswap".to_string(),
+            kind: DexKind::UniswapV2,
+            router: String::new(),
+            factory: Some(V2_FACTORY.to_string()),
+            fee: None,
+        },
+        DexConfig {
+            name: "uniswap_v3".to_string(),
+            kind: DexKind::UniswapV3,
+            router: String::new(),
+            factory: Some(V3_FACTORY.to_string()),
+            fee: None,
+        },
+    ]
+}
+
+fn whitelist() -> Vec<DiscoveryToken> {
+    [(USDC, "USDC", 1_000.0), (WETH, "WETH", 1.0), (WMATIC, "WMATIC", 1_000.0)]
+        .into_iter()
+        .map(|(address, symbol, min_liquidity)| DiscoveryToken {
+            symbol: symbol.to_string(),
+            address: address.to_string(),
+            min_liquidity,
+        })
+        .collect()
+}
+
+fn pair(base: &str, quote: &str, base_address: &str, quote_address: &str) -> TokenPair {
+    TokenPair {
+        base: base.to_string(),
+        quote: quote.to_string(),
+        base_address: base_address.to_string(),
+        quote_address: quote_address.to_string(),
+    }
+}
+
+fn topic(token: &str) -> H256 {
+    H256::from(address(token))
+}
+
+fn pair_created(token0: &str, token1: &str, pair: Address, block: u64) -> Log {
+    Log {
+        address: address(V2_FACTORY),
+        topics: vec![
+            H256(ethers::utils::keccak256("PairCreated(address,address,address,uint256)")),
+            topic(token0),
+            topic(token1),
+        ],
+        data: abi::encode(&[Token::Address(pair), Token::Uint(U256::one())]).into(),
+        block_number: Some(block.into()),
+        removed: Some(false),
+        ..Default::default()
+    }
+}
+
+fn pool_created(token0: &str, token1: &str, fee: u32, pool: Address, block: u64) -> Log {
+    Log {
+        address: address(V3_FACTORY),
+        topics: vec![
+            H256(ethers::utils::keccak256("PoolCreated(address,address,uint24,int24,address)")),
+            topic(token0),
+            topic(token1),
+            H256::from_low_u64_be(fee.into()),
+        ],
+        data: abi::encode(&[Token::Int(U256::from(60)), Token::Address(pool)]).into(),
+        block_number: Some(block.into()),
+        removed: Some(false),
+        ..Default::default()
+    }
+}
+
+fn block_param(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+/// Serves `logs` from `eth_getLogs` by block range, recording each range asked for.
+fn serve_logs(rpc: &MockRpc, logs: Arc<Mutex<Vec<Log>>>) -> Arc<Mutex<Vec<(u64, u64)>>> {
+    let ranges = Arc::new(Mutex::new(Vec::new()));
+    let seen = ranges.clone();
+    rpc.fallback(move |method, params| {
+        if method != "eth_getLogs" {
+            return None;
+        }
+        let (from, to) = (block_param(&params[0]["fromBlock"]), block_param(&params[0]["toBlock"]));
+        seen.lock().unwrap().push((from, to));
+        let logs = logs.lock().unwrap();
+        let matching: Vec<&Log> = logs
+            .iter()
+            .filter(|log| (from..=to).contains(&log.block_number.unwrap().as_u64()))
+            .collect();
+        Some(json!(matching))
+    });
+    ranges
+}
+
+fn balance(rpc: &MockRpc, token: &str, pool: Address, amount: U256) {
+    rpc.on_call_with(address(token), "balanceOf(address)", vec![Token::Address(pool)], vec![Token::Uint(amount)]);
+}
+
+async fn discovery(rpc: &MockRpc) -> PoolDiscovery {
+    rpc.on_token(address(WETH), "WETH", 18)
+        .on_token(address(USDC), "USDC", 6)
+        .on_token(address(WMATIC), "WMATIC", 18);
+    let client = Arc::new(Provider::new(RpcPool::single(&rpc.serve().await).unwrap()));
+    let tokens = Arc::new(TokenRegistry::new(client.clone()));
+    PoolDiscovery::new(client, tokens, &dexes(), &whitelist())
+        .unwrap()
+        .with_configured_pairs(&[pair("WETH", "USDC", WETH, USDC)])
+        .with_backfill(5_000)
+}
+
+fn quote(dex: &str, token_pair: &str) -> PriceData {
+    PriceData {
+        dex_name: dex.to_string(),
+        token_pair: token_pair.to_string(),
+        price: Decimal::ONE,
+        timestamp: 0,
+        liquidity: Decimal::ONE,
+        fee: 0.003,
+        block: None,
+        reserves: None,
+        v3_liquidity: None,
+    }
+}
+
+fn discovered(dex: &str, fee: Option<u32>, pair: TokenPair, enabled: bool) -> DiscoveredPool {
+    DiscoveredPool {
+        pool: pool(1),
+        dex: dex.to_string(),
+        pair,
+        fee,
+        created_block: 1,
+        base_balance: Decimal::ONE,
+        quote_balance: Decimal::ONE,
+        enabled,
+    }
+}
+
+#[tokio::test]
+async fn backfill_keeps_deep_pools_between_whitelisted_tokens() {
+    let rpc = MockRpc::new();
+    let logs = Arc::new(Mutex::new(vec![
+        pair_created(USDC, WETH, pool(1), 5_100),
+        // DAI is not whitelisted
+        pair_created(WETH, DAI, pool(2), 6_000),
+        pool_created(WMATIC, WETH, 3000, pool(3), 7_000),
+        pool_created(USDC, WMATIC, 500, pool(4), 9_999),
+    ]));
+    let ranges = serve_logs(&rpc, logs);
+    balance(&rpc, USDC, pool(1), units(250_000, 6));
+    balance(&rpc, WETH, pool(1), units(100, 18));
+    // Under the 1000 WMATIC minimum
+    balance(&rpc, WMATIC, pool(3), units(500, 18));
+    balance(&rpc, WETH, pool(3), units(2, 18));
+    balance(&rpc, USDC, pool(4), units(5_000, 6));
+    balance(&rpc, WMATIC, pool(4), units(10_000, 18));
+    let mut discovery = discovery(&rpc).await;
+
+    let report = discovery.scan_to(head(10_000)).await.unwrap();
+
+    assert_eq!((report.from_block, report.to_block), (5_000, 10_000));
+    assert_eq!(report.created, 4);
+    assert_eq!(report.too_shallow, 1);
+    assert_eq!(discovery.scanned_to(), Some(10_000));
+    // 5001 blocks in chunks of at most 1000
+    let ranges = ranges.lock().unwrap().clone();
+    assert_eq!(ranges.len(), 6);
+    assert_eq!(ranges.first(), Some(&(5_000, 5_999)));
+    assert_eq!(ranges.last(), Some(&(10_000, 10_000)));
+
+    let [sushi, v3] = report.pools.as_slice() else {
+        panic!("expected two pools, got {:?}", report.pools);
+    };
+    // Oriented like the configured pair
+    assert_eq!(sushi.pool, pool(1));
+    assert_eq!(sushi.token_pair(), "WETH/USDC");
+    assert_eq!(sushi.market(), "sushiswap");
+    assert_eq!(sushi.created_block, 5_100);
+    assert_eq!(sushi.base_balance, Decimal::from(100));
+    assert_eq!(sushi.quote_balance, Decimal::from(250_000));
+    // Unconfigured pairs quote in the earlier whitelisted token
+    assert_eq!(v3.token_pair(), "WMATIC/USDC");
+    assert_eq!(v3.market(), "uniswap_v3_500");
+    assert_eq!(v3.pair.base_address, WMATIC);
+    assert_eq!(v3.pair.quote_address, USDC);
+    assert!(v3.enabled);
+}
+
+#[tokio::test]
+async fn later_passes_continue_from_the_last_block() {
+    let rpc = MockRpc::new();
+    let logs = Arc::new(Mutex::new(vec![pair_created(USDC, WETH, pool(1), 9_990)]));
+    let ranges = serve_logs(&rpc, logs.clone());
+    balance(&rpc, USDC, pool(1), units(250_000, 6));
+    balance(&rpc, WETH, pool(1), units(100, 18));
+    balance(&rpc, WETH, pool(5), units(3, 18));
+    balance(&rpc, WMATIC, pool(5), units(20_000, 18));
+    let mut discovery = discovery(&rpc).await.with_backfill(100);
+
+    assert_eq!(discovery.scan_to(head(10_000)).await.unwrap().pools.len(), 1);
+
+    logs.lock().unwrap().push(pool_created(WETH, WMATIC, 3000, pool(5), 10_005));
+    let report = discovery.scan_to(head(10_010)).await.unwrap();
+    assert_eq!((report.from_block, report.to_block), (10_001, 10_010));
+    assert_eq!(report.pools.len(), 1);
+    assert_eq!(report.pools[0].token_pair(), "WMATIC/WETH");
+
+    // Nothing new to search at the same head
+    assert!(discovery.scan_to(head(10_010)).await.unwrap().pools.is_empty());
+    assert_eq!(ranges.lock().unwrap().clone(), vec![(9_900, 10_000), (10_001, 10_010)]);
+}
+
+#[tokio::test]
+async fn failed_passes_are_retried_from_the_same_block() {
+    let rpc = MockRpc::new();
+    let mut discovery = discovery(&rpc).await.with_backfill(100);
+
+    // No eth_getLogs mock: the node errors
+    assert!(discovery.scan_to(head(10_000)).await.is_err());
+    assert_eq!(discovery.scanned_to(), None);
+
+    serve_logs(&rpc, Arc::new(Mutex::new(Vec::new())));
+    let report = discovery.scan_to(head(10_000)).await.unwrap();
+    assert_eq!(report.from_block, 9_900);
+}
+
+#[test]
+fn discovered_markets_gate_quotes() {
+    let configured = [pair("WETH", "USDC", WETH, USDC)];
+    let wmatic_usdc = pair("WMATIC", "USDC", WMATIC, USDC);
+    let markets = DiscoveredMarkets::from_pools(
+        &configured,
+        &[
+            discovered("sushiswap", None, configured[0].clone(), true),
+            discovered("uniswap_v3", Some(3000), configured[0].clone(), false),
+            discovered("uniswap_v3", Some(500), wmatic_usdc.clone(), true),
+            discovered("sushiswap", None, wmatic_usdc.clone(), true),
+        ],
+    );
+
+    assert_eq!(markets.pairs().len(), 1);
+    assert_eq!(markets.pairs()[0].base, "WMATIC");
+
+    // Configured pairs keep every market but disabled pools
+    assert!(markets.allows(&quote("uniswap_v3_500", "WETH/USDC")));
+    assert!(!markets.allows(&quote("uniswap_v3_3000", "WETH/USDC")));
+    // Discovered pairs are quoted only where a pool was found
+    assert!(markets.allows(&quote("uniswap_v3_500", "WMATIC/USDC")));
+    assert!(markets.allows(&quote("sushiswap", "WMATIC/USDC")));
+    assert!(!markets.allows(&quote("uniswap_v3_3000", "WMATIC/USDC")));
+}
+
+#[test]
+fn disabling_a_pool_removes_its_market() {
+    let wmatic_usdc = pair("WMATIC", "USDC", WMATIC, USDC);
+    let mut markets = DiscoveredMarkets::new(&[]);
+    let mut pool = discovered("sushiswap", None, wmatic_usdc, true);
+
+    assert_eq!(markets.insert(&pool).map(|pair| pair.base), Some("WMATIC".to_string()));
+    // Already monitored
+    assert!(markets.insert(&pool).is_none());
+    assert!(markets.allows(&quote("sushiswap", "WMATIC/USDC")));
+
+    pool.enabled = false;
+    markets.insert(&pool);
+    assert!(!markets.allows(&quote("sushiswap", "WMATIC/USDC")));
+}
\ No newline at end of file
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use polygon_arbitrage_bot::config::{DexConfig, TokenPair};
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal};
+use polygon_arbitrage_bot::modules::price_fetcher::{PriceData, PriceFetcher};
+use polygon_arbitrage_bot::modules::price_source::{DexKind, PriceSource, PriceSourceRegistry};
+
+struct FixedSource {
+    name: &'static str,
+    price: f64,
+}
+
+#[async_trait]
+impl PriceSource for FixedSource {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn kind(&self) -> DexKind {
+        DexKind::Simulated
+    }
+
+    async fn quote(&self, pair: &TokenPair) -> Result<Vec<PriceData>> {
+        Ok(vec![PriceData {
+            dex_name: self.name.to_string(),
+            token_pair: format!("{}/{}", pair.base, pair.quote),
+            price: amounts::decimal(self.price),
+            timestamp: 0,
+            liquidity: Decimal::from(1_000),
+            fee: 0.003,
+            block: None,
+            reserves: None,
+            v3_liquidity: None,
+        }])
+    }
+}
+
+struct FailingSource;
+
+#[async_trait]
+impl PriceSource for FailingSource {
+    fn name(&self) -> &str {
+        "broken"
+    }
+
+    fn kind(&self) -> DexKind {
+        DexKind::Simulated
+    }
+
+    async fn quote(&self, _pair: &TokenPair) -> Result<Vec<PriceData>> {
+        Err(anyhow!("RPC unavailable"))
+    }
+}
+
+fn pair(base: &str) -> TokenPair {
+    TokenPair {
+        base: base.to_string(),
+        quote: "USDC".to_string(),
+        base_address: String::new(),
+        quote_address: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn fetches_from_every_registered_source() {
+    let mut registry = PriceSourceRegistry::new();
+    registry.register(Box::new(FixedSource { name: "alpha", price: 10.0 }));
+    registry.register(Box::new(FailingSource));
+    registry.register(Box::new(FixedSource { name: "beta", price: 11.0 }));
+    let fetcher = PriceFetcher::new(registry);
+
+    let prices = fetcher
+        .fetch_all_prices(&[pair("WETH"), pair("WBTC")])
+        .await
+        .unwrap();
+
+    // The failing source is skipped, the others quote both pairs
+    let quotes: Vec<(&str, &str, f64)> = prices
+        .iter()
+        .map(|p| (p.dex_name.as_str(), p.token_pair.as_str(), p.price_f64()))
+        .collect();
+    assert_eq!(
+        quotes,
+        vec![
+            ("alpha", "WETH/USDC", 10.0),
+            ("beta", "WETH/USDC", 11.0),
+            ("alpha", "WBTC/USDC", 10.0),
+            ("beta", "WBTC/USDC", 11.0),
+        ]
+    );
+}
+
+#[test]
+fn looks_up_sources_by_name() {
+    let mut registry = PriceSourceRegistry::new();
+    registry.register(Box::new(FixedSource { name: "alpha", price: 1.0 }));
+
+    assert_eq!(registry.len(), 1);
+    assert_eq!(registry.get("alpha").unwrap().kind(), DexKind::Simulated);
+    assert!(registry.get("missing").is_none());
+}
+
+
+fn dex(name: &str, kind: DexKind, fee: Option<f64>) -> DexConfig {
+    DexConfig {
+        name: name.to_string(),
+        kind,
+        router: String::new(),
+        factory: Some("0x5757371414417b8C6CAad45bAeF941aBc7d3Ab32".to_string()),
+        fee,
+    }
+}
+
+#[test]
+fn sources_carry_their_configured_fees() {
+    let registry = PriceSourceRegistry::from_dexes(
+        "http://localhost:8545",
+        &[
+            dex("quickswap", DexKind::UniswapV2, None),
+            dex("apeswap", DexKind::UniswapV2, Some(0.002)),
+            dex("uniswap_v3", DexKind::UniswapV3, None),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(registry.get("quickswap").unwrap().swap_fee(), Some(0.003));
+    assert_eq!(registry.get("apeswap").unwrap().swap_fee(), Some(0.002));
+    // V3 fees depend on the pool's tier
+    assert_eq!(registry.get("uniswap_v3").unwrap().swap_fee(), None);
+}
+
+#[test]
+fn rejects_invalid_fee_config() {
+    let url = "http://localhost:8545";
+
+    assert!(PriceSourceRegistry::from_dexes(url, &[dex("fork", DexKind::UniswapV2, Some(1.5))]).is_err());
+    assert!(PriceSourceRegistry::from_dexes(url, &[dex("uniswap_v3", DexKind::UniswapV3, Some(0.003))]).is_err());
+}
+
+#[tokio::test]
+async fn simulated_source_quotes_without_a_node() {
+    // Nothing listens on port 1
+    let registry = PriceSourceRegistry::from_dexes("http://127.0.0.1:1", &[dex("sushiswap", DexKind::Simulated, None)]).unwrap();
+    let pair = TokenPair {
+        base: "WETH".to_string(),
+        quote: "USDC".to_string(),
+        base_address: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".to_string(),
+        quote_address: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_string(),
+    };
+
+    let prices = registry.get("sushiswap").unwrap().quote(&pair).await.unwrap();
+
+    assert!(registry.client().is_none());
+    assert_eq!(prices[0].price_f64(), 2505.0);
+    assert_eq!(prices[0].reserves.unwrap().base.decimals, 18);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{block_json, MockRpc};
+use ethers::providers::Provider;
+use ethers::types::H256;
+use polygon_arbitrage_bot::modules::reorg::{ChainLink, ReorgDetector};
+use polygon_arbitrage_bot::modules::rpc_pool::{RpcClient, RpcPool};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// Block hashes per chain version: 1 replaces blocks from 101 on, 2 replaces all
+fn hash(version: u64, number: u64) -> H256 {
+    match version {
+        1 if number >= 101 => H256::from_low_u64_be(0xaa00 + number),
+        2 => H256::from_low_u64_be(0xbb00 + number),
+        _ => H256::from_low_u64_be(number),
+    }
+}
+
+fn link(version: u64, number: u64) -> ChainLink {
+    ChainLink {
+        number,
+        hash: hash(version, number),
+        parent_hash: hash(version, number - 1),
+    }
+}
+
+fn block_number(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+/// A node answering `eth_getBlockByNumber` from whichever chain version is current.
+async fn chain() -> (MockRpc, Arc<AtomicU64>, Arc<RpcClient>) {
+    let rpc = MockRpc::new();
+    let version = Arc::new(AtomicU64::new(0));
+    let current = version.clone();
+    rpc.fallback(move |method, params| {
+        let number = (method == "eth_getBlockByNumber").then(|| block_number(&params[0]))?;
+        let version = current.load(Ordering::SeqCst);
+        Some(block_json(number, hash(version, number), hash(version, number - 1), 0))
+    });
+    let client = Arc::new(Provider::new(RpcPool::single(&rpc.serve().await).unwrap()));
+    (rpc, version, client)
+}
+
+#[tokio::test]
+async fn follows_a_linear_chain_without_rpc_calls() {
+    let (rpc, _, client) = chain().await;
+    let mut detector = ReorgDetector::new(client);
+
+    for number in 100..=102 {
+        assert!(detector.observe(link(0, number)).await.unwrap().is_none());
+    }
+    // The same head twice is not news
+    assert!(detector.observe(link(0, 102)).await.unwrap().is_none());
+
+    assert_eq!(detector.recent().len(), 3);
+    assert!(rpc.requests().is_empty());
+}
+
+#[tokio::test]
+async fn reports_blocks_replaced_by_a_new_branch() {
+    let (_rpc, version, client) = chain().await;
+    let mut detector = ReorgDetector::new(client);
+    let mut events = detector.subscribe();
+    for number in 100..=102 {
+        detector.observe(link(0, number)).await.unwrap();
+    }
+
+    version.store(1, Ordering::SeqCst);
+    let event = detector.observe(link(1, 102)).await.unwrap().unwrap();
+
+    assert_eq!(event.common_ancestor, Some(100));
+    assert_eq!(event.orphaned, vec![link(0, 101), link(0, 102)]);
+    assert_eq!(event.new_head, link(1, 102));
+    assert_eq!(event.orphaned_hashes(), vec![hash(0, 101), hash(0, 102)]);
+    // The new branch continues from its own head
+    assert_eq!(detector.recent(), vec![link(0, 100), link(1, 102)]);
+    assert!(detector.observe(link(1, 103)).await.unwrap().is_none());
+
+    detector.publish(&event);
+    assert_eq!(events.recv().await.unwrap().orphaned.len(), 2);
+}
+
+#[tokio::test]
+async fn gaps_and_lagging_heads_are_not_reorgs() {
+    let (rpc, _, client) = chain().await;
+    let mut detector = ReorgDetector::new(client);
+    detector.observe(link(0, 100)).await.unwrap();
+
+    // Blocks 101-104 were never seen; 100 is still canonical
+    assert!(detector.observe(link(0, 105)).await.unwrap().is_none());
+    assert_eq!(rpc.count("eth_getBlockByNumber"), 1);
+
+    // An older head from a node that is behind changes nothing
+    let behind = ChainLink {
+        hash: H256::repeat_byte(0x99),
+        ..link(0, 99)
+    };
+    assert!(detector.observe(behind).await.unwrap().is_none());
+    assert_eq!(detector.recent(), vec![link(0, 100), link(0, 105)]);
+}
+
+#[tokio::test]
+async fn forks_older_than_the_history_orphan_everything_tracked() {
+    let (_rpc, version, client) = chain().await;
+    let mut detector = ReorgDetector::new(client).with_depth(2);
+    for number in 100..=102 {
+        detector.observe(link(0, number)).await.unwrap();
+    }
+    assert_eq!(detector.recent(), vec![link(0, 101), link(0, 102)]);
+
+    version.store(2, Ordering::SeqCst);
+    let event = detector.observe(link(2, 103)).await.unwrap().unwrap();
+
+    assert_eq!(event.common_ancestor, None);
+    assert_eq!(event.orphaned, vec![link(0, 101), link(0, 102)]);
+    assert_eq!(detector.recent(), vec![link(2, 103)]);
+}
\ No newline at end of file
@@ -0,0 +1,177 @@
+mod common;
+
+use common::MockRpc;
+use ethers::providers::{Middleware, Provider};
+use ethers::types::Bytes;
+use polygon_arbitrage_bot::config::RpcEndpoint;
+use polygon_arbitrage_bot::modules::rpc_pool::{RpcClient, RpcPool};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+fn endpoint(url: &str, weight: f64, max_rps: Option<f64>) -> RpcEndpoint {
+    RpcEndpoint {
+        url: url.to_string(),
+        weight,
+        max_rps,
+    }
+}
+
+fn client(endpoints: &[RpcEndpoint]) -> (RpcPool, RpcClient) {
+    let pool = RpcPool::new(endpoints).unwrap();
+    (pool.clone(), Provider::new(pool))
+}
+
+async fn node() -> (MockRpc, String) {
+    let rpc = MockRpc::new();
+    rpc.on_method("eth_chainId", json!("0x89"));
+    let url = rpc.serve().await;
+    (rpc, url)
+}
+
+/// An endpoint that answers every request with HTTP 429 and a plain-text body.
+async fn throttling() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 4096];
+                while let Ok(n) = stream.read(&mut buffer).await {
+                    if n == 0 {
+                        break;
+                    }
+                    let body = "Too Many Requests";
+                    let reply = format!("HTTP/1.1 429 Too Many Requests\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    format!("http://{}", addr)
+}
+
+/// An address nothing listens on.
+async fn unreachable() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+#[tokio::test]
+async fn spreads_reads_by_weight() {
+    let (heavy, heavy_url) = node().await;
+    let (light, light_url) = node().await;
+    let (pool, client) = client(&[endpoint(&heavy_url, 3.0, None), endpoint(&light_url, 1.0, None)]);
+
+    for _ in 0..40 {
+        client.get_chainid().await.unwrap();
+    }
+
+    let heavy = heavy.count("eth_chainId");
+    let light = light.count("eth_chainId");
+    assert_eq!(heavy + light, 40);
+    assert!((24..=36).contains(&heavy), "heavy endpoint served {} of 40", heavy);
+    let health = pool.health();
+    assert!(health.iter().all(|endpoint| endpoint.healthy && endpoint.failures == 0));
+    assert!(health.iter().all(|endpoint| endpoint.latency_ms.is_some()));
+}
+
+#[tokio::test]
+async fn retries_failed_reads_on_another_endpoint() {
+    let (rpc, url) = node().await;
+    let throttled = throttling().await;
+    let (pool, client) = client(&[endpoint(&throttled, 10.0, None), endpoint(&url, 1.0, None)]);
+
+    assert_eq!(client.get_chainid().await.unwrap().as_u64(), 137);
+    assert_eq!(rpc.count("eth_chainId"), 1);
+
+    let health = pool.health();
+    assert_eq!((health[0].requests, health[0].failures), (1, 1));
+    assert!(!health[0].healthy);
+    assert!(health[0].error_rate > 0.0);
+    assert!(health[1].healthy);
+
+    // The throttled endpoint sits out its cooldown despite its weight
+    client.get_chainid().await.unwrap();
+    assert_eq!(rpc.count("eth_chainId"), 2);
+    assert_eq!(pool.health()[0].requests, 1);
+}
+
+#[tokio::test]
+async fn errors_once_every_endpoint_failed() {
+    let first = unreachable().await;
+    let second = throttling().await;
+    let (pool, client) = client(&[endpoint(&first, 1.0, None), endpoint(&second, 1.0, None)]);
+
+    assert!(client.get_chainid().await.is_err());
+
+    let health = pool.health();
+    assert_eq!(health.iter().map(|endpoint| endpoint.failures).sum::<u64>(), 2);
+    assert!(health.iter().all(|endpoint| !endpoint.healthy));
+}
+
+#[tokio::test]
+async fn node_errors_and_writes_are_not_retried() {
+    let (first, first_url) = node().await;
+    let (second, second_url) = node().await;
+    let (pool, client) = client(&[endpoint(&first_url, 1.0, None), endpoint(&second_url, 1.0, None)]);
+
+    // The node answered, just with an error: another node would say the same
+    assert!(client.get_block_number().await.is_err());
+    assert_eq!(first.count("eth_blockNumber") + second.count("eth_blockNumber"), 1);
+    assert!(pool.health().iter().all(|endpoint| endpoint.failures == 0 && endpoint.healthy));
+
+    // A transaction sent twice could land twice
+    let dead = unreachable().await;
+    let (_, client) = self::client(&[endpoint(&dead, 10.0, None), endpoint(&first_url, 1.0, None)]);
+    assert!(client.send_raw_transaction(Bytes::from(vec![1u8])).await.is_err());
+    assert_eq!(first.count("eth_sendRawTransaction"), 0);
+}
+
+#[tokio::test]
+async fn spends_no_more_than_the_rps_budget() {
+    let (limited, limited_url) = node().await;
+    let (open, open_url) = node().await;
+    let (_, client) = client(&[endpoint(&limited_url, 10.0, Some(2.0)), endpoint(&open_url, 1.0, None)]);
+
+    for _ in 0..10 {
+        client.get_chainid().await.unwrap();
+    }
+
+    // A second's worth went to the preferred endpoint; the rest overflowed
+    assert_eq!(limited.count("eth_chainId"), 2);
+    assert_eq!(open.count("eth_chainId"), 8);
+}
+
+#[tokio::test]
+async fn waits_for_budget_when_every_endpoint_is_spent() {
+    let (rpc, url) = node().await;
+    let (_, client) = client(&[endpoint(&url, 1.0, Some(10.0))]);
+
+    let started = Instant::now();
+    for _ in 0..15 {
+        client.get_chainid().await.unwrap();
+    }
+
+    // 10 in the first burst, 5 more at 10 per second
+    assert!(started.elapsed() >= Duration::from_millis(450), "took {:?}", started.elapsed());
+    assert_eq!(rpc.count("eth_chainId"), 15);
+}
+
+#[test]
+fn health_hides_url_paths() {
+    let pool = RpcPool::new(&[
+        endpoint("https://polygon.example/v2/secret-key", 1.0, None),
+        endpoint("https://polygon.example/v2/other-key", 1.0, Some(5.0)),
+    ])
+    .unwrap();
+
+    let health = pool.health();
+    assert_eq!(health[0].endpoint, "polygon.example");
+    assert_eq!(health[1].endpoint, "polygon.example#2");
+    assert!(health.iter().all(|endpoint| !endpoint.summary().contains("key")));
+    assert!(RpcPool::new(&[]).is_err());
+}
\ No newline at end of file
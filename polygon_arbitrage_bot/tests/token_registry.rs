@@ -0,0 +1,86 @@
+mod common;
+
+use common::{address, MockRpc, USDC, WETH};
+use ethers::providers::Provider;
+use polygon_arbitrage_bot::config::TokenPair;
+use polygon_arbitrage_bot::modules::rpc_pool::RpcPool;
+use polygon_arbitrage_bot::modules::token_registry::{parse_checksummed, TokenRegistry};
+use std::sync::Arc;
+
+const WBTC: &str = "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6";
+const WMATIC: &str = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270";
+
+fn pair(base: &str, base_address: &str, quote: &str, quote_address: &str) -> TokenPair {
+    TokenPair {
+        base: base.to_string(),
+        quote: quote.to_string(),
+        base_address: base_address.to_string(),
+        quote_address: quote_address.to_string(),
+    }
+}
+
+async fn registry(rpc: &MockRpc) -> TokenRegistry {
+    let url = rpc.serve().await;
+    TokenRegistry::new(Arc::new(Provider::new(RpcPool::single(&url).unwrap())))
+}
+
+#[test]
+fn checksums_are_enforced_on_mixed_case_addresses() {
+    assert_eq!(parse_checksummed(WETH).unwrap(), address(WETH));
+    // No checksum to check when the case carries none
+    assert_eq!(parse_checksummed(&WETH.to_lowercase()).unwrap(), address(WETH));
+
+    let mangled = WETH.replace("ceB", "CEb");
+    let error = parse_checksummed(&mangled).unwrap_err().to_string();
+    assert!(error.contains("bad checksum"), "{}", error);
+    assert!(error.contains(WETH), "{}", error);
+
+    assert!(parse_checksummed("0x1234").is_err());
+}
+
+#[tokio::test]
+async fn loads_configured_tokens_once() {
+    let rpc = MockRpc::new();
+    rpc.on_token(address(WETH), "WETH", 18)
+        .on_token(address(USDC), "USDC.e", 6)
+        .on_token(address(WBTC), "WBTC", 8);
+    let tokens = registry(&rpc).await;
+    let pairs = vec![pair("WETH", WETH, "USDC", USDC), pair("WBTC", WBTC, "USDC", USDC)];
+
+    tokens.register_pairs(&pairs).await.unwrap();
+
+    assert_eq!(tokens.len(), 3);
+    let usdc = tokens.get(address(USDC)).unwrap();
+    assert_eq!((usdc.symbol.as_str(), usdc.decimals), ("USDC.e", 6));
+    assert_eq!(usdc.name, "USDC.e Token");
+    assert_eq!(tokens.find("WBTC").unwrap().decimals, 8);
+
+    // Metadata is cached: no further reads
+    let calls = rpc.count("eth_call");
+    assert_eq!(tokens.decimals(address(WETH)).await.unwrap(), 18);
+    tokens.register_pairs(&pairs).await.unwrap();
+    assert_eq!(rpc.count("eth_call"), calls);
+}
+
+#[tokio::test]
+async fn reports_every_misconfigured_token_at_once() {
+    let rpc = MockRpc::new();
+    rpc.on_token(address(WETH), "WETH", 18).on_token(address(WMATIC), "WMATIC", 18);
+    let tokens = registry(&rpc).await;
+    let pairs = vec![
+        // USDC has no contract behind it in the mock
+        pair("WETH", WETH, "USDC", USDC),
+        // WBTC's address points at WMATIC
+        pair("WBTC", WMATIC, "WETH", WETH),
+        pair("DAI", &WETH.replace("ceB", "CEb"), "WETH", WETH),
+    ];
+
+    let error = tokens.register_pairs(&pairs).await.unwrap_err().to_string();
+
+    assert!(error.starts_with("Invalid token configuration"), "{}", error);
+    assert!(error.contains("USDC: 0x2791"), "{}", error);
+    assert!(error.contains("doesn't answer as an ERC-20"), "{}", error);
+    assert!(error.contains("WBTC: 0x0d50") && error.contains("is WMATIC"), "{}", error);
+    assert!(error.contains("DAI: Address") && error.contains("bad checksum"), "{}", error);
+    assert_eq!(error.lines().count(), 4);
+}
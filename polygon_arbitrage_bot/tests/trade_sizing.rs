@@ -0,0 +1,226 @@
+use polygon_arbitrage_bot::modules::amounts::{self, Decimal, TokenAmount};
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageDetector;
+use polygon_arbitrage_bot::modules::price_fetcher::{PriceData, Reserves};
+use polygon_arbitrage_bot::modules::profit_calculator::ProfitCalculator;
+use polygon_arbitrage_bot::modules::trade_sizing::{constant_product_out, price_impact, size_trade, size_two_pool_trade};
+use polygon_arbitrage_bot::modules::v3_math::sqrt_ratio_at_tick;
+use polygon_arbitrage_bot::modules::v3_swap::V3Liquidity;
+use ethers::types::Address;
+use std::collections::BTreeMap;
+
+fn reserves(base: f64, quote: f64) -> Reserves {
+    Reserves {
+        base: TokenAmount::from_f64(base, 18).unwrap(),
+        quote: TokenAmount::from_f64(quote, 18).unwrap(),
+    }
+}
+
+fn round_trip(buy: &Reserves, sell: &Reserves, fee: f64, amount_in: f64) -> f64 {
+    let base_out = constant_product_out(amount_in, buy.quote.to_f64(), buy.base.to_f64(), fee);
+    constant_product_out(base_out, sell.base.to_f64(), sell.quote.to_f64(), fee)
+}
+
+fn quote(dex: &str, pool: Reserves) -> PriceData {
+    PriceData {
+        dex_name: dex.to_string(),
+        token_pair: "WETH/USDC".to_string(),
+        price: pool.quote.to_decimal().unwrap() / pool.base.to_decimal().unwrap(),
+        timestamp: 0,
+        liquidity: pool.quote.to_decimal().unwrap() * Decimal::TWO,
+        fee: 0.003,
+        block: None,
+        reserves: Some(pool),
+        v3_liquidity: None,
+    }
+}
+
+#[test]
+fn constant_product_matches_router_math() {
+    // 1000 in against 100k/100k with 0.3% fee: 997 * 100000 / 100997
+    let out = constant_product_out(1000.0, 100_000.0, 100_000.0, 0.003);
+    assert!((out - 987.158_034_397_06).abs() < 1e-6);
+}
+
+#[test]
+fn closed_form_input_beats_every_other_size() {
+    // WETH at 2500 on one pool and 2550 on the other
+    let buy = reserves(400.0, 1_000_000.0);
+    let sell = reserves(400.0, 1_020_000.0);
+
+    let sizing = size_two_pool_trade(&buy, 0.003, &sell, 0.003).unwrap();
+
+    assert!((sizing.expected_output - round_trip(&buy, &sell, 0.003, sizing.input_amount)).abs() < 1e-6);
+    assert!((sizing.profit - (sizing.expected_output - sizing.input_amount)).abs() < 1e-9);
+    for step in 1..200 {
+        let input = sizing.input_amount * step as f64 / 100.0;
+        let profit = round_trip(&buy, &sell, 0.003, input) - input;
+        assert!(profit <= sizing.profit + 1e-9, "size {} beats the optimum", input);
+    }
+}
+
+#[test]
+fn profit_curve_peaks_at_the_optimum() {
+    let buy = reserves(400.0, 1_000_000.0);
+    let sell = reserves(400.0, 1_020_000.0);
+
+    let sizing = size_two_pool_trade(&buy, 0.003, &sell, 0.003).unwrap();
+
+    let optimum = sizing
+        .profit_curve
+        .iter()
+        .find(|point| (point.input_amount - sizing.input_amount).abs() < 1e-9)
+        .unwrap();
+    assert!(optimum.marginal_profit.abs() < 1e-9);
+    for point in &sizing.profit_curve {
+        assert!(point.profit <= sizing.profit + 1e-9);
+        if point.input_amount < sizing.input_amount {
+            assert!(point.marginal_profit > 0.0);
+        } else if point.input_amount > sizing.input_amount {
+            assert!(point.marginal_profit < 0.0);
+        }
+    }
+}
+
+#[test]
+fn spread_inside_fees_has_no_profitable_size() {
+    // 0.4% apart, two 0.3% fees
+    let buy = reserves(400.0, 1_000_000.0);
+    let sell = reserves(400.0, 1_004_000.0);
+
+    assert!(size_two_pool_trade(&buy, 0.003, &sell, 0.003).is_none());
+    assert!(size_two_pool_trade(&buy, 0.0, &sell, 0.0).is_some());
+}
+
+#[test]
+fn detector_reports_the_optimal_size_and_drops_unprofitable_spreads() {
+    let detector = ArbitrageDetector::new(0.001);
+
+    // A shallow pool caps the trade well below the $1000 default
+    let prices = vec![
+        quote("sushiswap", reserves(0.4, 1_000.0)),
+        quote("quickswap", reserves(400.0, 1_020_000.0)),
+    ];
+    let opportunities = detector.detect_opportunities(&prices).unwrap();
+    assert_eq!(opportunities.len(), 1);
+    let opp = &opportunities[0];
+    let sizing = opp.sizing.as_ref().unwrap();
+    assert_eq!(opp.buy_dex, "sushiswap");
+    assert!((amounts::float(opp.trade_size) - sizing.input_amount).abs() < 1e-9);
+    assert!(opp.trade_size < Decimal::TEN);
+    assert!((amounts::float(opp.profit_usd) - sizing.profit).abs() < 1e-9);
+    // The sized input is carried in the quote token's own units
+    assert_eq!(opp.amount_in, TokenAmount::from_f64(sizing.input_amount, 18));
+
+    let prices = vec![
+        quote("sushiswap", reserves(400.0, 1_000_000.0)),
+        quote("quickswap", reserves(400.0, 1_004_000.0)),
+    ];
+    assert!(detector.detect_opportunities(&prices).unwrap().is_empty());
+}
+
+#[test]
+fn unsized_trades_convert_the_default_size_into_quote_tokens() {
+    let detector = ArbitrageDetector::new(0.001);
+    let matic_weth = |dex: &str, base: f64, quote_amount: f64, state: bool| PriceData {
+        token_pair: "WMATIC/WETH".to_string(),
+        reserves: Some(reserves(base, quote_amount)).filter(|_| state),
+        ..quote(dex, reserves(base, quote_amount))
+    };
+    // The sell side has no pool state, so the $1000 default is not resized
+    let pair = vec![
+        matic_weth("sushiswap", 1_000_000.0, 200.0, true),
+        matic_weth("quickswap", 1_000_000.0, 204.0, false),
+    ];
+
+    // Without a USD price for WETH there is no telling how much WETH $1000 is
+    let opportunity = detector.detect_opportunities(&pair).unwrap().remove(0);
+    assert!(opportunity.sizing.is_none());
+    assert_eq!(opportunity.amount_in, None);
+    assert_eq!(opportunity.buy_impact, None);
+    assert_eq!(opportunity.quote_amount(), None);
+
+    // WETH at $2500: $1000 is 0.4 WETH
+    let mut prices = pair.clone();
+    prices.push(quote("uniswap", reserves(400.0, 1_000_000.0)));
+    let opportunity = detector.detect_opportunities(&prices).unwrap().remove(0);
+    assert_eq!(opportunity.amount_in, TokenAmount::from_f64(0.4, 18));
+    assert_eq!(opportunity.buy_impact, price_impact(&pair[0], false, 0.4));
+    assert!(opportunity.buy_impact.unwrap() < 0.01);
+}
+
+#[test]
+fn v3_legs_are_sized_by_walking_ticks() {
+    // A V3 pool at price 1 with 1000e18 liquidity and no ticks nearby trades
+    // exactly like x*y=k on its 1000/1000 virtual reserves
+    let mut v3 = quote("uniswap_v3_3000", reserves(1_000.0, 1_000.0));
+    v3.v3_liquidity = Some(V3Liquidity {
+        pool: Address::zero(),
+        fee: 3000,
+        tick_spacing: 60,
+        sqrt_price_x96: sqrt_ratio_at_tick(0).unwrap(),
+        tick: 0,
+        liquidity: 1_000_000_000_000_000_000_000,
+        base_is_token0: true,
+        base_decimals: 18,
+        quote_decimals: 18,
+        ticks: BTreeMap::new(),
+        loaded_words: Some((-1, 1)),
+    });
+    let v2 = quote("quickswap", reserves(1_000.0, 1_030.0));
+
+    let walked = size_trade(&v3, &v2).unwrap();
+    let closed_form = size_two_pool_trade(&reserves(1_000.0, 1_000.0), 0.003, &reserves(1_000.0, 1_030.0), 0.003).unwrap();
+
+    assert!((walked.input_amount - closed_form.input_amount).abs() / closed_form.input_amount < 1e-4);
+    assert!((walked.profit - closed_form.profit).abs() / closed_form.profit < 1e-6);
+
+    // A wall of liquidity leaving just below the current tick caps the trade
+    let mut capped = v3.clone();
+    let pool = capped.v3_liquidity.as_mut().unwrap();
+    pool.base_is_token0 = false;
+    pool.ticks.insert(-60, 999_000_000_000_000_000_000);
+    let walked_capped = size_trade(&capped, &v2).unwrap();
+    assert!(walked_capped.input_amount < walked.input_amount);
+}
+
+#[test]
+fn price_impact_grows_with_size_against_the_reserves() {
+    let pool = quote("sushiswap", reserves(400.0, 1_000_000.0));
+
+    // 1000 USDC into 1M USDC of reserves loses ~0.1% to depth
+    let small = price_impact(&pool, false, 1_000.0).unwrap();
+    let expected = 1.0 - constant_product_out(1_000.0, 1_000_000.0, 400.0, 0.003) / (1_000.0 / 2_500.0 * 0.997);
+    assert!((small - expected).abs() < 1e-12);
+    assert!((small - 0.000996).abs() < 1e-5);
+
+    let large = price_impact(&pool, false, 100_000.0).unwrap();
+    assert!(large > small * 50.0);
+
+    let mut no_state = pool.clone();
+    no_state.reserves = None;
+    assert!(price_impact(&no_state, false, 1_000.0).is_none());
+}
+
+#[tokio::test]
+async fn deep_pools_charge_their_price_impact_instead_of_the_flat_allowance() {
+    let detector = ArbitrageDetector::new(0.001);
+    let prices = vec![
+        quote("sushiswap", reserves(4_000.0, 10_000_000.0)),
+        quote("quickswap", reserves(4_000.0, 10_200_000.0)),
+    ];
+    let opportunity = detector.detect_opportunities(&prices).unwrap().remove(0);
+    let (buy_impact, sell_impact) = (opportunity.buy_impact.unwrap(), opportunity.sell_impact.unwrap());
+
+    let calculator = ProfitCalculator::new().with_reserve_tolerance(0.0005);
+    let analysis = calculator.calculate_detailed_profit(&opportunity).await.unwrap();
+
+    let trade_size = amounts::float(opportunity.trade_size);
+    assert!((amounts::float(analysis.price_impact_cost) - trade_size * (buy_impact + sell_impact)).abs() < 1e-9);
+    assert!((amounts::float(analysis.risk_buffer) - 2.0 * trade_size * (1.0005f64.powi(2) - 1.0)).abs() < 1e-9);
+    assert_eq!(analysis.slippage_cost, analysis.price_impact_cost + analysis.risk_buffer);
+    // Quoted-price profit less price impact lands on the sized profit
+    let after_impact = analysis.gross_profit - analysis.price_impact_cost;
+    let profit = opportunity.profit_usd;
+    assert!((after_impact - profit).abs() / profit < Decimal::new(2, 2));
+    assert!(analysis.net_profit > Decimal::ZERO);
+}
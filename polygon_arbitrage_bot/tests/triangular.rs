@@ -0,0 +1,82 @@
+mod common;
+
+use common::quote;
+use polygon_arbitrage_bot::config::TokenPair;
+use polygon_arbitrage_bot::modules::arbitrage_detector::ArbitrageDetector;
+use polygon_arbitrage_bot::modules::triangular::find_triangles;
+
+fn pair(base: &str, quote: &str) -> TokenPair {
+    TokenPair {
+        base: base.to_string(),
+        quote: quote.to_string(),
+        base_address: String::new(),
+        quote_address: String::new(),
+    }
+}
+
+fn pairs() -> Vec<TokenPair> {
+    vec![
+        pair("WETH", "USDC"),
+        pair("WBTC", "USDC"),
+        pair("WMATIC", "USDC"),
+        pair("WMATIC", "WETH"),
+    ]
+}
+
+#[test]
+fn finds_only_fully_connected_triangles() {
+    let triangles = find_triangles(&pairs());
+
+    // WBTC has no cross pair, so it cannot close a triangle
+    assert_eq!(
+        triangles,
+        vec![["USDC".to_string(), "WETH".to_string(), "WMATIC".to_string()]]
+    );
+}
+
+#[test]
+fn detects_mispriced_cross_pair() {
+    let detector = ArbitrageDetector::new(0.001);
+    // WMATIC/WETH is 2% rich versus the two USDC legs
+    let prices = vec![
+        quote("sushiswap", "WETH/USDC", 2500.0, 0.0),
+        quote("quickswap", "WMATIC/USDC", 0.8, 0.0),
+        quote("quickswap", "WMATIC/WETH", 0.8 / 2500.0 * 1.02, 0.0),
+    ];
+
+    let opportunities = detector
+        .detect_triangular_opportunities(&pairs(), &prices)
+        .unwrap();
+
+    assert_eq!(opportunities.len(), 1);
+    let opp = &opportunities[0];
+    assert_eq!(opp.route(), "USDC -> WMATIC -> WETH -> USDC");
+    assert!((opp.profit_percentage - 0.02).abs() < 1e-9);
+    assert!((opp.profit_usd - 20.0).abs() < 1e-6);
+    assert!((opp.start_amount - 1000.0).abs() < 1e-9);
+    assert!((opp.end_amount - 1020.0).abs() < 1e-6);
+
+    let dexes: Vec<&str> = opp.hops.iter().map(|h| h.dex_name.as_str()).collect();
+    assert_eq!(dexes, vec!["quickswap", "quickswap", "sushiswap"]);
+    assert!((opp.hops[0].amount_out - 1250.0).abs() < 1e-9);
+    for window in opp.hops.windows(2) {
+        assert_eq!(window[0].amount_out, window[1].amount_in);
+    }
+}
+
+#[test]
+fn fees_along_the_cycle_remove_thin_edges() {
+    let detector = ArbitrageDetector::new(0.001);
+    // A 0.5% mispricing does not survive three 0.3% swap fees
+    let prices = vec![
+        quote("sushiswap", "WETH/USDC", 2500.0, 0.003),
+        quote("quickswap", "WMATIC/USDC", 0.8, 0.003),
+        quote("quickswap", "WMATIC/WETH", 0.8 / 2500.0 * 1.005, 0.003),
+    ];
+
+    let opportunities = detector
+        .detect_triangular_opportunities(&pairs(), &prices)
+        .unwrap();
+
+    assert!(opportunities.is_empty());
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{address, uint, units, weth_usdc, MockRpc, USDC, WETH};
+use ethers::abi::Token;
+use polygon_arbitrage_bot::config::DexConfig;
+use polygon_arbitrage_bot::modules::amounts::Decimal;
+use polygon_arbitrage_bot::modules::price_source::{DexKind, PriceSourceRegistry};
+
+const ROUTER: &str = "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506";
+const FACTORY: &str = "0xc35DADB65012eC5796536bD9864eD8773aBc74C4";
+const PAIR: &str = "0x34965ba0ac2451A34a0471F04CCa3F990b8dea27";
+
+fn mock_weth_usdc_pair(rpc: &MockRpc) {
+    rpc.on_call(
+        address(FACTORY),
+        "getPair(address,address)",
+        vec![Token::Address(address(PAIR))],
+    )
+    .on_call(
+        address(PAIR),
+        "getReserves()",
+        vec![
+            Token::Uint(units(250_000, 6)),
+            Token::Uint(units(100, 18)),
+            uint(1_700_000_000),
+        ],
+    )
+    .on_call(address(PAIR), "token0()", vec![Token::Address(address(USDC))])
+    .on_token(address(WETH), "WETH", 18)
+    .on_token(address(USDC), "USDC", 6);
+}
+
+fn registry(url: &str) -> PriceSourceRegistry {
+    let dexes = vec![DexConfig {
+        name: "sushiswap".to_string(),
+        kind: DexKind::UniswapV2,
+        router: ROUTER.to_string(),
+        factory: Some(FACTORY.to_string()),
+        fee: None,
+    }];
+
+    PriceSourceRegistry::from_dexes(url, &dexes).unwrap()
+}
+
+#[tokio::test]
+async fn prices_v2_pair_from_reserves() {
+    let rpc = MockRpc::new();
+    mock_weth_usdc_pair(&rpc);
+    let url = rpc.serve().await;
+
+    let registry = registry(&url);
+    let prices = registry
+        .get("sushiswap")
+        .unwrap()
+        .quote(&weth_usdc())
+        .await
+        .unwrap();
+
+    assert_eq!(prices.len(), 1);
+    let price = &prices[0];
+    assert_eq!(price.dex_name, "sushiswap");
+    assert_eq!(price.token_pair, "WETH/USDC");
+    assert_eq!(price.price, Decimal::from(2500));
+    assert_eq!(price.liquidity, Decimal::from(500_000));
+}
+
+#[tokio::test]
+async fn caches_pair_and_decimals_between_reads() {
+    let rpc = MockRpc::new();
+    mock_weth_usdc_pair(&rpc);
+    let url = rpc.serve().await;
+    let registry = registry(&url);
+    let source = registry.get("sushiswap").unwrap();
+
+    source.quote(&weth_usdc()).await.unwrap();
+    let after_first = rpc.count("eth_call");
+    source.quote(&weth_usdc()).await.unwrap();
+
+    // Pair address, token order and decimals are all cached by now
+    assert_eq!(rpc.count("eth_call") - after_first, 1);
+}
+
+#[tokio::test]
+async fn missing_pair_is_an_error() {
+    let rpc = MockRpc::new();
+    rpc.on_call(
+        address(FACTORY),
+        "getPair(address,address)",
+        vec![Token::Address(Default::default())],
+    );
+    let url = rpc.serve().await;
+
+    let registry = registry(&url);
+    let result = registry.get("sushiswap").unwrap().quote(&weth_usdc()).await;
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{address, uint, weth_usdc, MockRpc, USDC, WETH};
+use ethers::abi::Token;
+use ethers::types::U256;
+use polygon_arbitrage_bot::config::DexConfig;
+use polygon_arbitrage_bot::modules::amounts;
+use polygon_arbitrage_bot::modules::price_source::{DexKind, PriceSourceRegistry};
+
+const ROUTER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
+const FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+const POOL_500: &str = "0x45dDa9cb7c25131DF268515131f647d726f50608";
+const POOL_3000: &str = "0x0e44cEb592AcFC5D3F09D996302eB4C499ff8c10";
+const POOL_10000: &str = "0x86f1d8390222A3691C28938eC7404A1661E618e0";
+
+fn sqrt_price_x96(sqrt_price: u64) -> U256 {
+    U256::from(sqrt_price) << 96
+}
+
+fn mock_pool(rpc: &MockRpc, fee: u32, pool: &str, sqrt_price: u64, liquidity: u128) {
+    rpc.on_call_with(
+        address(FACTORY),
+        "getPool(address,address,uint24)",
+        vec![
+            Token::Address(address(WETH)),
+            Token::Address(address(USDC)),
+            uint(fee as u128),
+        ],
+        vec![Token::Address(address(pool))],
+    )
+    .on_call(
+        address(pool),
+        "slot0()",
+        vec![
+            Token::Uint(sqrt_price_x96(sqrt_price)),
+            Token::Int(U256::zero()),
+            uint(0),
+            uint(1),
+            uint(1),
+            uint(0),
+            Token::Bool(true),
+        ],
+    )
+    .on_call(address(pool), "liquidity()", vec![uint(liquidity)])
+    .on_call(address(pool), "token0()", vec![Token::Address(address(USDC))]);
+}
+
+#[tokio::test]
+async fn reports_each_initialized_fee_tier() {
+    let rpc = MockRpc::new();
+    // Tiers without a pool return the zero address
+    rpc.on_call(
+        address(FACTORY),
+        "getPool(address,address,uint24)",
+        vec![Token::Address(Default::default())],
+    )
+    .on_token(address(WETH), "WETH", 18)
+    .on_token(address(USDC), "USDC", 6);
+
+    // USDC is token0, so sqrtPrice = sqrt(1e12 / price_of_weth)
+    mock_pool(&rpc, 500, POOL_500, 20_000, 20_000_000_000_000_000);
+    mock_pool(&rpc, 3000, POOL_3000, 19_900, 1_000_000_000_000_000);
+    mock_pool(&rpc, 10000, POOL_10000, 20_000, 0);
+    let url = rpc.serve().await;
+
+    let dexes = vec![DexConfig {
+        name: "uniswap_v3".to_string(),
+        kind: DexKind::UniswapV3,
+        router: ROUTER.to_string(),
+        factory: Some(FACTORY.to_string()),
+        fee: None,
+    }];
+    let registry = PriceSourceRegistry::from_dexes(&url, &dexes).unwrap();
+
+    let prices = registry
+        .get("uniswap_v3")
+        .unwrap()
+        .quote(&weth_usdc())
+        .await
+        .unwrap();
+
+    let names: Vec<&str> = prices.iter().map(|p| p.dex_name.as_str()).collect();
+    assert_eq!(names, vec!["uniswap_v3_500", "uniswap_v3_3000"]);
+
+    assert!((prices[0].price_f64() - 2500.0).abs() < 1e-6);
+    // L / sqrtPrice = 1e12 raw USDC on each side
+    assert!((amounts::float(prices[0].liquidity) - 2_000_000.0).abs() < 1e-6);
+
+    let expected = 1e12 / (19_900.0f64 * 19_900.0);
+    assert!((prices[1].price_f64() - expected).abs() / expected < 1e-9);
+}
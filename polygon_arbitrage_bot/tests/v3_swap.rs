@@ -0,0 +1,150 @@
+use ethers::types::{Address, U256};
+use polygon_arbitrage_bot::modules::v3_math::{self, compute_swap_step, sqrt_ratio_at_tick, MAX_TICK, MIN_TICK};
+use polygon_arbitrage_bot::modules::v3_swap::V3Liquidity;
+use std::collections::BTreeMap;
+
+fn dec(value: &str) -> U256 {
+    U256::from_dec_str(value).unwrap()
+}
+
+fn e18(value: u64) -> U256 {
+    U256::from(value) * U256::exp10(18)
+}
+
+fn pool(tick: i32, liquidity: u128, ticks: &[(i32, i128)], words: (i16, i16)) -> V3Liquidity {
+    V3Liquidity {
+        pool: Address::zero(),
+        fee: 3000,
+        tick_spacing: 60,
+        sqrt_price_x96: sqrt_ratio_at_tick(tick).unwrap(),
+        tick,
+        liquidity,
+        base_is_token0: true,
+        base_decimals: 18,
+        quote_decimals: 18,
+        ticks: ticks.iter().copied().collect::<BTreeMap<_, _>>(),
+        loaded_words: Some(words),
+    }
+}
+
+#[test]
+fn tick_math_matches_the_contract_bounds() {
+    assert_eq!(sqrt_ratio_at_tick(MIN_TICK).unwrap(), v3_math::min_sqrt_ratio());
+    assert_eq!(sqrt_ratio_at_tick(MAX_TICK).unwrap(), v3_math::max_sqrt_ratio());
+    assert_eq!(sqrt_ratio_at_tick(0).unwrap(), U256::one() << 96);
+    assert!(sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+}
+
+#[test]
+fn tick_at_sqrt_ratio_inverts_tick_math() {
+    for tick in [MIN_TICK, -198060, -1, 0, 1, 60, 198061, MAX_TICK - 1] {
+        let sqrt_price = sqrt_ratio_at_tick(tick).unwrap();
+        assert_eq!(v3_math::tick_at_sqrt_ratio(sqrt_price).unwrap(), tick);
+        // Anything short of the next tick's ratio still rounds down to it
+        let below_next = sqrt_ratio_at_tick(tick + 1).unwrap() - 1;
+        assert_eq!(v3_math::tick_at_sqrt_ratio(below_next).unwrap(), tick);
+    }
+    assert!(v3_math::tick_at_sqrt_ratio(v3_math::max_sqrt_ratio()).is_err());
+}
+
+#[test]
+fn tick_math_tracks_the_real_curve() {
+    // Every bit of |tick| has its own constant, so probe each of them
+    for bit in 0..20 {
+        for tick in [1i32 << bit, -(1i32 << bit)] {
+            let exact = sqrt_ratio_at_tick(tick).unwrap();
+            let expected = 1.0001f64.powf(tick as f64 / 2.0);
+            let actual = exact.to_string().parse::<f64>().unwrap() / 2f64.powi(96);
+            assert!(((actual - expected) / expected).abs() < 1e-9, "tick {}", tick);
+        }
+    }
+}
+
+#[test]
+fn swap_step_capped_at_the_price_target() {
+    // SwapMath.spec: exact amount in that gets capped at price target in one for zero
+    let price = U256::one() << 96; // encodePriceSqrt(1, 1)
+    let target = dec("79623317895830914510639640423"); // encodePriceSqrt(101, 100)
+
+    let step = compute_swap_step(price, target, 2_000_000_000_000_000_000, e18(1), 600).unwrap();
+
+    assert_eq!(step.amount_in, dec("9975124224178055"));
+    assert_eq!(step.fee_amount, dec("5988667735148"));
+    assert_eq!(step.amount_out, dec("9925619580021728"));
+    assert_eq!(step.sqrt_price_next, target);
+}
+
+#[test]
+fn swap_step_fully_spends_the_input() {
+    // SwapMath.spec: exact amount in that is fully spent in one for zero
+    let price = U256::one() << 96;
+    let target = dec("250541448375047931186413801569"); // encodePriceSqrt(1000, 100)
+
+    let step = compute_swap_step(price, target, 2_000_000_000_000_000_000, e18(1), 600).unwrap();
+
+    assert_eq!(step.amount_in, dec("999400000000000000"));
+    assert_eq!(step.fee_amount, dec("600000000000000"));
+    assert_eq!(step.amount_out, dec("666399946655997866"));
+    assert!(step.sqrt_price_next < target);
+}
+
+#[test]
+fn single_range_swap_matches_closed_form() {
+    // No initialized ticks nearby: behaves like x*y=k on virtual reserves
+    let liquidity = 1_000_000_000_000_000_000_000u128; // 1000e18
+    let pool = pool(0, liquidity, &[], (-1, 0));
+
+    let result = pool.simulate_swap(true, e18(1)).unwrap();
+
+    // x = y = L at price 1; out = y - L^2 / (x + in * (1 - fee))
+    let amount_in = 1.0 * 0.997;
+    let expected = 1000.0 - 1000.0 * 1000.0 / (1000.0 + amount_in);
+    let out = result.amount_out.to_string().parse::<f64>().unwrap() / 1e18;
+    assert!(result.filled);
+    assert_eq!(result.amount_in, e18(1));
+    assert_eq!(result.ticks_crossed, 0);
+    assert!((out - expected).abs() < 1e-12);
+}
+
+#[test]
+fn crossing_a_tick_changes_liquidity() {
+    let liquidity = 1_000_000_000_000_000_000_000u128;
+    // Liquidity drops to a tenth below tick -60
+    let thin = pool(0, liquidity, &[(-60, 900_000_000_000_000_000_000)], (-1, 0));
+    let deep = pool(0, liquidity, &[(-60, 0)], (-1, 0));
+
+    let big_trade = e18(10);
+    let thin_result = thin.simulate_swap(true, big_trade).unwrap();
+    let deep_result = deep.simulate_swap(true, big_trade).unwrap();
+
+    assert_eq!(thin_result.ticks_crossed, 1);
+    assert!(thin_result.amount_out < deep_result.amount_out);
+    assert!(thin_result.sqrt_price_x96 < sqrt_ratio_at_tick(-60).unwrap());
+
+    // Up to tick -60 both pools are identical
+    let amount_to_tick = v3_math::amount0_delta(
+        sqrt_ratio_at_tick(-60).unwrap(),
+        U256::one() << 96,
+        liquidity,
+        true,
+    )
+    .unwrap();
+    let small_trade = amount_to_tick / 2;
+    assert_eq!(
+        thin.simulate_swap(true, small_trade).unwrap(),
+        deep.simulate_swap(true, small_trade).unwrap()
+    );
+}
+
+#[test]
+fn stops_at_the_edge_of_loaded_ticks() {
+    let liquidity = 1_000_000_000_000u128;
+    let pool = pool(0, liquidity, &[], (0, 0));
+
+    // Selling token0 moves into word -1, which was never loaded
+    let result = pool.simulate_swap(true, e18(1000)).unwrap();
+
+    assert!(!result.filled);
+    assert!(result.amount_in < e18(1000));
+    assert_eq!(result.sqrt_price_x96, U256::one() << 96);
+}